@@ -0,0 +1,977 @@
+use crate::clock::Clock;
+use crate::highlight::{Highlighter, TokenKind};
+use crate::ipp::{self, IppMessage, IppUri, IppValue};
+use crate::job::Job;
+use crate::printer::PrinterFault;
+
+use genpdf::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::Duration;
+
+//模拟打印时按 PRINT_STEP 分段等待，期间可被取消
+const PRINT_STEP: Duration = Duration::from_millis(100);
+
+//网络打印机的连接和读写超时
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(30);
+
+//向网络打印机发送数据的分块大小，每块之间检查取消和故障
+const SEND_CHUNK: usize = 8192;
+
+//IPP 打印机上任务状态的查询间隔，连续查询失败达到 IPP_MAX_POLL_ERRORS 次时任务失败
+const IPP_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const IPP_MAX_POLL_ERRORS: u32 = 5;
+
+//彩色打印时行号和信息头的颜色
+const LINE_NUMBER_COLOR: style::Color = style::Color::Rgb(150, 150, 150);
+const HEADER_COLOR: style::Color = style::Color::Rgb(30, 80, 180);
+
+//语法高亮的颜色
+const KEYWORD_COLOR: style::Color = style::Color::Rgb(0, 0, 190);
+const TYPE_COLOR: style::Color = style::Color::Rgb(0, 120, 130);
+const FUNCTION_COLOR: style::Color = style::Color::Rgb(120, 80, 0);
+const STRING_COLOR: style::Color = style::Color::Rgb(170, 20, 20);
+const NUMBER_COLOR: style::Color = style::Color::Rgb(10, 130, 80);
+const COMMENT_COLOR: style::Color = style::Color::Rgb(0, 120, 0);
+const META_COLOR: style::Color = style::Color::Rgb(130, 0, 130);
+
+//一次打印的结果
+pub enum PrintOutcome {
+    Done,                      // 打印完成或已取消
+    Interrupted(PrinterFault), // 打印机故障，任务需要重新打印
+}
+
+//打印过程中的控制信息：取消标记、打印机故障和模拟打印耗时
+pub struct PrintControl<'a> {
+    pub cancelled: &'a AtomicBool,
+    pub fault: &'a Mutex<Option<PrinterFault>>,
+    pub clock: &'a dyn Clock,
+    pub duration: Duration, // 按打印速度计算的模拟耗时
+}
+
+impl PrintControl<'_> {
+    //需要停止时返回停止的原因：已取消时为 Done，打印机故障时为 Interrupted
+    pub fn check(&self) -> Option<PrintOutcome> {
+        if self.cancelled.load(Ordering::SeqCst) {
+            return Some(PrintOutcome::Done);
+        }
+        (*self.fault.lock().unwrap()).map(PrintOutcome::Interrupted)
+    }
+
+    //等待模拟打印耗时，被取消或故障时提前返回
+    pub fn wait(&self) -> Option<PrintOutcome> {
+        self.sleep(self.duration)
+    }
+
+    //等待一段时间，被取消或故障时提前返回
+    pub fn sleep(&self, duration: Duration) -> Option<PrintOutcome> {
+        let mut elapsed = Duration::ZERO;
+        while elapsed < duration {
+            if let Some(outcome) = self.check() {
+                return Some(outcome);
+            }
+            let step = PRINT_STEP.min(duration - elapsed);
+            self.clock.sleep(step);
+            elapsed += step;
+        }
+        None
+    }
+}
+
+//打印机的输出方式
+pub trait PrinterBackend: Send + Sync {
+    //打印一份任务，`control` 报告取消或故障时尽快停止并返回对应结果
+    fn print(&self, job: &Job, control: &PrintControl) -> Result<PrintOutcome, String>;
+
+    //后端的简短描述，显示在打印机状态中
+    fn describe(&self) -> String;
+
+    //能查询打印机状态时返回 true，打印机会定期调用 `query_fault`
+    fn reports_state(&self) -> bool {
+        false
+    }
+
+    //查询打印机当前的故障
+    fn query_fault(&self) -> Option<PrinterFault> {
+        None
+    }
+}
+
+//打印机后端配置，printers.json 中每台打印机的 `backend` 字段
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BackendConfig {
+    Pdf {
+        #[serde(default = "default_output_dir")]
+        dir: PathBuf,
+    }, // 生成 PDF 到目录并模拟打印耗时
+    Raw {
+        address: String,
+    }, // JetDirect 风格的 TCP 直连（端口默认 9100）
+    Lpr {
+        address: String, // 端口默认 515
+        #[serde(default = "default_lpr_queue")]
+        queue: String,
+    }, // RFC 1179 LPR 协议
+    Ipp {
+        uri: String, // ipp://host[:port]/path
+    }, // IPP Print-Job
+}
+
+fn default_output_dir() -> PathBuf {
+    PathBuf::from("./output")
+}
+
+fn default_lpr_queue() -> String {
+    "lp".to_string()
+}
+
+impl Default for BackendConfig {
+    fn default() -> Self {
+        BackendConfig::Pdf {
+            dir: default_output_dir(),
+        }
+    }
+}
+
+impl BackendConfig {
+    pub fn build(&self) -> Result<Box<dyn PrinterBackend>, String> {
+        Ok(match self {
+            BackendConfig::Pdf { dir } => Box::new(PdfDirectory { dir: dir.clone() }),
+            BackendConfig::Raw { address } => Box::new(RawSocket {
+                address: with_default_port(address, 9100),
+            }),
+            BackendConfig::Lpr { address, queue } => Box::new(LprClient {
+                address: with_default_port(address, 515),
+                queue: queue.clone(),
+            }),
+            BackendConfig::Ipp { uri } => Box::new(IppClient {
+                target: IppUri::parse(uri)?,
+                uri: uri.clone(),
+                request_id: AtomicU32::new(1),
+            }),
+        })
+    }
+}
+
+//地址中没有端口时补上默认端口
+fn with_default_port(address: &str, port: u16) -> String {
+    // IPv6 地址需要写成 [::1] 的形式
+    let has_port = address.rsplit_once(':').is_some_and(|(host, port)| {
+        (!host.contains(':') || host.ends_with(']')) && port.parse::<u16>().is_ok()
+    });
+    if has_port {
+        address.to_string()
+    } else {
+        format!("{}:{}", address, port)
+    }
+}
+
+//任务要打印的 PDF：附带现成文档时原样读取，否则渲染源代码
+pub fn job_pdf(job: &Job) -> Result<Vec<u8>, String> {
+    match &job.document {
+        Some(document) => {
+            fs::read(&document.path).map_err(|e| format!("读取文档 {} 失败: {}", document.path, e))
+        }
+        None => render_pdf(job),
+    }
+}
+
+//语法高亮的样式：彩色任务用颜色区分，黑白任务用 MapleMono 的粗体和斜体
+fn token_style(kind: TokenKind, color: bool) -> style::Style {
+    let style = style::Style::new();
+    match (kind, color) {
+        (TokenKind::Plain, _) => style,
+        (TokenKind::Keyword, true) => style.bold().with_color(KEYWORD_COLOR),
+        (TokenKind::Type, true) => style.with_color(TYPE_COLOR),
+        (TokenKind::Function, true) => style.with_color(FUNCTION_COLOR),
+        (TokenKind::String, true) => style.with_color(STRING_COLOR),
+        (TokenKind::Number, true) => style.with_color(NUMBER_COLOR),
+        (TokenKind::Comment, true) => style.italic().with_color(COMMENT_COLOR),
+        (TokenKind::Meta, true) => style.with_color(META_COLOR),
+        (TokenKind::Keyword, false) => style.bold(),
+        (TokenKind::Meta, false) => style.bold().italic(),
+        (TokenKind::Comment | TokenKind::String, false) => style.italic(),
+        (TokenKind::Type | TokenKind::Function | TokenKind::Number, false) => style,
+    }
+}
+
+//把源代码渲染为 PDF，彩色任务用颜色区分行号和假脱机添加的信息头；已知语言时做语法高亮
+pub fn render_pdf(job: &Job) -> Result<Vec<u8>, String> {
+    //加载字体
+    let font_family = fonts::from_files("./fonts", "MapleMono", None)
+        .map_err(|e| format!("字体加载失败: {}", e))?;
+
+    let mut doc = Document::new(font_family);
+    doc.set_title(job.file_name.clone());
+
+    //设置页面样式
+    let mut decorator = genpdf::SimplePageDecorator::new();
+    decorator.set_margins(10);
+    doc.set_page_decorator(decorator);
+
+    let mut highlighter = job.language.map(Highlighter::new);
+    for (count, line) in job.file_content.lines().enumerate() {
+        let number = format!("{:>3}: ", count + 1);
+        let header = line.starts_with("\\\\ ");
+        let mut p = elements::Paragraph::default();
+        if job.color {
+            p.push_styled(number, style::Style::new().with_color(LINE_NUMBER_COLOR));
+        } else {
+            p.push(number);
+        }
+        match &mut highlighter {
+            Some(highlighter) if !header => {
+                for (kind, text) in highlighter.line(line) {
+                    p.push_styled(text, token_style(kind, job.color));
+                }
+            }
+            _ if job.color && header => p.push_styled(line, style::Style::new().with_color(HEADER_COLOR)),
+            _ => p.push(line),
+        }
+        doc.push(p);
+    }
+
+    let mut pdf = Vec::new();
+    doc.render(&mut pdf)
+        .map_err(|e| format!("PDF 生成失败: {}", e))?;
+    Ok(pdf)
+}
+
+//连接网络打印机并设置读写超时
+fn connect(address: &str) -> Result<TcpStream, String> {
+    let addr = address
+        .to_socket_addrs()
+        .map_err(|e| format!("无法解析打印机地址 {}: {}", address, e))?
+        .next()
+        .ok_or_else(|| format!("无法解析打印机地址 {}", address))?;
+    let stream = TcpStream::connect_timeout(&addr, NETWORK_TIMEOUT)
+        .map_err(|e| format!("无法连接打印机 {}: {}", address, e))?;
+    stream
+        .set_read_timeout(Some(NETWORK_TIMEOUT))
+        .and_then(|_| stream.set_write_timeout(Some(NETWORK_TIMEOUT)))
+        .map_err(|e| format!("设置超时失败: {}", e))?;
+    Ok(stream)
+}
+
+//分块发送数据，每块之间检查取消和故障
+fn send(stream: &mut TcpStream, data: &[u8], control: &PrintControl) -> Result<Option<PrintOutcome>, String> {
+    for chunk in data.chunks(SEND_CHUNK) {
+        if let Some(outcome) = control.check() {
+            return Ok(Some(outcome));
+        }
+        stream
+            .write_all(chunk)
+            .map_err(|e| format!("发送数据失败: {}", e))?;
+    }
+    Ok(None)
+}
+
+//生成 PDF 到目录，然后等待模拟打印耗时（原有行为）
+pub struct PdfDirectory {
+    pub dir: PathBuf,
+}
+
+impl PrinterBackend for PdfDirectory {
+    fn print(&self, job: &Job, control: &PrintControl) -> Result<PrintOutcome, String> {
+        if let Some(outcome) = control.check() {
+            return Ok(outcome);
+        }
+        let pdf = job_pdf(job)?;
+        fs::create_dir_all(&self.dir).map_err(|e| format!("创建输出目录失败: {}", e))?;
+        let path = self.dir.join(format!("{}.pdf", job.file_name));
+        fs::write(&path, pdf).map_err(|e| format!("写入 PDF 失败: {}", e))?;
+
+        //模拟打印，已取消或中断的任务不保留输出文件
+        match control.wait() {
+            Some(outcome) => {
+                let _ = fs::remove_file(&path);
+                Ok(outcome)
+            }
+            None => Ok(PrintOutcome::Done),
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!("pdf:{}", self.dir.display())
+    }
+}
+
+//JetDirect 风格的直连打印：连接打印机的 9100 端口，发送 PDF 后关闭连接
+pub struct RawSocket {
+    pub address: String,
+}
+
+impl PrinterBackend for RawSocket {
+    fn print(&self, job: &Job, control: &PrintControl) -> Result<PrintOutcome, String> {
+        if let Some(outcome) = control.check() {
+            return Ok(outcome);
+        }
+        let pdf = job_pdf(job)?;
+        let mut stream = connect(&self.address)?;
+        if let Some(outcome) = send(&mut stream, &pdf, control)? {
+            // 中途断开，打印机丢弃不完整的数据
+            return Ok(outcome);
+        }
+        stream
+            .shutdown(std::net::Shutdown::Write)
+            .map_err(|e| format!("关闭连接失败: {}", e))?;
+        // 等待打印机读完并关闭连接，忽略它返回的状态信息
+        let _ = io::copy(&mut stream, &mut io::sink());
+        Ok(PrintOutcome::Done)
+    }
+
+    fn describe(&self) -> String {
+        format!("raw:{}", self.address)
+    }
+}
+
+//RFC 1179 LPR 客户端：向 LPD 的队列提交一个控制文件和一个数据文件
+pub struct LprClient {
+    pub address: String,
+    pub queue: String,
+}
+
+//LPR 中的主机名，控制文件和数据文件的名称都包含它
+const LPR_HOST: &str = "spooling";
+
+impl LprClient {
+    //发送命令或子命令并等待 LPD 的确认（一个值为 0 的字节）
+    fn command(stream: &mut TcpStream, command: &[u8]) -> Result<(), String> {
+        stream
+            .write_all(command)
+            .map_err(|e| format!("发送 LPR 命令失败: {}", e))?;
+        Self::ack(stream)
+    }
+
+    fn ack(stream: &mut TcpStream) -> Result<(), String> {
+        let mut ack = [0u8; 1];
+        stream
+            .read_exact(&mut ack)
+            .map_err(|e| format!("等待 LPD 确认失败: {}", e))?;
+        if ack[0] != 0 {
+            return Err(format!("LPD 拒绝请求（{}）", ack[0]));
+        }
+        Ok(())
+    }
+
+    //控制文件：主机、用户、任务名，以及以保留控制字符方式打印数据文件
+    fn control_file(job: &Job, data_file: &str) -> String {
+        let user: String = clean(&job.team_name, 31);
+        let title: String = clean(&job.file_name, 99);
+        format!(
+            "H{host}\nP{user}\nJ{title}\nN{title}.pdf\nl{data}\nU{data}\n",
+            host = LPR_HOST,
+            user = user,
+            title = title,
+            data = data_file,
+        )
+    }
+}
+
+//控制文件中的字段不能含换行和控制字符，并有长度限制
+fn clean(value: &str, max: usize) -> String {
+    value
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(max)
+        .collect()
+}
+
+impl PrinterBackend for LprClient {
+    fn print(&self, job: &Job, control: &PrintControl) -> Result<PrintOutcome, String> {
+        if let Some(outcome) = control.check() {
+            return Ok(outcome);
+        }
+        let pdf = job_pdf(job)?;
+        let number = job.job_id % 1000;
+        let control_name = format!("cfA{:03}{}", number, LPR_HOST);
+        let data_name = format!("dfA{:03}{}", number, LPR_HOST);
+        let control_file = Self::control_file(job, &data_name);
+
+        let mut stream = connect(&self.address)?;
+        // 02 queue LF：接收任务
+        Self::command(&mut stream, format!("\x02{}\n", self.queue).as_bytes())?;
+
+        // 03 count SP name LF：先发数据文件，控制文件最后发送，LPD 收到控制文件后才开始打印
+        Self::command(&mut stream, format!("\x03{} {}\n", pdf.len(), data_name).as_bytes())?;
+        if let Some(outcome) = send(&mut stream, &pdf, control)? {
+            // 断开连接，LPD 丢弃不完整的任务
+            return Ok(outcome);
+        }
+        Self::command(&mut stream, &[0])?;
+
+        if let Some(outcome) = control.check() {
+            // 01 LF：放弃这个任务
+            let _ = stream.write_all(b"\x01\n");
+            return Ok(outcome);
+        }
+        // 02 count SP name LF：控制文件
+        Self::command(
+            &mut stream,
+            format!("\x02{} {}\n", control_file.len(), control_name).as_bytes(),
+        )?;
+        stream
+            .write_all(control_file.as_bytes())
+            .map_err(|e| format!("发送控制文件失败: {}", e))?;
+        Self::command(&mut stream, &[0])?;
+        Ok(PrintOutcome::Done)
+    }
+
+    fn describe(&self) -> String {
+        format!("lpr:{}/{}", self.address, self.queue)
+    }
+}
+
+//IPP 客户端：用 Print-Job 提交 PDF，用 Get-Job-Attributes 轮询任务状态直到打印结束，
+//用 Get-Printer-Attributes 读取打印机状态
+pub struct IppClient {
+    pub uri: String,
+    target: IppUri,
+    request_id: AtomicU32,
+}
+
+impl IppClient {
+    //带有 printer-uri 的请求
+    fn request(&self, operation: u16) -> IppMessage {
+        let mut request = IppMessage::new(operation, self.request_id.fetch_add(1, Ordering::SeqCst));
+        let mut group = ipp::operation_group();
+        group.add("printer-uri", IppValue::String(ipp::TAG_URI, self.uri.clone()));
+        request.groups.push(group);
+        request
+    }
+
+    fn call(&self, request: &IppMessage) -> Result<IppMessage, String> {
+        let response = ipp::send(connect(&self.target.host)?, &self.target, request)?;
+        if !response.is_successful() {
+            return Err(format!("IPP 打印机返回错误（状态 0x{:04x}）", response.code));
+        }
+        Ok(response)
+    }
+
+    fn job_state(&self, job_id: i32) -> Result<i32, String> {
+        let mut request = self.request(ipp::GET_JOB_ATTRIBUTES);
+        request
+            .group_mut(ipp::OPERATION_ATTRIBUTES)
+            .add("job-id", IppValue::Integer(job_id))
+            .add(
+                "requested-attributes",
+                IppValue::String(ipp::TAG_KEYWORD, "job-state".to_string()),
+            );
+        self.call(&request)?
+            .value("job-state")
+            .and_then(IppValue::as_int)
+            .ok_or_else(|| "IPP 响应缺少 job-state".to_string())
+    }
+
+    fn cancel(&self, job_id: i32, user: &str) {
+        let mut request = self.request(ipp::CANCEL_JOB);
+        request
+            .group_mut(ipp::OPERATION_ATTRIBUTES)
+            .add("job-id", IppValue::Integer(job_id))
+            .add("requesting-user-name", IppValue::String(ipp::TAG_NAME, user.to_string()));
+        if let Err(e) = self.call(&request) {
+            eprintln!("取消 IPP 任务 {} 失败: {}", job_id, e);
+        }
+    }
+}
+
+//按 printer-state-reasons 和 printer-state 判断故障，忽略 -warning 和 -report 级别的原因
+fn fault_from_state(state: Option<i32>, reasons: &[IppValue]) -> Option<PrinterFault> {
+    for reason in reasons.iter().filter_map(IppValue::as_str) {
+        if reason.ends_with("-warning") || reason.ends_with("-report") {
+            continue;
+        }
+        match reason.trim_end_matches("-error") {
+            "media-empty" | "media-needed" => return Some(PrinterFault::PaperOut),
+            "media-jam" => return Some(PrinterFault::Jammed),
+            "offline" | "shutdown" => return Some(PrinterFault::Offline),
+            _ => {}
+        }
+    }
+    (state == Some(ipp::PRINTER_STOPPED)).then_some(PrinterFault::Error)
+}
+
+impl PrinterBackend for IppClient {
+    fn print(&self, job: &Job, control: &PrintControl) -> Result<PrintOutcome, String> {
+        if let Some(outcome) = control.check() {
+            return Ok(outcome);
+        }
+        let pdf = job_pdf(job)?;
+        let user = clean(&job.team_name, 255);
+        let mut request = self.request(ipp::PRINT_JOB);
+        request
+            .group_mut(ipp::OPERATION_ATTRIBUTES)
+            .add("requesting-user-name", IppValue::String(ipp::TAG_NAME, user.clone()))
+            .add("job-name", IppValue::String(ipp::TAG_NAME, clean(&job.file_name, 255)))
+            .add(
+                "document-format",
+                IppValue::String(ipp::TAG_MIME_TYPE, "application/pdf".to_string()),
+            );
+        request.data = pdf;
+        let job_id = self
+            .call(&request)?
+            .value("job-id")
+            .and_then(IppValue::as_int)
+            .ok_or_else(|| "IPP 响应缺少 job-id".to_string())?;
+
+        // 轮询到打印结束；取消或本地故障时取消打印机上的任务
+        let mut errors = 0;
+        loop {
+            if let Some(outcome) = control.sleep(IPP_POLL_INTERVAL) {
+                self.cancel(job_id, &user);
+                return Ok(outcome);
+            }
+            match self.job_state(job_id) {
+                Ok(ipp::JOB_COMPLETED) => return Ok(PrintOutcome::Done),
+                Ok(ipp::JOB_CANCELED) => return Err("任务在打印机上被取消".to_string()),
+                Ok(ipp::JOB_ABORTED) => return Err("打印机中止了任务".to_string()),
+                Ok(_) => errors = 0,
+                Err(e) => {
+                    errors += 1;
+                    if errors >= IPP_MAX_POLL_ERRORS {
+                        return Err(format!("无法查询 IPP 任务状态: {}", e));
+                    }
+                }
+            }
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!("ipp:{}", self.uri)
+    }
+
+    fn reports_state(&self) -> bool {
+        true
+    }
+
+    //连接不上时视为离线
+    fn query_fault(&self) -> Option<PrinterFault> {
+        let mut request = self.request(ipp::GET_PRINTER_ATTRIBUTES);
+        request.group_mut(ipp::OPERATION_ATTRIBUTES).add_all(
+            "requested-attributes",
+            vec![
+                IppValue::String(ipp::TAG_KEYWORD, "printer-state".to_string()),
+                IppValue::String(ipp::TAG_KEYWORD, "printer-state-reasons".to_string()),
+            ],
+        );
+        match self.call(&request) {
+            Ok(response) => fault_from_state(
+                response.value("printer-state").and_then(IppValue::as_int),
+                response.values("printer-state-reasons"),
+            ),
+            Err(_) => Some(PrinterFault::Offline),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{self, SystemClock};
+    use crate::job::{Document, JobStatus};
+    use crate::printer::{PoolConfig, PrinterConfig, PrinterPool, PrinterStatus};
+    use chrono::{TimeZone, Utc};
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::sync::{Arc, mpsc};
+    use std::thread;
+
+    //附带现成文档的任务，文档内容跨越多个发送块，不需要渲染
+    fn job_with_document(name: &str) -> (Job, Vec<u8>) {
+        let pdf: Vec<u8> = b"%PDF-1.4\n"
+            .iter()
+            .copied()
+            .chain((0..SEND_CHUNK * 2 + 100).map(|i| (i % 251) as u8))
+            .collect();
+        let path = std::env::temp_dir().join(format!("spooling-{}-{}.pdf", name, std::process::id()));
+        fs::write(&path, &pdf).unwrap();
+        let mut job = Job::new(
+            7,
+            1,
+            "team1".to_string(),
+            Utc.with_ymd_and_hms(2025, 1, 1, 9, 30, 0).unwrap(),
+            String::new(),
+            false,
+            "A".to_string(),
+        );
+        job.document = Some(Document {
+            path: path.to_string_lossy().into_owned(),
+            pages: 1,
+        });
+        (job, pdf)
+    }
+
+    fn print(backend: &dyn PrinterBackend, job: &Job) -> Result<PrintOutcome, String> {
+        let cancelled = AtomicBool::new(false);
+        let fault = Mutex::new(None);
+        backend.print(
+            job,
+            &PrintControl {
+                cancelled: &cancelled,
+                fault: &fault,
+                clock: &SystemClock,
+                duration: Duration::ZERO,
+            },
+        )
+    }
+
+    //读到换行为止（包括换行）
+    fn read_line(reader: &mut impl BufRead) -> Vec<u8> {
+        let mut line = Vec::new();
+        reader.read_until(b'\n', &mut line).unwrap();
+        line
+    }
+
+    fn read_bytes(reader: &mut impl Read, count: usize) -> Vec<u8> {
+        let mut bytes = vec![0; count];
+        reader.read_exact(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("spooling-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn pdf_directory_writes_the_document() {
+        let (job, pdf) = job_with_document("pdf-dir");
+        let dir = scratch_dir("pdf-dir-out");
+        let backend = PdfDirectory { dir: dir.clone() };
+        assert!(matches!(print(&backend, &job), Ok(PrintOutcome::Done)));
+        assert_eq!(fs::read(dir.join(format!("{}.pdf", job.file_name))).unwrap(), pdf);
+        job.discard_document();
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn pdf_directory_removes_output_of_cancelled_job() {
+        let (job, _) = job_with_document("pdf-dir-cancel");
+        let dir = scratch_dir("pdf-dir-cancel-out");
+        let backend = PdfDirectory { dir: dir.clone() };
+        let output = dir.join(format!("{}.pdf", job.file_name));
+        let cancelled = AtomicBool::new(false);
+        let fault = Mutex::new(None);
+        let outcome = thread::scope(|scope| {
+            scope.spawn(|| {
+                //输出文件写好后，在模拟打印期间取消
+                while !output.exists() {
+                    thread::sleep(Duration::from_millis(5));
+                }
+                cancelled.store(true, Ordering::SeqCst);
+            });
+            backend.print(
+                &job,
+                &PrintControl {
+                    cancelled: &cancelled,
+                    fault: &fault,
+                    clock: &SystemClock,
+                    duration: Duration::from_secs(30),
+                },
+            )
+        });
+        assert!(matches!(outcome, Ok(PrintOutcome::Done)));
+        assert!(!output.exists());
+        job.discard_document();
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn raw_socket_sends_the_document_and_closes() {
+        let (job, pdf) = job_with_document("raw");
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let printer = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = Vec::new();
+            stream.read_to_end(&mut received).unwrap();
+            received
+        });
+
+        let backend = RawSocket { address };
+        assert!(matches!(print(&backend, &job), Ok(PrintOutcome::Done)));
+        assert_eq!(printer.join().unwrap(), pdf);
+        job.discard_document();
+    }
+
+    #[test]
+    fn lpr_client_follows_rfc1179() {
+        let (job, pdf) = job_with_document("lpr");
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let lpd = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut received = Vec::new();
+
+            // 接收任务
+            received.push(read_line(&mut reader));
+            stream.write_all(&[0]).unwrap();
+
+            // 数据文件和控制文件：子命令、确认、内容和结尾的 0 字节、确认
+            for _ in 0..2 {
+                let command = read_line(&mut reader);
+                let count: usize = String::from_utf8_lossy(&command[1..])
+                    .split(' ')
+                    .next()
+                    .unwrap()
+                    .parse()
+                    .unwrap();
+                received.push(command);
+                stream.write_all(&[0]).unwrap();
+                received.push(read_bytes(&mut reader, count + 1));
+                stream.write_all(&[0]).unwrap();
+            }
+            received
+        });
+
+        let backend = LprClient {
+            address,
+            queue: "lp".to_string(),
+        };
+        assert!(matches!(print(&backend, &job), Ok(PrintOutcome::Done)));
+
+        let control_file = format!(
+            "Hspooling\nPteam1\nJ{name}\nN{name}.pdf\nldfA007spooling\nUdfA007spooling\n",
+            name = job.file_name
+        );
+        let mut data_file = pdf.clone();
+        data_file.push(0);
+        let mut control_bytes = control_file.clone().into_bytes();
+        control_bytes.push(0);
+        assert_eq!(
+            lpd.join().unwrap(),
+            vec![
+                b"\x02lp\n".to_vec(),
+                format!("\x03{} dfA007spooling\n", pdf.len()).into_bytes(),
+                data_file,
+                format!("\x02{} cfA007spooling\n", control_file.len()).into_bytes(),
+                control_bytes,
+            ]
+        );
+        job.discard_document();
+    }
+
+    #[test]
+    fn lpr_client_fails_when_lpd_refuses_the_queue() {
+        let (job, _) = job_with_document("lpr-refused");
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let lpd = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let command = read_line(&mut reader);
+            stream.write_all(&[1]).unwrap();
+            command
+        });
+
+        let backend = LprClient {
+            address,
+            queue: "missing".to_string(),
+        };
+        let error = print(&backend, &job).err().unwrap();
+        assert!(error.contains("LPD 拒绝请求"), "{}", error);
+        assert_eq!(lpd.join().unwrap(), b"\x02missing\n");
+        job.discard_document();
+    }
+
+    //模拟的 IPP 打印机：记录收到的请求，依次返回 `job_states` 中的任务状态（最后一个一直返回），
+    //按 `printer` 返回打印机状态和原因
+    struct MockIpp {
+        requests: Mutex<Vec<IppMessage>>,
+        job_states: Mutex<Vec<i32>>,
+        printer: Mutex<(i32, Vec<&'static str>)>,
+    }
+
+    impl MockIpp {
+        fn new(job_states: Vec<i32>) -> Arc<Self> {
+            Arc::new(MockIpp {
+                requests: Mutex::new(Vec::new()),
+                job_states: Mutex::new(job_states),
+                printer: Mutex::new((ipp::PRINTER_IDLE, vec!["none"])),
+            })
+        }
+
+        fn respond(&self, request: &IppMessage) -> IppMessage {
+            let mut response = IppMessage::new(ipp::SUCCESSFUL_OK, request.request_id);
+            response.groups.push(ipp::operation_group());
+            match request.code {
+                ipp::PRINT_JOB => {
+                    response
+                        .group_mut(ipp::JOB_ATTRIBUTES)
+                        .add("job-id", IppValue::Integer(42));
+                }
+                ipp::GET_JOB_ATTRIBUTES => {
+                    let mut states = self.job_states.lock().unwrap();
+                    let state = if states.len() > 1 { states.remove(0) } else { states[0] };
+                    response
+                        .group_mut(ipp::JOB_ATTRIBUTES)
+                        .add("job-state", IppValue::Enum(state));
+                }
+                ipp::GET_PRINTER_ATTRIBUTES => {
+                    let (state, reasons) = self.printer.lock().unwrap().clone();
+                    response
+                        .group_mut(ipp::PRINTER_ATTRIBUTES)
+                        .add("printer-state", IppValue::Enum(state))
+                        .add_all(
+                            "printer-state-reasons",
+                            reasons
+                                .into_iter()
+                                .map(|reason| IppValue::String(ipp::TAG_KEYWORD, reason.to_string()))
+                                .collect(),
+                        );
+                }
+                _ => response.code = ipp::SERVER_ERROR_OPERATION_NOT_SUPPORTED,
+            }
+            response
+        }
+
+        //在 127.0.0.1 的随机端口上接受请求，每个连接处理一个请求，返回打印机的 URI
+        fn serve(self: &Arc<Self>) -> String {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let uri = format!("ipp://{}/ipp/print", listener.local_addr().unwrap());
+            let mock = self.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let mut stream = stream.unwrap();
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut length = 0;
+                    loop {
+                        let line = String::from_utf8(read_line(&mut reader)).unwrap();
+                        if line.trim().is_empty() {
+                            break;
+                        }
+                        if let Some((name, value)) = line.split_once(':')
+                            && name.eq_ignore_ascii_case("content-length")
+                        {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                    let request = IppMessage::decode(&read_bytes(&mut reader, length)).unwrap();
+                    let body = mock.respond(&request).encode();
+                    mock.requests.lock().unwrap().push(request);
+                    write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/ipp\r\nContent-Length: {}\r\n\r\n",
+                        body.len()
+                    )
+                    .unwrap();
+                    stream.write_all(&body).unwrap();
+                }
+            });
+            uri
+        }
+    }
+
+    //通过只有一台 IPP 打印机的打印机池打印，返回回调中依次看到的任务状态
+    fn print_on_ipp(uri: String, job: Job) -> Vec<JobStatus> {
+        let pool = PrinterPool::new(
+            PoolConfig {
+                color_fallback: Default::default(),
+                printers: vec![PrinterConfig {
+                    name: "ipp".to_string(),
+                    color: false,
+                    location: String::new(),
+                    speed: Default::default(),
+                    faults: Default::default(),
+                    backend: BackendConfig::Ipp { uri },
+                }],
+            },
+            clock::system(),
+        );
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let on_event = Arc::new(move |job: &Job| {
+            let _ = sender.lock().unwrap().send(job.status.clone());
+        });
+        assert!(pool.submit_task(job, on_event).is_ok());
+
+        let mut statuses = Vec::new();
+        loop {
+            let status = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+            let finished = status.is_finished();
+            statuses.push(status);
+            if finished {
+                return statuses;
+            }
+        }
+    }
+
+    #[test]
+    fn ipp_client_prints_and_polls_until_completed() {
+        let (job, pdf) = job_with_document("ipp");
+        let mock = MockIpp::new(vec![ipp::JOB_PROCESSING, ipp::JOB_COMPLETED]);
+        let uri = mock.serve();
+
+        let statuses = print_on_ipp(uri.clone(), job.clone());
+        assert_eq!(statuses, vec![JobStatus::Printing, JobStatus::Completed]);
+
+        let requests = mock.requests.lock().unwrap();
+        let operations: Vec<u16> = requests.iter().map(|request| request.code).collect();
+        assert_eq!(
+            operations,
+            vec![ipp::PRINT_JOB, ipp::GET_JOB_ATTRIBUTES, ipp::GET_JOB_ATTRIBUTES]
+        );
+        let print_job = &requests[0];
+        assert_eq!(print_job.data, pdf);
+        let text = |request: &IppMessage, name: &str| {
+            request.value(name).and_then(IppValue::as_str).map(str::to_string)
+        };
+        assert_eq!(text(print_job, "printer-uri"), Some(uri));
+        assert_eq!(text(print_job, "requesting-user-name").as_deref(), Some("team1"));
+        assert_eq!(text(print_job, "job-name"), Some(job.file_name.clone()));
+        assert_eq!(text(print_job, "document-format").as_deref(), Some("application/pdf"));
+        for request in &requests[1..] {
+            assert_eq!(request.value("job-id"), Some(&IppValue::Integer(42)));
+        }
+        job.discard_document();
+    }
+
+    #[test]
+    fn ipp_job_aborted_by_printer_fails() {
+        let (job, _) = job_with_document("ipp-aborted");
+        let mock = MockIpp::new(vec![ipp::JOB_ABORTED]);
+        let statuses = print_on_ipp(mock.serve(), job.clone());
+        assert_eq!(
+            statuses.last(),
+            Some(&JobStatus::Failed {
+                reason: "打印机中止了任务".to_string()
+            })
+        );
+        job.discard_document();
+    }
+
+    #[test]
+    fn ipp_printer_state_maps_to_printer_status() {
+        let mock = MockIpp::new(vec![ipp::JOB_PENDING]);
+        let backend = BackendConfig::Ipp { uri: mock.serve() }.build().unwrap();
+        let cases = [
+            (ipp::PRINTER_STOPPED, vec!["media-empty-error"], Some(PrinterStatus::PaperOut)),
+            (ipp::PRINTER_STOPPED, vec!["media-needed"], Some(PrinterStatus::PaperOut)),
+            (ipp::PRINTER_STOPPED, vec!["media-jam"], Some(PrinterStatus::Jammed)),
+            (ipp::PRINTER_STOPPED, vec!["offline-report", "offline"], Some(PrinterStatus::Offline)),
+            (ipp::PRINTER_IDLE, vec!["media-empty-warning"], None),
+            (ipp::PRINTER_PROCESSING, vec!["none"], None),
+            (ipp::PRINTER_STOPPED, vec!["cover-open"], Some(PrinterStatus::Error)),
+        ];
+        for (state, reasons, expected) in cases {
+            *mock.printer.lock().unwrap() = (state, reasons.clone());
+            let status = backend.query_fault().map(PrinterStatus::from);
+            assert_eq!(status, expected, "{} {:?}", state, reasons);
+        }
+
+        // 连接不上的打印机视为离线
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("ipp://{}/ipp/print", closed.local_addr().unwrap());
+        drop(closed);
+        let backend = BackendConfig::Ipp { uri }.build().unwrap();
+        assert_eq!(backend.query_fault(), Some(PrinterFault::Offline));
+    }
+}
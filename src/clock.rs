@@ -0,0 +1,81 @@
+use chrono::{DateTime, Utc};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// 时间来源：正常运行时使用系统时间，模拟时使用虚拟时间
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+
+    /// 等待一段时间
+    fn sleep(&self, duration: Duration);
+}
+
+/// 系统时间
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// 系统时钟的共享实例
+pub fn system() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+/// 虚拟时间：只在 `advance_to` 时前进，供单线程的离散事件模拟读取当前时间。
+///
+/// 模拟不运行真实的工作线程和 `Printer`，工作线程的搬运和打印由 `sim::Engine` 按事件推进，
+/// 因此虚拟时钟不支持多线程等待：`sleep` 只把时间向前推进 `duration`，不会阻塞
+pub struct VirtualClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl VirtualClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        VirtualClock { now: Mutex::new(start) }
+    }
+
+    /// 前进到指定时刻；时间不会倒退
+    pub fn advance_to(&self, time: DateTime<Utc>) {
+        let mut now = self.now.lock().unwrap();
+        if time > *now {
+            *now = time;
+        }
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap();
+        *now += chrono::Duration::from_std(duration).unwrap_or(chrono::Duration::MAX);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn virtual_time_only_moves_forward() {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let clock = VirtualClock::new(start);
+        clock.advance_to(start + chrono::Duration::seconds(30));
+        clock.advance_to(start + chrono::Duration::seconds(10));
+        assert_eq!(clock.now(), start + chrono::Duration::seconds(30));
+
+        clock.sleep(Duration::from_secs(5));
+        assert_eq!(clock.now(), start + chrono::Duration::seconds(35));
+    }
+}
@@ -0,0 +1,661 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+//源代码语法高亮：手写的逐行词法分析，块注释和多行字符串的状态跨行保留
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Language {
+    C,
+    Cpp,
+    Java,
+    Python,
+    Kotlin,
+    Rust,
+}
+
+impl Language {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "c" | "h" => Some(Language::C),
+            "cpp" | "cc" | "cxx" | "c++" | "hpp" | "hh" | "hxx" => Some(Language::Cpp),
+            "java" => Some(Language::Java),
+            "py" | "pyw" => Some(Language::Python),
+            "kt" | "kts" => Some(Language::Kotlin),
+            "rs" => Some(Language::Rust),
+            _ => None,
+        }
+    }
+
+    //按文件名的扩展名判断，例如 a.cpp
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        let (_, extension) = name.trim().rsplit_once('.')?;
+        Self::from_extension(extension)
+    }
+
+    fn keywords(self) -> &'static [&'static str] {
+        match self {
+            Language::C => C_KEYWORDS,
+            Language::Cpp => CPP_KEYWORDS,
+            Language::Java => JAVA_KEYWORDS,
+            Language::Python => PYTHON_KEYWORDS,
+            Language::Kotlin => KOTLIN_KEYWORDS,
+            Language::Rust => RUST_KEYWORDS,
+        }
+    }
+
+    //内置类型；首字母大写的标识符另按类型处理
+    fn types(self) -> &'static [&'static str] {
+        match self {
+            Language::C => C_TYPES,
+            Language::Cpp => CPP_TYPES,
+            Language::Java => JAVA_TYPES,
+            Language::Python => PYTHON_TYPES,
+            Language::Kotlin => &[],
+            Language::Rust => RUST_TYPES,
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "c" => Ok(Language::C),
+            "cpp" | "c++" | "cxx" => Ok(Language::Cpp),
+            "java" => Ok(Language::Java),
+            "python" | "python3" | "py" => Ok(Language::Python),
+            "kotlin" | "kt" => Ok(Language::Kotlin),
+            "rust" | "rs" => Ok(Language::Rust),
+            _ => Err(format!(
+                "不支持的语言 {}（可选 c、cpp、java、python、kotlin、rust）",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Language::C => "c",
+            Language::Cpp => "cpp",
+            Language::Java => "java",
+            Language::Python => "python",
+            Language::Kotlin => "kotlin",
+            Language::Rust => "rust",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+const C_KEYWORDS: &[&str] = &[
+    "auto", "break", "case", "const", "continue", "default", "do", "else", "enum", "extern", "for",
+    "goto", "if", "inline", "register", "restrict", "return", "sizeof", "static", "struct", "switch",
+    "typedef", "union", "volatile", "while", "true", "false", "NULL",
+];
+const C_TYPES: &[&str] = &[
+    "char", "double", "float", "int", "long", "short", "signed", "unsigned", "void", "bool", "_Bool",
+    "size_t", "FILE", "int8_t", "int16_t", "int32_t", "int64_t", "uint8_t", "uint16_t", "uint32_t",
+    "uint64_t",
+];
+const CPP_KEYWORDS: &[&str] = &[
+    "auto", "break", "case", "const", "continue", "default", "do", "else", "enum", "extern", "for",
+    "goto", "if", "inline", "register", "return", "sizeof", "static", "struct", "switch", "typedef",
+    "union", "volatile", "while", "true", "false", "NULL", "alignas", "alignof", "and", "asm",
+    "catch", "class", "constexpr", "consteval", "constinit", "const_cast", "decltype", "delete",
+    "dynamic_cast", "explicit", "export", "friend", "mutable", "namespace", "new", "noexcept", "not",
+    "nullptr", "operator", "or", "override", "final", "private", "protected", "public",
+    "reinterpret_cast", "static_assert", "static_cast", "template", "this", "throw", "try", "typeid",
+    "typename", "using", "virtual", "concept", "requires", "co_await", "co_return", "co_yield",
+];
+const CPP_TYPES: &[&str] = &[
+    "char", "double", "float", "int", "long", "short", "signed", "unsigned", "void", "bool",
+    "wchar_t", "char8_t", "char16_t", "char32_t", "size_t", "int8_t", "int16_t", "int32_t",
+    "int64_t", "uint8_t", "uint16_t", "uint32_t", "uint64_t", "string", "vector", "map", "set",
+    "unordered_map", "unordered_set", "multiset", "multimap", "pair", "tuple", "queue",
+    "priority_queue", "deque", "stack", "bitset", "array",
+];
+const JAVA_KEYWORDS: &[&str] = &[
+    "abstract", "assert", "break", "case", "catch", "class", "const", "continue", "default", "do",
+    "else", "enum", "extends", "final", "finally", "for", "goto", "if", "implements", "import",
+    "instanceof", "interface", "native", "new", "package", "private", "protected", "public",
+    "return", "static", "strictfp", "super", "switch", "synchronized", "this", "throw", "throws",
+    "transient", "try", "volatile", "while", "var", "record", "yield", "sealed", "permits", "true",
+    "false", "null",
+];
+const JAVA_TYPES: &[&str] = &["boolean", "byte", "char", "double", "float", "int", "long", "short", "void"];
+const PYTHON_KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in",
+    "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with",
+    "yield", "match", "case",
+];
+const PYTHON_TYPES: &[&str] = &[
+    "int", "float", "str", "bool", "list", "dict", "set", "tuple", "bytes", "bytearray", "object",
+    "complex", "frozenset",
+];
+//只收硬关键字和常用的修饰符，get、set、field 等软关键字常用作标识符
+const KOTLIN_KEYWORDS: &[&str] = &[
+    "as", "break", "class", "continue", "do", "else", "false", "for", "fun", "if", "in",
+    "interface", "is", "null", "object", "package", "return", "super", "this", "throw", "true",
+    "try", "typealias", "typeof", "val", "var", "when", "while", "by", "catch", "constructor",
+    "finally", "import", "init", "where", "abstract", "annotation", "companion", "const",
+    "crossinline", "data", "enum", "external", "final", "infix", "inline", "inner", "internal",
+    "lateinit", "noinline", "open", "operator", "out", "override", "private", "protected", "public",
+    "reified", "sealed", "suspend", "tailrec", "vararg",
+];
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe",
+    "use", "where", "while",
+];
+const RUST_TYPES: &[&str] = &[
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize", "f32",
+    "f64", "bool", "char", "str",
+];
+
+//记号种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Plain,
+    Keyword,
+    Type,
+    Function, // 紧跟左括号的标识符
+    String,   // 字符串和字符字面量
+    Number,
+    Comment,
+    Meta, // 预处理指令、注解、装饰器、Rust 属性和宏
+}
+
+//跨行保留的状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum State {
+    Code,
+    BlockComment(u32), // 嵌套深度，只有 Rust 的块注释可以嵌套
+    String {
+        close: String,   // 结束定界符
+        escapes: bool,   // 反斜杠是否转义
+        multiline: bool, // 为 false 时在行尾结束
+    },
+}
+
+pub struct Highlighter {
+    language: Language,
+    state: State,
+    include: bool, // 刚读到 #include，接下来的 <...> 是文件名
+}
+
+impl Highlighter {
+    pub fn new(language: Language) -> Self {
+        Highlighter {
+            language,
+            state: State::Code,
+            include: false,
+        }
+    }
+
+    //把一行切分为记号，相邻的同类记号合并
+    pub fn line<'a>(&mut self, line: &'a str) -> Vec<(TokenKind, &'a str)> {
+        let mut tokens: Vec<(TokenKind, usize, usize)> = Vec::new();
+        let mut pos = 0;
+        self.include = false;
+        while pos < line.len() {
+            let rest = &line[pos..];
+            let (kind, len) = match &self.state {
+                State::Code => self.code_token(line, pos),
+                State::BlockComment(depth) => {
+                    let (depth, len) = block_comment(rest, *depth, self.language == Language::Rust);
+                    if depth == 0 {
+                        self.state = State::Code;
+                    } else {
+                        self.state = State::BlockComment(depth);
+                    }
+                    (TokenKind::Comment, len)
+                }
+                State::String { close, escapes, .. } => match string_end(rest, close, *escapes) {
+                    Some(len) => {
+                        self.state = State::Code;
+                        (TokenKind::String, len)
+                    }
+                    None => (TokenKind::String, rest.len()),
+                },
+            };
+            match tokens.last_mut() {
+                Some((last, _, end)) if *last == kind => *end = pos + len,
+                _ => tokens.push((kind, pos, pos + len)),
+            }
+            pos += len;
+        }
+        if let State::String { multiline: false, .. } = self.state {
+            self.state = State::Code;
+        }
+        tokens
+            .into_iter()
+            .map(|(kind, start, end)| (kind, &line[start..end]))
+            .collect()
+    }
+
+    //代码状态下的一个记号，返回种类和字节长度；进入注释或字符串时只返回开头部分并切换状态
+    fn code_token(&mut self, line: &str, pos: usize) -> (TokenKind, usize) {
+        let rest = &line[pos..];
+        let c = rest.chars().next().unwrap();
+        let at_line_start = line[..pos].trim().is_empty();
+        let language = self.language;
+
+        if c.is_whitespace() {
+            let len = rest.find(|c: char| !c.is_whitespace()).unwrap_or(rest.len());
+            return (TokenKind::Plain, len);
+        }
+        let include = std::mem::take(&mut self.include);
+
+        // 注释
+        if language == Language::Python {
+            if c == '#' {
+                return (TokenKind::Comment, rest.len());
+            }
+        } else if rest.starts_with("//") {
+            return (TokenKind::Comment, rest.len());
+        } else if rest.starts_with("/*") {
+            self.state = State::BlockComment(1);
+            return (TokenKind::Comment, 2);
+        }
+
+        // 预处理指令、属性、注解和装饰器
+        match (language, c) {
+            (Language::C | Language::Cpp, '#') if at_line_start => {
+                let after = rest[1..].trim_start();
+                let directive = identifier_len(after);
+                self.include = &after[..directive] == "include";
+                return (TokenKind::Meta, rest.len() - after.len() + directive);
+            }
+            (Language::C | Language::Cpp, '<') if include => {
+                self.state = State::String {
+                    close: ">".to_string(),
+                    escapes: false,
+                    multiline: false,
+                };
+                return (TokenKind::String, 1);
+            }
+            (Language::Rust, '#') if rest[1..].starts_with('[') || rest[1..].starts_with("![") => {
+                return (TokenKind::Meta, attribute_len(rest));
+            }
+            (Language::Java | Language::Kotlin, '@') if identifier_len(&rest[1..]) > 0 => {
+                return (TokenKind::Meta, 1 + identifier_len(&rest[1..]));
+            }
+            (Language::Python, '@') if at_line_start => {
+                let name = rest[1..]
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+                    .unwrap_or(rest.len() - 1);
+                return (TokenKind::Meta, 1 + name);
+            }
+            _ => {}
+        }
+
+        // 字符串，可能带前缀
+        let word = identifier_len(rest);
+        if let Some((open, state)) = self.string_start(&rest[..word], &rest[word..]) {
+            self.state = state;
+            return (TokenKind::String, word + open);
+        }
+
+        // 数字；.5 这样的小数要和 1..5、t.0 区分
+        let next_is_digit = rest[c.len_utf8()..].starts_with(|c: char| c.is_ascii_digit());
+        let after_operand = line[..pos]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | ')' | ']'));
+        if c.is_ascii_digit() || (c == '.' && next_is_digit && !after_operand) {
+            return (TokenKind::Number, number_len(rest));
+        }
+
+        // 标识符
+        if word > 0 {
+            let name = &rest[..word];
+            let after = &rest[word..];
+            // Rust 的原始标识符 r#type
+            if language == Language::Rust && name == "r" && after.starts_with('#') {
+                let raw = identifier_len(&after[1..]);
+                if raw > 0 {
+                    return (TokenKind::Plain, word + 1 + raw);
+                }
+            }
+            let kind = if language.keywords().contains(&name) {
+                TokenKind::Keyword
+            } else if language == Language::Rust && after.starts_with('!') && !after.starts_with("!=") {
+                return (TokenKind::Meta, word + 1);
+            } else if language.types().contains(&name) || is_type_name(name) {
+                TokenKind::Type
+            } else if after.starts_with('(') {
+                TokenKind::Function
+            } else {
+                TokenKind::Plain
+            };
+            return (kind, word);
+        }
+
+        (TokenKind::Plain, c.len_utf8())
+    }
+
+    //`prefix` 为引号前的标识符（可以为空），`rest` 从引号开始；是字符串时返回前缀之后开头部分的长度和新状态
+    fn string_start(&self, prefix: &str, rest: &str) -> Option<(usize, State)> {
+        let quote = rest.chars().next()?;
+        let string = |open: usize, close: &str, escapes: bool, multiline: bool| {
+            let state = State::String {
+                close: close.to_string(),
+                escapes,
+                multiline,
+            };
+            Some((open, state))
+        };
+        match self.language {
+            Language::Python => {
+                let lower = prefix.to_ascii_lowercase();
+                if !["", "r", "u", "b", "f", "br", "rb", "fr", "rf"].contains(&lower.as_str()) {
+                    return None;
+                }
+                let raw = lower.contains('r');
+                for triple in ["\"\"\"", "'''"] {
+                    if rest.starts_with(triple) {
+                        return string(3, triple, !raw, true);
+                    }
+                }
+                match quote {
+                    '"' | '\'' => string(1, &rest[..1], !raw, false),
+                    _ => None,
+                }
+            }
+            Language::Java | Language::Kotlin => {
+                if !prefix.is_empty() {
+                    return None;
+                }
+                if rest.starts_with("\"\"\"") {
+                    // Kotlin 的三引号字符串不处理转义
+                    return string(3, "\"\"\"", self.language == Language::Java, true);
+                }
+                match quote {
+                    '"' | '\'' => string(1, &rest[..1], true, false),
+                    _ => None,
+                }
+            }
+            Language::C | Language::Cpp => {
+                if self.language == Language::Cpp
+                    && ["R", "LR", "uR", "UR", "u8R"].contains(&prefix)
+                    && quote == '"'
+                {
+                    // R"delim( ... )delim"
+                    let delimiter = rest[1..].find('(').filter(|&len| len <= 16)?;
+                    let close = format!("){}\"", &rest[1..1 + delimiter]);
+                    return string(delimiter + 2, &close, false, true);
+                }
+                if !["", "L", "u", "U", "u8"].contains(&prefix) {
+                    return None;
+                }
+                match quote {
+                    '"' | '\'' => string(1, &rest[..1], true, false),
+                    _ => None,
+                }
+            }
+            Language::Rust => match (prefix, quote) {
+                ("" | "b", '"') => string(1, "\"", true, true),
+                ("r" | "br", '"' | '#') => {
+                    let hashes = rest.find(|c: char| c != '#')?;
+                    if !rest[hashes..].starts_with('"') {
+                        return None; // 原始标识符 r#name
+                    }
+                    let close = format!("\"{}", "#".repeat(hashes));
+                    string(hashes + 1, &close, false, true)
+                }
+                // 字符字面量，和生命周期 'a 区分
+                ("" | "b", '\'') => {
+                    let mut chars = rest[1..].chars();
+                    let first = chars.next()?;
+                    if first == '\\' || chars.next() == Some('\'') {
+                        string(1, "'", true, false)
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            },
+        }
+    }
+}
+
+//块注释中的一段，返回剩余深度和长度
+fn block_comment(rest: &str, mut depth: u32, nested: bool) -> (u32, usize) {
+    let bytes = rest.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i..].starts_with(b"*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return (0, i);
+            }
+        } else if nested && bytes[i..].starts_with(b"/*") {
+            depth += 1;
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+    (depth, rest.len())
+}
+
+//字符串结束定界符之后的位置，本行没有结束时为 None
+fn string_end(rest: &str, close: &str, escapes: bool) -> Option<usize> {
+    let bytes = rest.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if escapes && bytes[i] == b'\\' {
+            i += 2;
+        } else if bytes[i..].starts_with(close.as_bytes()) {
+            return Some(i + close.len());
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+fn identifier_len(s: &str) -> usize {
+    if !s.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        return 0;
+    }
+    s.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(s.len())
+}
+
+//Rust 属性 #[...] 的长度，到匹配的右方括号或行尾
+fn attribute_len(s: &str) -> usize {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    s.len()
+}
+
+//数字字面量：字母、数字、下划线、C++ 的数字分隔符，小数点后必须是数字，指数后可以有正负号
+fn number_len(s: &str) -> usize {
+    let bytes = s.as_bytes();
+    let hex = bytes.len() > 1 && bytes[0] == b'0' && matches!(bytes[1], b'x' | b'X');
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let next_is_digit = bytes.get(i + 1).is_some_and(u8::is_ascii_digit);
+        let next_is_hex_digit = bytes.get(i + 1).is_some_and(u8::is_ascii_hexdigit);
+        let exponent = i > 0 && !hex && matches!(bytes[i - 1], b'e' | b'E');
+        let part = c.is_ascii_alphanumeric()
+            || c == b'_'
+            || (c == b'\'' && if hex { next_is_hex_digit } else { next_is_digit })
+            || (c == b'.' && next_is_digit)
+            || (matches!(c, b'+' | b'-') && exponent && next_is_digit);
+        if !part {
+            break;
+        }
+        i += 1;
+    }
+    i
+}
+
+//首字母大写且含小写字母的标识符按类型处理，全大写的常量和宏除外
+fn is_type_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_uppercase()) && name.chars().any(|c| c.is_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TokenKind::*;
+    use super::*;
+
+    type Case = (&'static [&'static str], &'static [&'static [(TokenKind, &'static str)]]);
+
+    //逐行高亮，和每行期望的记号比较
+    fn check(language: Language, cases: &[Case]) {
+        for (lines, expected) in cases {
+            let mut highlighter = Highlighter::new(language);
+            let tokens: Vec<_> = lines.iter().map(|line| highlighter.line(line)).collect();
+            assert_eq!(tokens, *expected, "{lines:?}");
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn rust_tokens() {
+        check(Language::Rust, &[
+            // 嵌套块注释跨行
+            (&["a /* x /* y */ z", "end */ b"], &[
+                &[(Plain, "a "), (Comment, "/* x /* y */ z")],
+                &[(Comment, "end */"), (Plain, " b")],
+            ]),
+            // 原始字符串
+            (&[r###"let s = r#"a "q" b"#;"###, r###"r##"x "# y"##"###], &[
+                &[(Keyword, "let"), (Plain, " s = "), (String, r###"r#"a "q" b"#"###), (Plain, ";")],
+                &[(String, r###"r##"x "# y"##"###)],
+            ]),
+            // 原始标识符不是字符串
+            (&["r#type"], &[
+                &[(Plain, "r#type")],
+            ]),
+            // 字符字面量和生命周期
+            (&["fn f<'a>(x: &'a str) -> char { 'a' }", r"['\n', b'x', 'é']"], &[
+                &[(Keyword, "fn"), (Plain, " f<'a>(x: &'a "), (Type, "str"), (Plain, ") -> "),
+                  (Type, "char"), (Plain, " { "), (String, "'a'"), (Plain, " }")],
+                &[(Plain, "["), (String, r"'\n'"), (Plain, ", "), (String, "b'x'"), (Plain, ", "),
+                  (String, "'é'"), (Plain, "]")],
+            ]),
+            // 普通字符串可以跨行
+            (&[r#"let s = "one"#, r#"two";"#], &[
+                &[(Keyword, "let"), (Plain, " s = "), (String, r#""one"#)],
+                &[(String, r#"two""#), (Plain, ";")],
+            ]),
+            // 转义之后的非 ASCII 字符
+            (&[r#"let s = "\é中文\"文"; // 注释"#, r#"变量("\\"é)"#], &[
+                &[(Keyword, "let"), (Plain, " s = "), (String, r#""\é中文\"文""#), (Plain, "; "),
+                  (Comment, "// 注释")],
+                &[(Function, "变量"), (Plain, "("), (String, r#""\\""#), (Plain, "é)")],
+            ]),
+        ]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn python_tokens() {
+        check(Language::Python, &[
+            // 三引号字符串跨行，其中的单个引号不结束字符串
+            (&[r#"s = """one "two""#, r#"three""" + 'x'  # done"#], &[
+                &[(Plain, "s = "), (String, r#""""one "two""#)],
+                &[(String, r#"three""""#), (Plain, " + "), (String, "'x'"), (Plain, "  "),
+                  (Comment, "# done")],
+            ]),
+            // 原始字符串中反斜杠不转义，普通单引号字符串在行尾结束
+            (&[r"p = r'\' + '''a", r"b\''''", "'open", "x = 1"], &[
+                &[(Plain, "p = "), (String, r"r'\'"), (Plain, " + "), (String, "'''a")],
+                &[(String, r"b\''''")],
+                &[(String, "'open")],
+                &[(Plain, "x = "), (Number, "1")],
+            ]),
+            (&["@app.route(\"/\")", "def f(x):"], &[
+                &[(Meta, "@app.route"), (Plain, "("), (String, "\"/\""), (Plain, ")")],
+                &[(Keyword, "def"), (Plain, " "), (Function, "f"), (Plain, "(x):")],
+            ]),
+            (&["s = '\\é' + \"中\\文\"  # 注释"], &[
+                &[(Plain, "s = "), (String, "'\\é'"), (Plain, " + "), (String, "\"中\\文\""),
+                  (Plain, "  "), (Comment, "# 注释")],
+            ]),
+        ]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn cpp_tokens() {
+        check(Language::Cpp, &[
+            // 数字分隔符和字符字面量
+            (&["int n = 1'000'000 + 0x1F'FF;", "char c = '\\'';"], &[
+                &[(Type, "int"), (Plain, " n = "), (Number, "1'000'000"), (Plain, " + "),
+                  (Number, "0x1F'FF"), (Plain, ";")],
+                &[(Type, "char"), (Plain, " c = "), (String, "'\\''"), (Plain, ";")],
+            ]),
+            (&[r#"auto s = R"x(a )" b)x";"#, "#include <vector>"], &[
+                &[(Keyword, "auto"), (Plain, " s = "), (String, r#"R"x(a )" b)x""#), (Plain, ";")],
+                &[(Meta, "#include"), (Plain, " "), (String, "<vector>")],
+            ]),
+        ]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn c_tokens() {
+        check(Language::C, &[
+            // C 的块注释不嵌套
+            (&["/* x /* y */ z"], &[
+                &[(Comment, "/* x /* y */"), (Plain, " z")],
+            ]),
+            (&["double d = 1.5e-3 + .5;"], &[
+                &[(Type, "double"), (Plain, " d = "), (Number, "1.5e-3"), (Plain, " + "),
+                  (Number, ".5"), (Plain, ";")],
+            ]),
+            // 转义之后的非 ASCII 字符，字符串在行尾结束
+            (&["puts(\"\\é", "ok\");"], &[
+                &[(Function, "puts"), (Plain, "("), (String, "\"\\é")],
+                &[(Plain, "ok"), (String, "\");")],
+            ]),
+        ]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn java_tokens() {
+        check(Language::Java, &[
+            (&["@Override", "String s = \"\"\"", "  \\\"\"\" x", "  \"\"\";"], &[
+                &[(Meta, "@Override")],
+                &[(Type, "String"), (Plain, " s = "), (String, "\"\"\"")],
+                &[(String, "  \\\"\"\" x")],
+                &[(String, "  \"\"\""), (Plain, ";")],
+            ]),
+        ]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn kotlin_tokens() {
+        check(Language::Kotlin, &[
+            // Kotlin 的三引号字符串不处理转义
+            (&["val s = \"\"\"a\\\"\"\" + x"], &[
+                &[(Keyword, "val"), (Plain, " s = "), (String, "\"\"\"a\\\"\"\""), (Plain, " + x")],
+            ]),
+        ]);
+    }
+}
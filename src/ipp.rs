@@ -0,0 +1,486 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+
+//IPP/1.1 消息的编码和解码（RFC 8010），以及通过 HTTP 发送 IPP 请求的最小客户端
+
+//属性组标签
+pub const OPERATION_ATTRIBUTES: u8 = 0x01;
+pub const JOB_ATTRIBUTES: u8 = 0x02;
+pub const END_OF_ATTRIBUTES: u8 = 0x03;
+pub const PRINTER_ATTRIBUTES: u8 = 0x04;
+
+//值标签
+pub const TAG_INTEGER: u8 = 0x21;
+pub const TAG_BOOLEAN: u8 = 0x22;
+pub const TAG_ENUM: u8 = 0x23;
+pub const TAG_TEXT: u8 = 0x41;
+pub const TAG_NAME: u8 = 0x42;
+pub const TAG_KEYWORD: u8 = 0x44;
+pub const TAG_URI: u8 = 0x45;
+pub const TAG_CHARSET: u8 = 0x47;
+pub const TAG_LANGUAGE: u8 = 0x48;
+pub const TAG_MIME_TYPE: u8 = 0x49;
+
+//操作
+pub const PRINT_JOB: u16 = 0x0002;
+pub const VALIDATE_JOB: u16 = 0x0004;
+pub const CANCEL_JOB: u16 = 0x0008;
+pub const GET_JOB_ATTRIBUTES: u16 = 0x0009;
+pub const GET_JOBS: u16 = 0x000A;
+pub const GET_PRINTER_ATTRIBUTES: u16 = 0x000B;
+
+//状态码
+pub const SUCCESSFUL_OK: u16 = 0x0000;
+pub const CLIENT_ERROR_BAD_REQUEST: u16 = 0x0400;
+pub const CLIENT_ERROR_NOT_AUTHORIZED: u16 = 0x0403;
+pub const CLIENT_ERROR_NOT_POSSIBLE: u16 = 0x0404;
+pub const CLIENT_ERROR_NOT_FOUND: u16 = 0x0406;
+pub const CLIENT_ERROR_DOCUMENT_FORMAT_NOT_SUPPORTED: u16 = 0x040A;
+pub const CLIENT_ERROR_COMPRESSION_NOT_SUPPORTED: u16 = 0x040F;
+pub const CLIENT_ERROR_DOCUMENT_FORMAT_ERROR: u16 = 0x0410;
+pub const SERVER_ERROR_INTERNAL_ERROR: u16 = 0x0500;
+pub const SERVER_ERROR_OPERATION_NOT_SUPPORTED: u16 = 0x0501;
+pub const SERVER_ERROR_VERSION_NOT_SUPPORTED: u16 = 0x0503;
+pub const SERVER_ERROR_NOT_ACCEPTING_JOBS: u16 = 0x0506;
+pub const SERVER_ERROR_BUSY: u16 = 0x0507;
+
+//job-state 的取值
+pub const JOB_PENDING: i32 = 3;
+pub const JOB_PROCESSING: i32 = 5;
+pub const JOB_CANCELED: i32 = 7;
+pub const JOB_ABORTED: i32 = 8;
+pub const JOB_COMPLETED: i32 = 9;
+
+//printer-state 的取值
+pub const PRINTER_IDLE: i32 = 3;
+pub const PRINTER_PROCESSING: i32 = 4;
+pub const PRINTER_STOPPED: i32 = 5;
+
+//属性值，未识别的类型保留原始字节
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IppValue {
+    Integer(i32),
+    Boolean(bool),
+    Enum(i32),
+    String(u8, String), // 各种字符串类型，第一项为值标签
+    Other(u8, Vec<u8>),
+}
+
+impl IppValue {
+    pub fn as_int(&self) -> Option<i32> {
+        match self {
+            IppValue::Integer(value) | IppValue::Enum(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            IppValue::String(_, value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn encode(&self) -> (u8, Vec<u8>) {
+        match self {
+            IppValue::Integer(value) => (TAG_INTEGER, value.to_be_bytes().to_vec()),
+            IppValue::Boolean(value) => (TAG_BOOLEAN, vec![u8::from(*value)]),
+            IppValue::Enum(value) => (TAG_ENUM, value.to_be_bytes().to_vec()),
+            IppValue::String(tag, value) => (*tag, value.as_bytes().to_vec()),
+            IppValue::Other(tag, bytes) => (*tag, bytes.clone()),
+        }
+    }
+
+    fn decode(tag: u8, bytes: &[u8]) -> IppValue {
+        let int = || bytes.try_into().ok().map(i32::from_be_bytes);
+        match tag {
+            TAG_INTEGER => int().map_or_else(|| IppValue::Other(tag, bytes.to_vec()), IppValue::Integer),
+            TAG_ENUM => int().map_or_else(|| IppValue::Other(tag, bytes.to_vec()), IppValue::Enum),
+            TAG_BOOLEAN if bytes.len() == 1 => IppValue::Boolean(bytes[0] != 0),
+            // 0x40-0x5f 为字符串类型，withLanguage 类型（0x35、0x36）保留原始字节
+            0x40..=0x5f => IppValue::String(tag, String::from_utf8_lossy(bytes).into_owned()),
+            _ => IppValue::Other(tag, bytes.to_vec()),
+        }
+    }
+}
+
+//一个属性，可以有多个值
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attribute {
+    pub name: String,
+    pub values: Vec<IppValue>,
+}
+
+//属性组
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeGroup {
+    pub tag: u8,
+    pub attributes: Vec<Attribute>,
+}
+
+impl AttributeGroup {
+    pub fn new(tag: u8) -> Self {
+        AttributeGroup {
+            tag,
+            attributes: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, name: &str, value: IppValue) -> &mut Self {
+        self.attributes.push(Attribute {
+            name: name.to_string(),
+            values: vec![value],
+        });
+        self
+    }
+
+    pub fn add_all(&mut self, name: &str, values: Vec<IppValue>) -> &mut Self {
+        self.attributes.push(Attribute {
+            name: name.to_string(),
+            values,
+        });
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Attribute> {
+        self.attributes.iter().find(|attribute| attribute.name == name)
+    }
+}
+
+//IPP 请求或响应：请求中 `code` 为操作，响应中为状态码
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IppMessage {
+    pub version: (u8, u8),
+    pub code: u16,
+    pub request_id: u32,
+    pub groups: Vec<AttributeGroup>,
+    pub data: Vec<u8>, // 属性之后的文档数据
+}
+
+//请求开头必需的 attributes-charset 和 attributes-natural-language
+pub fn operation_group() -> AttributeGroup {
+    let mut group = AttributeGroup::new(OPERATION_ATTRIBUTES);
+    group
+        .add("attributes-charset", IppValue::String(TAG_CHARSET, "utf-8".to_string()))
+        .add(
+            "attributes-natural-language",
+            IppValue::String(TAG_LANGUAGE, "en".to_string()),
+        );
+    group
+}
+
+impl IppMessage {
+    pub fn new(code: u16, request_id: u32) -> Self {
+        IppMessage {
+            version: (1, 1),
+            code,
+            request_id,
+            groups: Vec::new(),
+            data: Vec::new(),
+        }
+    }
+
+    pub fn group_mut(&mut self, tag: u8) -> &mut AttributeGroup {
+        if let Some(index) = self.groups.iter().position(|group| group.tag == tag) {
+            return &mut self.groups[index];
+        }
+        self.groups.push(AttributeGroup::new(tag));
+        self.groups.last_mut().unwrap()
+    }
+
+    //在所有属性组中查找属性的第一个值
+    pub fn value(&self, name: &str) -> Option<&IppValue> {
+        self.groups
+            .iter()
+            .find_map(|group| group.get(name))
+            .and_then(|attribute| attribute.values.first())
+    }
+
+    //在所有属性组中查找属性的所有值
+    pub fn values(&self, name: &str) -> &[IppValue] {
+        self.groups
+            .iter()
+            .find_map(|group| group.get(name))
+            .map_or(&[], |attribute| &attribute.values)
+    }
+
+    pub fn is_successful(&self) -> bool {
+        self.code < 0x0100
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = vec![self.version.0, self.version.1];
+        out.extend_from_slice(&self.code.to_be_bytes());
+        out.extend_from_slice(&self.request_id.to_be_bytes());
+        for group in &self.groups {
+            out.push(group.tag);
+            for attribute in &group.attributes {
+                for (index, value) in attribute.values.iter().enumerate() {
+                    // 多值属性的后续值名称为空
+                    let name = if index == 0 { attribute.name.as_bytes() } else { &[] };
+                    let (tag, bytes) = value.encode();
+                    out.push(tag);
+                    out.extend_from_slice(&(name.len() as u16).to_be_bytes());
+                    out.extend_from_slice(name);
+                    out.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+                    out.extend_from_slice(&bytes);
+                }
+            }
+        }
+        out.push(END_OF_ATTRIBUTES);
+        out.extend_from_slice(&self.data);
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader { bytes, pos: 0 };
+        let version = (reader.u8()?, reader.u8()?);
+        let code = reader.u16()?;
+        let request_id = u32::from_be_bytes(reader.take(4)?.try_into().unwrap());
+        let mut groups: Vec<AttributeGroup> = Vec::new();
+        loop {
+            let tag = reader.u8()?;
+            match tag {
+                END_OF_ATTRIBUTES => break,
+                // 0x00-0x0f 为属性组标签
+                0x00..=0x0f => groups.push(AttributeGroup::new(tag)),
+                _ => {
+                    let group = groups.last_mut().ok_or("属性出现在属性组之前")?;
+                    let name_len = reader.u16()? as usize;
+                    let name = String::from_utf8_lossy(reader.take(name_len)?).into_owned();
+                    let value_len = reader.u16()? as usize;
+                    let value = IppValue::decode(tag, reader.take(value_len)?);
+                    match group.attributes.last_mut() {
+                        Some(attribute) if name.is_empty() => attribute.values.push(value),
+                        _ => group.attributes.push(Attribute {
+                            name,
+                            values: vec![value],
+                        }),
+                    }
+                }
+            }
+        }
+        Ok(IppMessage {
+            version,
+            code,
+            request_id,
+            groups,
+            data: bytes[reader.pos..].to_vec(),
+        })
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.pos + len;
+        if end > self.bytes.len() {
+            return Err("IPP 消息不完整".to_string());
+        }
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+}
+
+//ipp://host[:port]/path 或 http://host[:port]/path，ipp 的默认端口为 631
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IppUri {
+    pub host: String, // 包含端口，用于连接和 Host 请求头
+    pub path: String,
+}
+
+impl IppUri {
+    pub fn parse(uri: &str) -> Result<Self, String> {
+        let (rest, default_port) = if let Some(rest) = uri.strip_prefix("ipp://") {
+            (rest, 631)
+        } else if let Some(rest) = uri.strip_prefix("http://") {
+            (rest, 80)
+        } else {
+            return Err(format!("不支持的 IPP 地址 {}（需要 ipp:// 或 http://）", uri));
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/"),
+        };
+        if authority.is_empty() {
+            return Err(format!("IPP 地址 {} 缺少主机名", uri));
+        }
+        let has_port = authority
+            .rsplit_once(':')
+            .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok());
+        let host = if has_port {
+            authority.to_string()
+        } else {
+            format!("{}:{}", authority, default_port)
+        };
+        Ok(IppUri {
+            host,
+            path: path.to_string(),
+        })
+    }
+}
+
+//通过 HTTP POST 发送 IPP 请求并解析响应
+pub fn send(stream: TcpStream, uri: &IppUri, request: &IppMessage) -> Result<IppMessage, String> {
+    let body = request.encode();
+    let mut writer = &stream;
+    let head = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/ipp\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        uri.path,
+        uri.host,
+        body.len()
+    );
+    writer
+        .write_all(head.as_bytes())
+        .and_then(|_| writer.write_all(&body))
+        .map_err(|e| format!("发送 IPP 请求失败: {}", e))?;
+
+    let body = read_http_response(BufReader::new(stream)).map_err(|e| format!("读取 IPP 响应失败: {}", e))?;
+    IppMessage::decode(&body)
+}
+
+//读取 HTTP 响应，非 200 时返回错误，支持 Content-Length、chunked 和读到连接关闭三种方式
+fn read_http_response(mut reader: impl BufRead) -> io::Result<Vec<u8>> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line)?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| invalid(format!("无效的 HTTP 状态行 {:?}", status_line.trim())))?;
+
+    let mut content_length = None;
+    let mut chunked = false;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.parse::<usize>().ok();
+            } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                chunked = value.eq_ignore_ascii_case("chunked");
+            }
+        }
+    }
+    if status != 200 {
+        return Err(invalid(format!("HTTP 状态 {}", status)));
+    }
+
+    let mut body = Vec::new();
+    if chunked {
+        loop {
+            let mut size_line = String::new();
+            reader.read_line(&mut size_line)?;
+            let size_text = size_line.trim().split(';').next().unwrap_or_default();
+            let size = usize::from_str_radix(size_text, 16)
+                .map_err(|_| invalid(format!("无效的分块长度 {:?}", size_text)))?;
+            if size == 0 {
+                break;
+            }
+            let start = body.len();
+            body.resize(start + size, 0);
+            reader.read_exact(&mut body[start..])?;
+            let mut crlf = String::new();
+            reader.read_line(&mut crlf)?;
+        }
+    } else if let Some(length) = content_length {
+        body.resize(length, 0);
+        reader.read_exact(&mut body)?;
+    } else {
+        reader.read_to_end(&mut body)?;
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_round_trips() {
+        let mut message = IppMessage::new(PRINT_JOB, 17);
+        message.groups.push(operation_group());
+        message
+            .group_mut(OPERATION_ATTRIBUTES)
+            .add("job-name", IppValue::String(TAG_NAME, "队伍1 的代码".to_string()))
+            .add_all(
+                "requested-attributes",
+                vec![
+                    IppValue::String(TAG_KEYWORD, "job-state".to_string()),
+                    IppValue::String(TAG_KEYWORD, "job-id".to_string()),
+                ],
+            );
+        message
+            .group_mut(JOB_ATTRIBUTES)
+            .add("copies", IppValue::Integer(2))
+            .add("job-state", IppValue::Enum(JOB_PROCESSING))
+            .add("color", IppValue::Boolean(true))
+            .add("resolution", IppValue::Other(0x32, vec![0, 0, 1, 44, 0, 0, 1, 44, 3]));
+        message.data = b"%PDF-1.4\n...".to_vec();
+
+        let decoded = IppMessage::decode(&message.encode()).unwrap();
+        assert_eq!(decoded, message);
+        assert_eq!(decoded.value("copies"), Some(&IppValue::Integer(2)));
+        assert_eq!(decoded.values("requested-attributes").len(), 2);
+        assert_eq!(decoded.value("job-name").and_then(IppValue::as_str), Some("队伍1 的代码"));
+    }
+
+    #[test]
+    fn truncated_message_is_rejected() {
+        let mut message = IppMessage::new(GET_JOBS, 1);
+        message.groups.push(operation_group());
+        let bytes = message.encode();
+        for len in [0, 5, bytes.len() - 1] {
+            assert!(IppMessage::decode(&bytes[..len]).is_err(), "{}", len);
+        }
+    }
+
+    fn response(raw: &[u8]) -> io::Result<Vec<u8>> {
+        read_http_response(BufReader::new(raw))
+    }
+
+    #[test]
+    fn reads_content_length_body() {
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Type: application/ipp\r\ncontent-length: 5\r\n\r\nhello, extra";
+        assert_eq!(response(raw).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn reads_chunked_body() {
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\nA;ext=1\r\n, chunked!\r\n0\r\n\r\n";
+        assert_eq!(response(raw).unwrap(), b"hello, chunked!");
+    }
+
+    #[test]
+    fn reads_body_until_close_without_length() {
+        let raw = b"HTTP/1.0 200 OK\r\nContent-Type: application/ipp\r\n\r\nall of it";
+        assert_eq!(response(raw).unwrap(), b"all of it");
+    }
+
+    #[test]
+    fn rejects_non_200_and_bad_chunks() {
+        let raw = b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n";
+        assert!(response(raw).unwrap_err().to_string().contains("404"));
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n";
+        assert!(response(raw).is_err());
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nshort";
+        assert!(response(raw).is_err());
+    }
+}
@@ -0,0 +1,716 @@
+use crate::highlight::Language;
+use crate::ipp::{self, AttributeGroup, IppMessage, IppValue};
+use crate::job::{Job, JobStatus};
+use crate::osim::SPOOLing::{CancelError, SpoolMode, SubmitError, rawJob};
+use crate::osim::spooler::Spooler;
+use crate::submission::{self, DocumentStore, TeamError, TeamMapping};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::time::Instant;
+
+//内嵌的 IPP 打印服务：lp、CUPS 和系统打印对话框可以直接向假脱机系统提交任务
+
+//IPP 服务配置（ipp.json）：按客户端地址或用户名确定提交的队伍
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IppServerConfig {
+    #[serde(default = "default_printer_name")]
+    pub printer_name: String,
+    #[serde(flatten)]
+    pub teams: TeamMapping, // 用户名为 requesting-user-name
+    #[serde(default)]
+    pub priority: u32, // IPP 任务的优先级
+}
+
+fn default_printer_name() -> String {
+    "spooling".to_string()
+}
+
+impl Default for IppServerConfig {
+    fn default() -> Self {
+        IppServerConfig {
+            printer_name: default_printer_name(),
+            teams: TeamMapping::default(),
+            priority: 0,
+        }
+    }
+}
+
+//发出请求的客户端
+pub struct Client {
+    pub peer: Option<IpAddr>,
+    pub printer_uri: String, // 客户端访问的打印机地址，用于生成 job-uri
+}
+
+//文档格式，application/octet-stream 按内容判断
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Pdf,
+    Text,
+    Auto,
+}
+
+//Print-Job 和 Validate-Job 共用的任务参数
+struct Ticket {
+    team_name: String,
+    job_name: String,
+    color: bool,
+    format: Format,
+}
+
+//失败时的状态码和说明
+type IppResult<T> = Result<T, (u16, String)>;
+
+pub struct IppServer {
+    config: IppServerConfig,
+    documents: DocumentStore,
+    started: Instant,
+}
+
+impl IppServer {
+    pub fn new(config: IppServerConfig) -> Self {
+        IppServer {
+            config,
+            documents: DocumentStore::new("ipp"),
+            started: Instant::now(),
+        }
+    }
+
+    //处理一个 IPP 请求；提交经过 `submit`，与 /print 走同一条路径
+    pub fn handle(
+        &self,
+        spooler: &dyn Spooler,
+        client: &Client,
+        request: &IppMessage,
+        submit: impl FnOnce(rawJob) -> Result<usize, SubmitError>,
+    ) -> IppMessage {
+        let result = if !matches!(request.version.0, 1 | 2) {
+            Err((
+                ipp::SERVER_ERROR_VERSION_NOT_SUPPORTED,
+                format!("不支持的 IPP 版本 {}.{}", request.version.0, request.version.1),
+            ))
+        } else {
+            match request.code {
+                ipp::PRINT_JOB => self.print_job(spooler, client, request, submit),
+                ipp::VALIDATE_JOB => self
+                    .ticket(client, request)
+                    .map(|_| response(request, ipp::SUCCESSFUL_OK)),
+                ipp::GET_PRINTER_ATTRIBUTES => Ok(self.printer_attributes(spooler, client, request)),
+                ipp::GET_JOB_ATTRIBUTES => self.job_attributes(spooler, client, request),
+                ipp::GET_JOBS => self.jobs(spooler, client, request),
+                ipp::CANCEL_JOB => self.cancel_job(spooler, client, request),
+                code => Err((
+                    ipp::SERVER_ERROR_OPERATION_NOT_SUPPORTED,
+                    format!("不支持的操作 0x{:04x}", code),
+                )),
+            }
+        };
+        result.unwrap_or_else(|(status, message)| {
+            let mut response = response(request, status);
+            response
+                .group_mut(ipp::OPERATION_ATTRIBUTES)
+                .add("status-message", text(&message));
+            response
+        })
+    }
+
+    fn team_name(&self, client: &Client, request: &IppMessage) -> IppResult<String> {
+        let user = request.value("requesting-user-name").and_then(IppValue::as_str);
+        self.config.teams.resolve(client.peer, user).map_err(|e| {
+            let status = match e {
+                TeamError::Unmapped(_) => ipp::CLIENT_ERROR_NOT_AUTHORIZED,
+                TeamError::InvalidName(_) | TeamError::Unknown => ipp::CLIENT_ERROR_BAD_REQUEST,
+            };
+            (status, e.to_string())
+        })
+    }
+
+    fn ticket(&self, client: &Client, request: &IppMessage) -> IppResult<Ticket> {
+        if let Some(compression) = request.value("compression").and_then(IppValue::as_str)
+            && compression != "none"
+        {
+            return Err((
+                ipp::CLIENT_ERROR_COMPRESSION_NOT_SUPPORTED,
+                format!("不支持压缩方式 {}", compression),
+            ));
+        }
+        let format = match request.value("document-format").and_then(IppValue::as_str) {
+            None => Format::Auto,
+            Some(mime) => parse_format(mime).ok_or((
+                ipp::CLIENT_ERROR_DOCUMENT_FORMAT_NOT_SUPPORTED,
+                format!("不支持文档格式 {}（支持 application/pdf 和 text/plain）", mime),
+            ))?,
+        };
+        let color = request
+            .value("print-color-mode")
+            .and_then(IppValue::as_str)
+            .is_some_and(|mode| mode == "color");
+        let job_name = ["job-name", "document-name"]
+            .iter()
+            .find_map(|name| request.value(name).and_then(IppValue::as_str))
+            .unwrap_or("untitled")
+            .to_string();
+        Ok(Ticket {
+            team_name: self.team_name(client, request)?,
+            job_name,
+            color,
+            format,
+        })
+    }
+
+    fn print_job(
+        &self,
+        spooler: &dyn Spooler,
+        client: &Client,
+        request: &IppMessage,
+        submit: impl FnOnce(rawJob) -> Result<usize, SubmitError>,
+    ) -> IppResult<IppMessage> {
+        let ticket = self.ticket(client, request)?;
+        let data = &request.data;
+        if data.is_empty() {
+            return Err((ipp::CLIENT_ERROR_BAD_REQUEST, "请求中没有文档数据".to_string()));
+        }
+        let format = match ticket.format {
+            Format::Auto if submission::is_pdf(data) => Format::Pdf,
+            Format::Auto => Format::Text,
+            format => format,
+        };
+
+        let (file_content, document) = match format {
+            Format::Pdf => {
+                if !submission::is_pdf(data) {
+                    return Err((
+                        ipp::CLIENT_ERROR_DOCUMENT_FORMAT_ERROR,
+                        "文档不是有效的 PDF".to_string(),
+                    ));
+                }
+                let document = self.documents.store(data).map_err(|e| {
+                    (ipp::SERVER_ERROR_INTERNAL_ERROR, format!("保存文档失败: {}", e))
+                })?;
+                (String::new(), Some(document))
+            }
+            _ => match String::from_utf8(data.clone()) {
+                Ok(text) => (text, None),
+                Err(_) => {
+                    return Err((
+                        ipp::CLIENT_ERROR_DOCUMENT_FORMAT_ERROR,
+                        "文本文档需要使用 UTF-8 编码".to_string(),
+                    ));
+                }
+            },
+        };
+
+        let raw_job = rawJob {
+            priority: self.config.priority,
+            team_name: ticket.team_name,
+            submit_time: Utc::now(),
+            file_content,
+            color: ticket.color,
+            language: Language::from_file_name(&ticket.job_name),
+            problem_name: ticket.job_name,
+            document,
+        };
+        match submit(raw_job) {
+            Ok(job_id) => {
+                let mut response = response(request, ipp::SUCCESSFUL_OK);
+                let job = spooler.get_job(job_id as u64);
+                let group = response.group_mut(ipp::JOB_ATTRIBUTES);
+                group
+                    .add("job-id", IppValue::Integer(job_id as i32))
+                    .add("job-uri", uri(&job_uri(client, job_id)))
+                    .add(
+                        "job-state",
+                        IppValue::Enum(job.as_ref().map_or(ipp::JOB_PENDING, |job| job_state(&job.status))),
+                    )
+                    .add(
+                        "job-state-reasons",
+                        keyword(job.as_ref().map_or("none", |job| job_state_reason(&job.status))),
+                    );
+                Ok(response)
+            }
+            // 提交失败时文档由假脱机系统删除
+            Err(e) => {
+                let status = match e {
+                    SubmitError::QuotaExceeded(_) | SubmitError::Unprintable(_) => {
+                        ipp::CLIENT_ERROR_NOT_POSSIBLE
+                    }
+                    SubmitError::BufferFull => ipp::SERVER_ERROR_BUSY,
+                    SubmitError::NotAccepting(_) => ipp::SERVER_ERROR_NOT_ACCEPTING_JOBS,
+                    SubmitError::IdUnavailable(_) | SubmitError::Unrecorded(_) => {
+                        ipp::SERVER_ERROR_INTERNAL_ERROR
+                    }
+                };
+                Err((status, format!("提交失败: {}", e)))
+            }
+        }
+    }
+
+    fn printer_attributes(&self, spooler: &dyn Spooler, client: &Client, request: &IppMessage) -> IppMessage {
+        let mode = spooler.mode();
+        let jobs = spooler.list_jobs();
+        let queued = jobs.iter().filter(|job| !job.status.is_finished()).count();
+        let printing = jobs.iter().any(|job| job.status == JobStatus::Printing);
+        let state = match mode {
+            SpoolMode::Paused | SpoolMode::Stopped => ipp::PRINTER_STOPPED,
+            _ if printing => ipp::PRINTER_PROCESSING,
+            _ => ipp::PRINTER_IDLE,
+        };
+        let reason = match mode {
+            SpoolMode::Running => "none",
+            SpoolMode::Paused => "paused",
+            SpoolMode::Draining => "moving-to-paused",
+            SpoolMode::Stopped => "shutdown",
+        };
+        let operations = [
+            ipp::PRINT_JOB,
+            ipp::VALIDATE_JOB,
+            ipp::CANCEL_JOB,
+            ipp::GET_JOB_ATTRIBUTES,
+            ipp::GET_JOBS,
+            ipp::GET_PRINTER_ATTRIBUTES,
+        ];
+
+        let mut group = AttributeGroup::new(ipp::PRINTER_ATTRIBUTES);
+        group
+            .add("printer-uri-supported", uri(&client.printer_uri))
+            .add("uri-security-supported", keyword("none"))
+            .add("uri-authentication-supported", keyword("requesting-user-name"))
+            .add("printer-name", name(&self.config.printer_name))
+            .add("printer-info", text("比赛打印假脱机系统"))
+            .add("printer-make-and-model", text("spooling"))
+            .add("printer-state", IppValue::Enum(state))
+            .add("printer-state-reasons", keyword(reason))
+            .add(
+                "printer-is-accepting-jobs",
+                IppValue::Boolean(matches!(mode, SpoolMode::Running | SpoolMode::Paused)),
+            )
+            .add("queued-job-count", IppValue::Integer(queued as i32))
+            .add_all("ipp-versions-supported", vec![keyword("1.1"), keyword("2.0")])
+            .add_all(
+                "operations-supported",
+                operations.iter().map(|&code| IppValue::Enum(code as i32)).collect(),
+            )
+            .add("charset-configured", IppValue::String(ipp::TAG_CHARSET, "utf-8".to_string()))
+            .add("charset-supported", IppValue::String(ipp::TAG_CHARSET, "utf-8".to_string()))
+            .add("natural-language-configured", IppValue::String(ipp::TAG_LANGUAGE, "en".to_string()))
+            .add(
+                "generated-natural-language-supported",
+                IppValue::String(ipp::TAG_LANGUAGE, "en".to_string()),
+            )
+            .add("document-format-default", mime("application/octet-stream"))
+            .add_all(
+                "document-format-supported",
+                vec![mime("application/pdf"), mime("text/plain"), mime("application/octet-stream")],
+            )
+            .add("color-supported", IppValue::Boolean(true))
+            .add("print-color-mode-default", keyword("monochrome"))
+            .add_all("print-color-mode-supported", vec![keyword("monochrome"), keyword("color")])
+            .add("compression-supported", keyword("none"))
+            .add("pdl-override-supported", keyword("not-attempted"))
+            .add("printer-up-time", IppValue::Integer(self.started.elapsed().as_secs() as i32));
+        filter_requested(&mut group, request);
+
+        let mut response = response(request, ipp::SUCCESSFUL_OK);
+        response.groups.push(group);
+        response
+    }
+
+    //请求中 job-id 或 job-uri 指定的任务
+    fn requested_job(&self, spooler: &dyn Spooler, request: &IppMessage) -> IppResult<Job> {
+        let job_id = request
+            .value("job-id")
+            .and_then(IppValue::as_int)
+            .or_else(|| {
+                request
+                    .value("job-uri")
+                    .and_then(IppValue::as_str)
+                    .and_then(|uri| uri.rsplit('/').next())
+                    .and_then(|id| id.parse().ok())
+            })
+            .ok_or((ipp::CLIENT_ERROR_BAD_REQUEST, "缺少 job-id".to_string()))?;
+        u64::try_from(job_id)
+            .ok()
+            .and_then(|job_id| spooler.get_job(job_id))
+            .ok_or((ipp::CLIENT_ERROR_NOT_FOUND, format!("任务 {} 不存在", job_id)))
+    }
+
+    fn job_attributes(&self, spooler: &dyn Spooler, client: &Client, request: &IppMessage) -> IppResult<IppMessage> {
+        let job = self.requested_job(spooler, request)?;
+        let mut response = response(request, ipp::SUCCESSFUL_OK);
+        response.groups.push(job_group(client, &job));
+        Ok(response)
+    }
+
+    //Get-Jobs：which-jobs 为 not-completed（默认）或 completed，my-jobs 只返回本队伍的任务
+    fn jobs(&self, spooler: &dyn Spooler, client: &Client, request: &IppMessage) -> IppResult<IppMessage> {
+        let completed = match request.value("which-jobs").and_then(IppValue::as_str) {
+            None | Some("not-completed") => false,
+            Some("completed") => true,
+            Some(which) => {
+                return Err((
+                    ipp::CLIENT_ERROR_BAD_REQUEST,
+                    format!("不支持 which-jobs 取值 {}", which),
+                ));
+            }
+        };
+        let team_name = match request.value("my-jobs") {
+            Some(IppValue::Boolean(true)) => Some(self.team_name(client, request)?),
+            _ => None,
+        };
+        let limit = request
+            .value("limit")
+            .and_then(IppValue::as_int)
+            .filter(|&limit| limit > 0)
+            .map_or(usize::MAX, |limit| limit as usize);
+
+        let mut jobs: Vec<Job> = spooler
+            .list_jobs()
+            .into_iter()
+            .filter(|job| job.status != JobStatus::SubmitFailed)
+            .filter(|job| job.status.is_finished() == completed)
+            .filter(|job| team_name.as_ref().is_none_or(|team_name| &job.team_name == team_name))
+            .collect();
+        jobs.sort_by_key(|job| job.job_id);
+
+        let mut response = response(request, ipp::SUCCESSFUL_OK);
+        response
+            .groups
+            .extend(jobs.iter().take(limit).map(|job| job_group(client, job)));
+        Ok(response)
+    }
+
+    //队伍只能取消自己的任务
+    fn cancel_job(&self, spooler: &dyn Spooler, client: &Client, request: &IppMessage) -> IppResult<IppMessage> {
+        let job = self.requested_job(spooler, request)?;
+        if self.team_name(client, request)? != job.team_name {
+            return Err((ipp::CLIENT_ERROR_NOT_AUTHORIZED, "只能取消本队伍的任务".to_string()));
+        }
+        match spooler.cancel(job.job_id as u64) {
+            Ok(()) => Ok(response(request, ipp::SUCCESSFUL_OK)),
+            Err(e @ CancelError::NotFound) => Err((ipp::CLIENT_ERROR_NOT_FOUND, format!("取消失败: {}", e))),
+            Err(e @ CancelError::Unrecorded(_)) => {
+                Err((ipp::SERVER_ERROR_INTERNAL_ERROR, format!("取消失败: {}", e)))
+            }
+            Err(e) => Err((ipp::CLIENT_ERROR_NOT_POSSIBLE, format!("取消失败: {}", e))),
+        }
+    }
+}
+
+//响应的骨架：沿用请求的版本和 request-id
+fn response(request: &IppMessage, status: u16) -> IppMessage {
+    let mut response = IppMessage::new(status, request.request_id);
+    if matches!(request.version.0, 1 | 2) {
+        response.version = request.version;
+    }
+    response.groups.push(ipp::operation_group());
+    response
+}
+
+fn job_uri(client: &Client, job_id: usize) -> String {
+    format!("{}/jobs/{}", client.printer_uri.trim_end_matches('/'), job_id)
+}
+
+fn job_group(client: &Client, job: &Job) -> AttributeGroup {
+    let mut group = AttributeGroup::new(ipp::JOB_ATTRIBUTES);
+    group
+        .add("job-id", IppValue::Integer(job.job_id as i32))
+        .add("job-uri", uri(&job_uri(client, job.job_id)))
+        .add("job-printer-uri", uri(&client.printer_uri))
+        .add("job-name", name(&job.problem_name))
+        .add("job-originating-user-name", name(&job.team_name))
+        .add("job-state", IppValue::Enum(job_state(&job.status)))
+        .add("job-state-reasons", keyword(job_state_reason(&job.status)))
+        .add("job-impressions", IppValue::Integer(job.page_count() as i32))
+        .add("time-at-creation", IppValue::Integer(job.submit_time.timestamp() as i32));
+    group
+}
+
+fn job_state(status: &JobStatus) -> i32 {
+    match status {
+        JobStatus::Waiting => ipp::JOB_PENDING,
+        JobStatus::Printing => ipp::JOB_PROCESSING,
+        JobStatus::Completed => ipp::JOB_COMPLETED,
+        JobStatus::SubmitFailed | JobStatus::Failed { .. } => ipp::JOB_ABORTED,
+        JobStatus::Cancelled => ipp::JOB_CANCELED,
+    }
+}
+
+fn job_state_reason(status: &JobStatus) -> &'static str {
+    match status {
+        JobStatus::Waiting => "none",
+        JobStatus::Printing => "job-printing",
+        JobStatus::Completed => "job-completed-successfully",
+        JobStatus::SubmitFailed | JobStatus::Failed { .. } => "aborted-by-system",
+        JobStatus::Cancelled => "job-canceled-by-user",
+    }
+}
+
+//只保留 requested-attributes 中列出的属性，未指定或包含 all 时全部返回
+fn filter_requested(group: &mut AttributeGroup, request: &IppMessage) {
+    let requested: Vec<&str> = request
+        .values("requested-attributes")
+        .iter()
+        .filter_map(IppValue::as_str)
+        .collect();
+    if requested.is_empty() || requested.contains(&"all") || requested.contains(&"printer-description") {
+        return;
+    }
+    group
+        .attributes
+        .retain(|attribute| requested.contains(&attribute.name.as_str()));
+}
+
+fn parse_format(mime: &str) -> Option<Format> {
+    let base = mime.split(';').next().unwrap_or_default().trim();
+    match base.to_ascii_lowercase().as_str() {
+        "application/pdf" => Some(Format::Pdf),
+        "text/plain" => Some(Format::Text),
+        "application/octet-stream" => Some(Format::Auto),
+        _ => None,
+    }
+}
+
+fn keyword(value: &str) -> IppValue {
+    IppValue::String(ipp::TAG_KEYWORD, value.to_string())
+}
+
+fn text(value: &str) -> IppValue {
+    IppValue::String(ipp::TAG_TEXT, value.to_string())
+}
+
+fn name(value: &str) -> IppValue {
+    IppValue::String(ipp::TAG_NAME, value.to_string())
+}
+
+fn uri(value: &str) -> IppValue {
+    IppValue::String(ipp::TAG_URI, value.to_string())
+}
+
+fn mime(value: &str) -> IppValue {
+    IppValue::String(ipp::TAG_MIME_TYPE, value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock;
+    use crate::osim::SPOOLing::SPOOLing;
+    use crate::osim::job_id::JobIdAllocator;
+    use crate::osim::policy::PolicyKind;
+    use crate::osim::quota::{QuotaConfig, QuotaLimits, QuotaManager};
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    const PDF: &[u8] = b"%PDF-1.4\n1 0 obj << /Type /Page >> endobj\n";
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("spooling-ipp-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    //文档写入 `dir` 的 IPP 服务
+    fn server(dir: &Path) -> IppServer {
+        IppServer {
+            config: IppServerConfig::default(),
+            documents: DocumentStore::in_dir(dir.to_path_buf(), "ipp"),
+            started: Instant::now(),
+        }
+    }
+
+    //不启动工作线程的 SPOOLing，提交的任务停在输入缓冲区
+    fn spooling(quota: QuotaConfig) -> SPOOLing {
+        SPOOLing::new(
+            10,
+            10,
+            10,
+            10,
+            PolicyKind::Priority,
+            QuotaManager::new(quota),
+            Arc::new(JobIdAllocator::in_memory()),
+            None,
+            None,
+            clock::system(),
+        )
+    }
+
+    fn client() -> Client {
+        Client {
+            peer: Some("10.0.0.11".parse().unwrap()),
+            printer_uri: "ipp://spooler:8080/ipp/print".to_string(),
+        }
+    }
+
+    fn request(code: u16, user: &str) -> IppMessage {
+        let mut request = IppMessage::new(code, 1);
+        request.groups.push(ipp::operation_group());
+        request
+            .group_mut(ipp::OPERATION_ATTRIBUTES)
+            .add("printer-uri", uri(&client().printer_uri))
+            .add("requesting-user-name", name(user));
+        request
+    }
+
+    fn print_request(user: &str, format: &str, data: &[u8]) -> IppMessage {
+        let mut request = request(ipp::PRINT_JOB, user);
+        request
+            .group_mut(ipp::OPERATION_ATTRIBUTES)
+            .add("job-name", name("a.cpp"))
+            .add("document-format", mime(format));
+        request.data = data.to_vec();
+        request
+    }
+
+    fn handle(server: &IppServer, spooler: &dyn Spooler, request: &IppMessage) -> IppMessage {
+        server.handle(spooler, &client(), request, |raw_job| spooler.submit_job(raw_job))
+    }
+
+    fn print(server: &IppServer, spooler: &dyn Spooler, user: &str) -> u64 {
+        let response = handle(server, spooler, &print_request(user, "text/plain", b"int main() {}"));
+        assert_eq!(response.code, ipp::SUCCESSFUL_OK);
+        response.value("job-id").and_then(IppValue::as_int).unwrap() as u64
+    }
+
+    fn documents(dir: &Path) -> usize {
+        fs::read_dir(dir).unwrap().count()
+    }
+
+    #[test]
+    fn print_job_submits_pdf_and_text() {
+        let dir = scratch_dir("print");
+        let server = server(&dir);
+        let spooler = spooling(QuotaConfig::default());
+
+        let response = handle(&server, &spooler, &print_request("alice", "application/pdf", PDF));
+        assert_eq!(response.code, ipp::SUCCESSFUL_OK);
+        assert_eq!(response.value("job-state"), Some(&IppValue::Enum(ipp::JOB_PENDING)));
+        let job_id = response.value("job-id").and_then(IppValue::as_int).unwrap();
+        assert_eq!(
+            response.value("job-uri").and_then(IppValue::as_str),
+            Some(format!("ipp://spooler:8080/ipp/print/jobs/{}", job_id).as_str())
+        );
+        let job = spooler.get_job(job_id as u64).unwrap();
+        assert_eq!(job.team_name, "alice");
+        assert_eq!(job.problem_name, "a.cpp");
+        assert_eq!(job.language, Some(Language::Cpp));
+        let document = job.document.unwrap();
+        assert_eq!(document.pages, 1);
+        assert_eq!(fs::read(&document.path).unwrap(), PDF);
+
+        // application/octet-stream 按内容判断，不是 PDF 时按文本排版
+        let response = handle(&server, &spooler, &print_request("alice", "application/octet-stream", b"x = 1"));
+        assert_eq!(response.code, ipp::SUCCESSFUL_OK);
+        let job_id = response.value("job-id").and_then(IppValue::as_int).unwrap();
+        let job = spooler.get_job(job_id as u64).unwrap();
+        assert_eq!(job.file_content, "x = 1");
+        assert!(job.document.is_none());
+        assert_eq!(documents(&dir), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn print_job_rejections() {
+        let dir = scratch_dir("reject");
+        let server = server(&dir);
+        let quota = QuotaConfig {
+            global: QuotaLimits::default(),
+            teams: HashMap::from([(
+                "bob".to_string(),
+                QuotaLimits {
+                    max_jobs: Some(0),
+                    ..QuotaLimits::default()
+                },
+            )]),
+        };
+        let spooler = spooling(quota);
+
+        let cases = [
+            (print_request("alice", "image/png", PDF), ipp::CLIENT_ERROR_DOCUMENT_FORMAT_NOT_SUPPORTED),
+            (print_request("alice", "application/pdf", b"text"), ipp::CLIENT_ERROR_DOCUMENT_FORMAT_ERROR),
+            (print_request("alice", "text/plain", &[0xff, 0xfe]), ipp::CLIENT_ERROR_DOCUMENT_FORMAT_ERROR),
+            (print_request("alice", "text/plain", b""), ipp::CLIENT_ERROR_BAD_REQUEST),
+            (print_request("../alice", "text/plain", b"x"), ipp::CLIENT_ERROR_BAD_REQUEST),
+            // 超出配额时假脱机系统拒绝，并删除已保存的文档
+            (print_request("bob", "application/pdf", PDF), ipp::CLIENT_ERROR_NOT_POSSIBLE),
+        ];
+        for (request, status) in &cases {
+            let response = handle(&server, &spooler, request);
+            assert_eq!(response.code, *status, "{:?}", request.value("requesting-user-name"));
+            assert!(response.value("status-message").is_some());
+        }
+        assert!(spooler.get_active_job_id().is_empty());
+        assert_eq!(documents(&dir), 0);
+
+        // 排空后不再接收
+        spooler.start_drain().unwrap();
+        let response = handle(&server, &spooler, &print_request("alice", "application/pdf", PDF));
+        assert_eq!(response.code, ipp::SERVER_ERROR_NOT_ACCEPTING_JOBS);
+        assert_eq!(documents(&dir), 0);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn cancel_job_only_for_own_team() {
+        let dir = scratch_dir("cancel");
+        let server = server(&dir);
+        let spooler = spooling(QuotaConfig::default());
+        let job_id = print(&server, &spooler, "alice");
+
+        let cancel = |user: &str| {
+            let mut request = request(ipp::CANCEL_JOB, user);
+            request
+                .group_mut(ipp::OPERATION_ATTRIBUTES)
+                .add("job-id", IppValue::Integer(job_id as i32));
+            handle(&server, &spooler, &request).code
+        };
+        assert_eq!(cancel("bob"), ipp::CLIENT_ERROR_NOT_AUTHORIZED);
+        assert_eq!(spooler.get_job(job_id).unwrap().status, JobStatus::Waiting);
+        assert_eq!(cancel("alice"), ipp::SUCCESSFUL_OK);
+        assert_eq!(spooler.get_job(job_id).unwrap().status, JobStatus::Cancelled);
+        assert_eq!(cancel("alice"), ipp::CLIENT_ERROR_NOT_POSSIBLE);
+
+        // job-uri 指定的任务
+        let mut request = request(ipp::CANCEL_JOB, "alice");
+        request
+            .group_mut(ipp::OPERATION_ATTRIBUTES)
+            .add("job-uri", uri(&job_uri(&client(), 99)));
+        assert_eq!(handle(&server, &spooler, &request).code, ipp::CLIENT_ERROR_NOT_FOUND);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn get_jobs_filters_by_team_and_state() {
+        let dir = scratch_dir("jobs");
+        let server = server(&dir);
+        let spooler = spooling(QuotaConfig::default());
+        let alice = [print(&server, &spooler, "alice"), print(&server, &spooler, "alice")];
+        let bob = print(&server, &spooler, "bob");
+        spooler.cancel(alice[1]).unwrap();
+
+        let jobs = |user: &str, my_jobs: bool, which: Option<&str>| -> Vec<u64> {
+            let mut request = request(ipp::GET_JOBS, user);
+            let group = request.group_mut(ipp::OPERATION_ATTRIBUTES);
+            group.add("my-jobs", IppValue::Boolean(my_jobs));
+            if let Some(which) = which {
+                group.add("which-jobs", keyword(which));
+            }
+            let response = handle(&server, &spooler, &request);
+            assert_eq!(response.code, ipp::SUCCESSFUL_OK);
+            response
+                .groups
+                .iter()
+                .filter(|group| group.tag == ipp::JOB_ATTRIBUTES)
+                .map(|group| group.get("job-id").unwrap().values[0].as_int().unwrap() as u64)
+                .collect()
+        };
+        assert_eq!(jobs("bob", false, None), vec![alice[0], bob]);
+        assert_eq!(jobs("bob", true, None), vec![bob]);
+        assert_eq!(jobs("alice", true, None), vec![alice[0]]);
+        assert_eq!(jobs("alice", true, Some("completed")), vec![alice[1]]);
+        assert_eq!(jobs("carol", true, None), Vec::<u64>::new());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,19 +1,58 @@
-use std::cmp::Ordering;
 use chrono::{DateTime, Utc};
-use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 
 use serde::{Serialize, Deserialize};
+use crate::highlight::Language;
+use std::fs;
+use std::io;
+use std::path::Path;
 
-static TOTAL_TASKS: AtomicUsize = AtomicUsize::new(0); //总提交任务数量
-static COMPLETED_TASKS: AtomicUsize = AtomicUsize::new(0); //成功任务数量
+/// 每页可打印的行数，用于估算页数
+pub const LINES_PER_PAGE: usize = 60;
 
-//返回总任务和
-pub fn stats() -> (usize, usize) {
-    (
-        TOTAL_TASKS.load(AtomicOrdering::SeqCst),
-        COMPLETED_TASKS.load(AtomicOrdering::SeqCst),
-    )
+/// 因打印机故障中断的次数达到该值时任务失败，不再重试
+pub const MAX_INTERRUPTIONS: u32 = 3;
+
+/// 按内容估算打印页数，至少一页
+pub fn page_count(file_content: &str) -> usize {
+    file_content.lines().count().div_ceil(LINES_PER_PAGE).max(1)
+}
+
+/// 文件名中的队伍名：字母、数字、下划线和连字符以外的字符换成下划线，
+/// 输出文件和下载路径由文件名拼成，不能含有路径分隔符
+fn file_safe(team_name: &str) -> String {
+    team_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect()
+}
+
+/// 客户端直接提交的 PDF 文档（例如通过 IPP），保存在磁盘上，打印时原样发送
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Document {
+    pub path: String,
+    pub pages: usize,
 }
+
+impl Document {
+    /// 把文档复制一份，供另一个任务独立打印和删除
+    pub fn duplicate(&self) -> io::Result<Document> {
+        let path = Path::new(&self.path).with_extension("copy.pdf");
+        fs::copy(&self.path, &path)?;
+        Ok(Document {
+            path: path.to_string_lossy().into_owned(),
+            pages: self.pages,
+        })
+    }
+
+    /// 删除文档文件，文件已不存在时不报错
+    pub fn discard(&self) -> io::Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
 ///  四种工作状态
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize,Clone)]
 pub enum JobStatus {
@@ -21,6 +60,21 @@ pub enum JobStatus {
     Printing,     // 正在打印
     Completed,    // 打印完成
     SubmitFailed, // 提交失败
+    Failed { reason: String }, // 打印失败
+    Cancelled,    // 已取消
+}
+
+impl JobStatus {
+    /// 任务是否已经离开打印流程
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::Completed
+                | JobStatus::SubmitFailed
+                | JobStatus::Failed { .. }
+                | JobStatus::Cancelled
+        )
+    }
 }
 
 
@@ -39,10 +93,18 @@ pub struct Job {
     pub status: JobStatus,
     pub start_print_time: Option<DateTime<Utc>>, // 打印开始时间
     pub end_print_time: Option<DateTime<Utc>>,   // 打印结束时间
+    #[serde(default)]
+    pub interruptions: u32, // 因打印机故障中断的次数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document: Option<Document>, // 现成的 PDF 文档，为空时按 file_content 排版
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<Language>, // 源代码语言，用于语法高亮
 }
 
 impl Job {
+    /// `job_id` 由假脱机系统的id分配器给出
     pub fn new(
+        job_id: usize,
         priority: u32,
         team_name:String,
         submit_time: DateTime<Utc>,
@@ -50,10 +112,9 @@ impl Job {
         color: bool,
         problem_name:String,
     ) -> Self {
-        let job_id=TOTAL_TASKS.fetch_add(1, AtomicOrdering::SeqCst);
         
         let timestamp = submit_time.format("%Y%m%d_%H%M%S").to_string();
-        let file_name = format!("{}_{}_{}", team_name, timestamp, job_id);
+        let file_name = format!("{}_{}_{}", file_safe(&team_name), timestamp, job_id);
         
         Self {
             job_id,
@@ -67,23 +128,69 @@ impl Job {
             status: JobStatus::Waiting,
             start_print_time: None,
             end_print_time: None,
+            interruptions: 0,
+            document: None,
+            language: None,
         }
     }
 
 
-    pub fn start_printing(&mut self) {
+    pub fn start_printing(&mut self, now: DateTime<Utc>) {
         self.status = JobStatus::Printing;
-        self.start_print_time=Some(Utc::now());
+        self.start_print_time=Some(now);
     }
 
-    pub fn complete(&mut self) {
+    pub fn complete(&mut self, now: DateTime<Utc>) {
         if self.status != JobStatus::Completed {
             self.status = JobStatus::Completed;
-            self.end_print_time = Some(Utc::now());
-            COMPLETED_TASKS.fetch_add(1, AtomicOrdering::SeqCst);
+            self.end_print_time = Some(now);
         }
     }
 
+    pub fn fail(&mut self, reason: String, now: DateTime<Utc>) {
+        self.status = JobStatus::Failed { reason };
+        self.end_print_time = Some(now);
+    }
+
+    /// 打印机故障导致打印中断，回到等待状态重新打印；中断次数过多时失败
+    pub fn interrupt(&mut self, reason: String, now: DateTime<Utc>) {
+        self.interruptions += 1;
+        if self.interruptions >= MAX_INTERRUPTIONS {
+            self.fail(reason, now);
+        } else {
+            self.status = JobStatus::Waiting;
+            self.start_print_time = None;
+        }
+    }
+
+    pub fn cancel(&mut self, now: DateTime<Utc>) {
+        self.status = JobStatus::Cancelled;
+        self.end_print_time = Some(now);
+    }
+
+    /// 文件行数
+    pub fn line_count(&self) -> usize {
+        self.file_content.lines().count()
+    }
+
+    /// 估算的打印页数，至少一页；附带文档时使用文档的页数
+    pub fn page_count(&self) -> usize {
+        match &self.document {
+            Some(document) => document.pages.max(1),
+            None => page_count(&self.file_content),
+        }
+    }
+
+    /// 任务结束后删除附带的文档文件
+    pub fn discard_document(&self) {
+        if let Some(document) = &self.document
+            && let Err(e) = document.discard()
+        {
+            eprintln!("[ERROR] 删除 Job {} 的文档 {} 失败：{}", self.job_id, document.path, e);
+        }
+    }
+
+    #[allow(dead_code)]
     pub fn display(&self) {
         println!(
             "任务ID: {}, 文件: {}, 提交时间: {}, 彩色: {}, 状态: {:?}",
@@ -96,25 +203,21 @@ impl Job {
     }
 }
 
-impl PartialEq for Job {
-    fn eq(&self, other: &Self) -> bool {
-        self.priority == other.priority && self.submit_time == other.submit_time
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn file_name_keeps_team_name_inside_the_directory() {
+        let submit_time = Utc.with_ymd_and_hms(2025, 1, 1, 9, 30, 0).unwrap();
+        let job = |team_name: &str| {
+            Job::new(3, 0, team_name.to_string(), submit_time, String::new(), false, "A".to_string())
+        };
+        assert_eq!(job("team-1").file_name, "team-1_20250101_093000_3");
+        assert_eq!(job("../../etc/x").file_name, "______etc_x_20250101_093000_3");
+        assert_eq!(job("a\\b c").file_name, "a_b_c_20250101_093000_3");
+        // 队伍名本身不变
+        assert_eq!(job("../x").team_name, "../x");
     }
 }
-
-impl Eq for Job {}
-
-impl Ord for Job {
-    fn cmp(&self, other: &Self) -> Ordering {
-        match self.priority.cmp(&other.priority) {
-            Ordering::Equal => self.submit_time.cmp(&other.submit_time),
-            other_order => other_order,
-        }
-    }
-}
-
-impl PartialOrd for Job {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
-}
\ No newline at end of file
@@ -0,0 +1,576 @@
+use crate::highlight::Language;
+use crate::job::{Document, Job, JobStatus};
+use crate::osim::SPOOLing::{SubmitError, rawJob};
+use crate::osim::spooler::Spooler;
+use crate::submission::{self, DocumentStore, MAX_DOCUMENT, TeamMapping};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+//RFC 1179 LPD 服务：实验室里只能用 lpr 的机器也可以向假脱机系统提交任务
+
+//客户端超过这个时间没有数据时断开连接
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+//命令行的最大长度
+const MAX_LINE: u64 = 1024;
+
+//控制文件的大小上限
+const MAX_CONTROL_FILE: u64 = 64 * 1024;
+
+//一个任务的数据文件个数和总字节数上限
+const MAX_DATA_FILES: usize = 16;
+const MAX_JOB_BYTES: u64 = MAX_DOCUMENT as u64;
+
+//同时处理的连接数上限，超出时直接断开新连接
+const MAX_CONNECTIONS: usize = 32;
+
+const ACK: u8 = 0;
+const NACK: u8 = 1;
+
+//LPD 服务配置（lpd.json）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LpdServerConfig {
+    #[serde(default)]
+    pub queues: Vec<String>, // 接受的队列名，为空时接受任意队列
+    #[serde(flatten)]
+    pub teams: TeamMapping, // 用户名为控制文件中的 P 行
+    #[serde(default)]
+    pub priority: u32, // LPD 任务的优先级
+}
+
+//提交任务，与 /print 走同一条路径
+pub type Submit = Arc<dyn Fn(rawJob) -> Result<usize, SubmitError> + Send + Sync>;
+
+//已接收、还没提交的数据文件，保存在文档目录中，清空或丢弃时删除
+#[derive(Default)]
+struct DataFiles {
+    paths: HashMap<String, PathBuf>,
+    bytes: u64,
+}
+
+impl DataFiles {
+    fn clear(&mut self) {
+        for (_, path) in self.paths.drain() {
+            let _ = fs::remove_file(path);
+        }
+        self.bytes = 0;
+    }
+}
+
+impl Drop for DataFiles {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+//占用一个连接名额，连接结束时归还
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+//控制文件中一个要打印的数据文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PrintFile {
+    name: String,           // 数据文件名（dfA...）
+    source: Option<String>, // 前面 N 行给出的原文件名
+}
+
+//控制文件中用到的部分
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct ControlFile {
+    host: Option<String>,     // H
+    user: Option<String>,     // P
+    job_name: Option<String>, // J
+    class: Option<String>,    // C，为 color 或 colour 时彩色打印
+    files: Vec<PrintFile>,    // 小写字母开头的打印命令
+}
+
+impl ControlFile {
+    fn parse(text: &str) -> Self {
+        let mut control = ControlFile::default();
+        let mut source = None;
+        for line in text.lines() {
+            let mut chars = line.chars();
+            let Some(command) = chars.next() else {
+                continue;
+            };
+            let value = chars.as_str().trim().to_string();
+            match command {
+                'H' => control.host = Some(value),
+                'P' => control.user = Some(value),
+                'J' => control.job_name = Some(value),
+                'C' => control.class = Some(value),
+                'N' => source = Some(value),
+                // 同一数据文件的重复打印命令表示多份，只打印一份
+                'a'..='z' if !control.files.iter().any(|file| file.name == value) => {
+                    control.files.push(PrintFile {
+                        name: value,
+                        source: source.take(),
+                    });
+                }
+                _ => {}
+            }
+        }
+        control
+    }
+
+    fn color(&self) -> bool {
+        self.class
+            .as_deref()
+            .is_some_and(|class| class.eq_ignore_ascii_case("color") || class.eq_ignore_ascii_case("colour"))
+    }
+}
+
+pub struct LpdServer {
+    config: LpdServerConfig,
+    spooler: Arc<dyn Spooler>,
+    submit: Submit,
+    documents: DocumentStore,
+}
+
+impl LpdServer {
+    pub fn new(config: LpdServerConfig, spooler: Arc<dyn Spooler>, submit: Submit) -> Self {
+        LpdServer {
+            config,
+            spooler,
+            submit,
+            documents: DocumentStore::new("lpd"),
+        }
+    }
+
+    //在后台线程监听，每个连接一个线程，最多同时处理 MAX_CONNECTIONS 个连接
+    pub fn start(self: Arc<Self>, address: &str) -> io::Result<()> {
+        let listener = TcpListener::bind(address)?;
+        println!("LPD 服务监听 {}", address);
+        let active = Arc::new(AtomicUsize::new(0));
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if active.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                            active.fetch_sub(1, Ordering::SeqCst);
+                            eprintln!("[WARN] LPD 连接数已达上限 {}，断开 {:?}", MAX_CONNECTIONS, stream.peer_addr().ok());
+                            continue;
+                        }
+                        let slot = ConnectionSlot(active.clone());
+                        let server = self.clone();
+                        thread::spawn(move || {
+                            let _slot = slot;
+                            let peer = stream.peer_addr().ok().map(|addr| addr.ip());
+                            if let Err(e) = server.serve(stream, peer) {
+                                eprintln!("[ERROR] LPD 连接 {:?} 出错：{}", peer, e);
+                            }
+                        });
+                    }
+                    Err(e) => eprintln!("[ERROR] LPD 接受连接失败：{}", e),
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn accepts(&self, queue: &str) -> bool {
+        self.config.queues.is_empty() || self.config.queues.iter().any(|name| name == queue)
+    }
+
+    fn serve(&self, stream: TcpStream, peer: Option<IpAddr>) -> io::Result<()> {
+        stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+        stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+        let mut reader = BufReader::new(&stream);
+        let mut writer = &stream;
+
+        let line = read_line(&mut reader)?;
+        let Some((&code, operands)) = line.split_first() else {
+            return Ok(());
+        };
+        let operands = String::from_utf8_lossy(operands);
+        let mut operands = operands.split_whitespace();
+        let queue = operands.next().unwrap_or_default();
+        match code {
+            // 开始打印等待中的任务：假脱机系统一直在打印，无需处理
+            0x01 => Ok(()),
+            0x02 if self.accepts(queue) => {
+                writer.write_all(&[ACK])?;
+                self.receive_job(peer, &mut reader, &mut writer)
+            }
+            0x03 | 0x04 => writer.write_all(self.queue_state(code == 0x04).as_bytes()),
+            0x05 => {
+                let agent = operands.next().unwrap_or_default();
+                self.remove_jobs(peer, agent, operands);
+                Ok(())
+            }
+            _ => writer.write_all(&[NACK]),
+        }
+    }
+
+    //接收任务的子命令：控制文件和它引用的数据文件都收齐后提交，提交结果作为最后一个文件的应答。
+    //数据文件直接写入文档目录，一个任务最多 MAX_DATA_FILES 个、共 MAX_JOB_BYTES 字节，超出时拒绝并断开
+    fn receive_job(&self, peer: Option<IpAddr>, reader: &mut impl BufRead, writer: &mut impl Write) -> io::Result<()> {
+        let mut control: Option<ControlFile> = None;
+        let mut data_files = DataFiles::default();
+        loop {
+            let line = read_line(reader)?;
+            let Some((&code, operands)) = line.split_first() else {
+                break;
+            };
+            match code {
+                // 放弃当前任务
+                0x01 => {
+                    control = None;
+                    data_files.clear();
+                    writer.write_all(&[ACK])?;
+                }
+                0x02 | 0x03 => {
+                    let operands = String::from_utf8_lossy(operands);
+                    let Some((count, name)) = operands
+                        .trim()
+                        .split_once(' ')
+                        .and_then(|(count, name)| Some((count.parse::<u64>().ok()?, name)))
+                    else {
+                        writer.write_all(&[NACK])?;
+                        return Ok(());
+                    };
+                    let too_large = if code == 0x02 {
+                        count > MAX_CONTROL_FILE
+                    } else {
+                        data_files.bytes + count > MAX_JOB_BYTES
+                            || (!data_files.paths.contains_key(name)
+                                && data_files.paths.len() >= MAX_DATA_FILES)
+                    };
+                    if too_large {
+                        println!("LPD 任务超出大小或文件数限制（来自 {:?}），已拒绝", peer);
+                        writer.write_all(&[NACK])?;
+                        return Ok(());
+                    }
+                    writer.write_all(&[ACK])?;
+
+                    if code == 0x02 {
+                        let mut content = Vec::new();
+                        reader.take(count).read_to_end(&mut content)?;
+                        if content.len() as u64 != count {
+                            return Err(io::ErrorKind::UnexpectedEof.into());
+                        }
+                        control = Some(ControlFile::parse(&String::from_utf8_lossy(&content)));
+                    } else {
+                        let path = self.documents.receive(reader, count)?;
+                        if let Some(previous) = data_files.paths.insert(name.to_string(), path) {
+                            let _ = fs::remove_file(previous);
+                        }
+                        data_files.bytes += count;
+                    }
+                    // 文件内容之后跟一个 \0
+                    reader.read_exact(&mut [0])?;
+
+                    let complete = control.as_ref().is_some_and(|control| {
+                        control.files.iter().all(|file| data_files.paths.contains_key(&file.name))
+                    });
+                    let accepted = if complete {
+                        let control = control.take().unwrap();
+                        let result = self.submit_files(peer, &control, &mut data_files);
+                        data_files.clear();
+                        match result {
+                            Ok(job_ids) => {
+                                println!("LPD 任务已提交（来自 {:?}）：{:?}", control.host, job_ids);
+                                true
+                            }
+                            Err(e) => {
+                                println!("LPD 任务提交失败（来自 {:?}）：{}", control.host, e);
+                                false
+                            }
+                        }
+                    } else {
+                        true
+                    };
+                    writer.write_all(&[if accepted { ACK } else { NACK }])?;
+                }
+                _ => {
+                    writer.write_all(&[NACK])?;
+                    return Ok(());
+                }
+            }
+        }
+        if control.is_some() || !data_files.paths.is_empty() {
+            println!("LPD 连接在任务收齐前关闭，已丢弃");
+        }
+        Ok(())
+    }
+
+    //每个数据文件作为一个任务提交；PDF 原样打印（文件直接作为任务的文档），其余按 UTF-8 文本排版
+    fn submit_files(
+        &self,
+        peer: Option<IpAddr>,
+        control: &ControlFile,
+        data_files: &mut DataFiles,
+    ) -> Result<Vec<usize>, String> {
+        let team_name = self
+            .config
+            .teams
+            .resolve(peer, control.user.as_deref())
+            .map_err(|e| e.to_string())?;
+        if control.files.is_empty() {
+            return Err("控制文件中没有要打印的文件".to_string());
+        }
+
+        let mut job_ids = Vec::new();
+        for file in &control.files {
+            // 取出后这个文件由任务负责删除
+            let path = data_files.paths.remove(&file.name).unwrap();
+            let data = fs::read(&path).map_err(|e| {
+                let _ = fs::remove_file(&path);
+                format!("读取数据文件失败: {}", e)
+            })?;
+            let (file_content, document) = if submission::is_pdf(&data) {
+                let document = Document {
+                    path: path.to_string_lossy().into_owned(),
+                    pages: submission::pdf_page_count(&data),
+                };
+                (String::new(), Some(document))
+            } else {
+                let _ = fs::remove_file(&path);
+                let text = String::from_utf8(data)
+                    .map_err(|_| format!("数据文件 {} 既不是 PDF 也不是 UTF-8 文本", file.name))?;
+                (text, None)
+            };
+            let problem_name = file
+                .source
+                .clone()
+                .or_else(|| control.job_name.clone())
+                .unwrap_or_else(|| file.name.clone());
+            let raw_job = rawJob {
+                priority: self.config.priority,
+                team_name: team_name.clone(),
+                submit_time: Utc::now(),
+                file_content,
+                color: control.color(),
+                language: Language::from_file_name(&problem_name),
+                problem_name,
+                document,
+            };
+            match (self.submit)(raw_job) {
+                Ok(job_id) => job_ids.push(job_id),
+                // 提交失败时文档由假脱机系统删除
+                Err(e) => {
+                    return Err(format!("提交失败: {}（已提交 {:?}）", e, job_ids));
+                }
+            }
+        }
+        Ok(job_ids)
+    }
+
+    //队列状态：未结束的任务，长格式附带提交时间
+    fn queue_state(&self, long: bool) -> String {
+        let mut jobs: Vec<Job> = self
+            .spooler
+            .list_jobs()
+            .into_iter()
+            .filter(|job| !job.status.is_finished())
+            .collect();
+        if jobs.is_empty() {
+            return "no entries\n".to_string();
+        }
+        jobs.sort_by_key(|job| job.job_id);
+
+        let mut state = format!("{:<8}{:<16}{:<8}{:<24}{}\n", "Rank", "Owner", "Job", "File", "Pages");
+        for (rank, job) in jobs.iter().enumerate() {
+            let rank = match job.status {
+                JobStatus::Printing => "active".to_string(),
+                _ => (rank + 1).to_string(),
+            };
+            state.push_str(&format!(
+                "{:<8}{:<16}{:<8}{:<24}{}",
+                rank,
+                job.team_name,
+                job.job_id,
+                job.problem_name,
+                job.page_count()
+            ));
+            if long {
+                state.push_str(&format!("  {}", job.submit_time.format("%Y-%m-%d %H:%M:%S")));
+            }
+            state.push('\n');
+        }
+        state
+    }
+
+    //删除任务：agent 只能取消本队伍的任务，操作数中的数字为任务id
+    fn remove_jobs<'a>(&self, peer: Option<IpAddr>, agent: &str, operands: impl Iterator<Item = &'a str>) {
+        let team_name = match self.config.teams.resolve(peer, Some(agent)) {
+            Ok(team_name) => team_name,
+            Err(e) => {
+                println!("LPD 删除任务被拒绝：{}", e);
+                return;
+            }
+        };
+        for job_id in operands.filter_map(|operand| operand.parse::<u64>().ok()) {
+            match self.spooler.get_job(job_id) {
+                Some(job) if job.team_name == team_name => match self.spooler.cancel(job_id) {
+                    Ok(()) => println!("LPD 已取消任务 {}", job_id),
+                    Err(e) => println!("LPD 取消任务 {} 失败：{}", job_id, e),
+                },
+                Some(_) => println!("LPD 取消任务 {} 被拒绝：只能取消本队伍的任务", job_id),
+                None => println!("LPD 取消任务 {} 失败：任务不存在", job_id),
+            }
+        }
+    }
+}
+
+//读取一行命令，不含结尾的 \n；连接关闭时返回空
+fn read_line(reader: &mut impl BufRead) -> io::Result<Vec<u8>> {
+    let mut line = Vec::new();
+    reader.take(MAX_LINE).read_until(b'\n', &mut line)?;
+    if line.last() == Some(&b'\n') {
+        line.pop();
+    }
+    Ok(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock;
+    use crate::osim::NoSPOOLing::NoSPOOLing;
+    use crate::osim::job_id::JobIdAllocator;
+    use crate::printer::{PoolConfig, PrinterPool};
+    use std::io::Cursor;
+    use std::path::Path;
+    use std::sync::Mutex;
+
+    //文档写入 `dir`，提交的任务记录在 `submitted` 中
+    fn server(dir: &Path, submitted: Arc<Mutex<Vec<rawJob>>>) -> LpdServer {
+        let pool = Arc::new(PrinterPool::new(
+            PoolConfig {
+                color_fallback: Default::default(),
+                printers: Vec::new(),
+            },
+            clock::system(),
+        ));
+        LpdServer {
+            config: LpdServerConfig::default(),
+            spooler: Arc::new(NoSPOOLing::new(pool, Arc::new(JobIdAllocator::in_memory()))),
+            submit: Arc::new(move |raw_job| {
+                let mut submitted = submitted.lock().unwrap();
+                submitted.push(raw_job);
+                Ok(submitted.len())
+            }),
+            documents: DocumentStore::in_dir(dir.to_path_buf(), "lpd"),
+        }
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("spooling-lpd-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn files_in(dir: &Path) -> usize {
+        fs::read_dir(dir).map_or(0, |entries| entries.count())
+    }
+
+    //接收任务的子命令：code count SP name LF，文件内容和结尾的 \0
+    fn subcommand(code: u8, name: &str, content: &[u8]) -> Vec<u8> {
+        let mut bytes = format!("{}{} {}\n", code as char, content.len(), name).into_bytes();
+        bytes.extend_from_slice(content);
+        bytes.push(0);
+        bytes
+    }
+
+    //把 `input` 作为 02 命令之后的数据交给 `receive_job`，返回应答
+    fn receive(server: &LpdServer, input: Vec<u8>) -> io::Result<Vec<u8>> {
+        let mut output = Vec::new();
+        server.receive_job(None, &mut Cursor::new(input), &mut output)?;
+        Ok(output)
+    }
+
+    #[test]
+    fn receives_text_and_pdf_data_files() {
+        let dir = scratch_dir("receive");
+        let submitted = Arc::new(Mutex::new(Vec::new()));
+        let server = server(&dir, submitted.clone());
+        let pdf = b"%PDF-1.4\n1 0 obj << /Type /Page >> endobj\n2 0 obj << /Type /Page >> endobj\n";
+        let control = "Hlab\nPteam1\nNa.cpp\nldfA001lab\nNb.pdf\nldfA002lab\n";
+        let input = [
+            subcommand(0x03, "dfA001lab", b"int main() {}\n"),
+            subcommand(0x03, "dfA002lab", pdf),
+            subcommand(0x02, "cfA001lab", control.as_bytes()),
+        ]
+        .concat();
+
+        assert_eq!(receive(&server, input).unwrap(), vec![ACK; 6]);
+        let submitted = submitted.lock().unwrap();
+        assert_eq!(submitted.len(), 2);
+        assert_eq!(submitted[0].team_name, "team1");
+        assert_eq!(submitted[0].problem_name, "a.cpp");
+        assert_eq!(submitted[0].file_content, "int main() {}\n");
+        assert!(submitted[0].document.is_none());
+
+        // PDF 直接作为任务的文档，文本文件已删除
+        let document = submitted[1].document.clone().unwrap();
+        assert_eq!(document.pages, 2);
+        assert_eq!(fs::read(&document.path).unwrap(), pdf);
+        assert_eq!(files_in(&dir), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rejects_jobs_over_the_size_limits() {
+        let dir = scratch_dir("size");
+        let submitted = Arc::new(Mutex::new(Vec::new()));
+        let server = server(&dir, submitted.clone());
+
+        // 声明的长度超出上限时不接收内容，也不分配内存
+        let input = format!("\x03{} dfA001lab\n", MAX_JOB_BYTES + 1).into_bytes();
+        assert_eq!(receive(&server, input).unwrap(), vec![NACK]);
+        let input = format!("\x02{} cfA001lab\n", MAX_CONTROL_FILE + 1).into_bytes();
+        assert_eq!(receive(&server, input).unwrap(), vec![NACK]);
+
+        // 多个文件合计超出上限，已收到的文件被删除
+        let mut input = subcommand(0x03, "dfA001lab", b"0123456789");
+        input.extend(format!("\x03{} dfA002lab\n", MAX_JOB_BYTES - 5).into_bytes());
+        assert_eq!(receive(&server, input).unwrap(), vec![ACK, ACK, NACK]);
+        assert_eq!(files_in(&dir), 0);
+        assert!(submitted.lock().unwrap().is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rejects_too_many_data_files() {
+        let dir = scratch_dir("count");
+        let submitted = Arc::new(Mutex::new(Vec::new()));
+        let server = server(&dir, submitted.clone());
+        let input: Vec<u8> = (0..=MAX_DATA_FILES)
+            .flat_map(|i| subcommand(0x03, &format!("df{}lab", i), b"x"))
+            .collect();
+
+        let mut expected = vec![ACK; MAX_DATA_FILES * 2];
+        expected.push(NACK);
+        assert_eq!(receive(&server, input).unwrap(), expected);
+        assert_eq!(files_in(&dir), 0);
+        assert!(submitted.lock().unwrap().is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn truncated_data_file_is_discarded() {
+        let dir = scratch_dir("truncated");
+        let server = server(&dir, Arc::new(Mutex::new(Vec::new())));
+        let mut input = b"\x03100 dfA001lab\n".to_vec();
+        input.extend_from_slice(&[b'x'; 10]);
+
+        assert!(receive(&server, input).is_err());
+        assert_eq!(files_in(&dir), 0);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,13 +1,38 @@
+mod backend;
+mod clock;
+mod highlight;
+mod ipp;
+mod ipp_server;
 mod job;
+mod lpd_server;
 mod osim;
 mod printer;
+mod rng;
+mod submission;
 
-use actix_web::{App, Error, HttpResponse, HttpServer, Responder, Result, web};
+use actix_web::{App, HttpRequest, HttpResponse, HttpServer, Responder, Result, web};
+use backend::BackendConfig;
 use chrono::Utc;
+use highlight::Language;
+use ipp::IppMessage;
+use ipp_server::{IppServer, IppServerConfig};
+use lpd_server::{LpdServer, LpdServerConfig};
 use osim::NoSPOOLing::NoSPOOLing;
-use osim::SPOOLing::{SPOOLing, rawJob};
-use printer::Printer;
+use osim::SPOOLing::{CancelError, SPOOLing, SpoolMode, SubmitError, rawJob};
+use osim::policy::PolicyKind;
+use osim::quota::{QuotaConfig, QuotaManager};
+use osim::job_id::JobIdAllocator;
+use osim::journal::Journal;
+use osim::spool_dir::SpoolDir;
+use osim::compare::Comparison;
+use osim::sim::{self, SimConfig};
+use osim::spooler::{Spooler, SpoolerConfig, SpoolerKind};
+use osim::workload::{self, TraceRecorder, WorkloadConfig};
+use printer::{
+    ColorFallback, FaultConfig, PoolConfig, PrintSpeed, PrinterConfig, PrinterFault, PrinterPool,
+};
 use serde_json::json;
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use actix_files::NamedFile;
@@ -28,19 +53,38 @@ struct PrintRequest {
     file_content: String,
     color: bool,
     problem_name: String,
+    language: Option<String>,  // 源代码语言，优先于 file_name
+    file_name: Option<String>, // 源文件名，按扩展名判断语言
 }
 
-//SPOOLing
 struct AppState {
-    spooling: Arc<SPOOLing>,
+    spooler: Arc<dyn Spooler>,
+    comparison: Option<Arc<Comparison>>, // 仅对比模式
+    trace: Option<TraceRecorder>,        // --record-trace 时记录每次提交
+    ipp: IppServer,
+
+    printers: Arc<PrinterPool>,
+    admin_token: Option<String>,
+    team_tokens: HashMap<String, String>, // 队伍名 -> 口令，读取自 teams.json
 }
 
-// /NoSPOOLing
-// struct AppState {
-//     spooling: Arc<NoSPOOLing>,
-// }
-
 async fn submit_job(data: web::Data<AppState>, req: web::Json<PrintRequest>) -> impl Responder {
+    let language = match &req.language {
+        Some(language) => match language.parse::<Language>() {
+            Ok(language) => Some(language),
+            Err(e) => {
+                return HttpResponse::BadRequest().json(json!({
+                    "status": "error",
+                    "message": format!("提交失败: {}", e)
+                }));
+            }
+        },
+        None => req
+            .file_name
+            .as_deref()
+            .and_then(Language::from_file_name)
+            .or_else(|| Language::from_file_name(&req.problem_name)),
+    };
     let raw_job = rawJob {
         priority: req.priority,
         team_name: req.team_name.to_string(),
@@ -48,9 +92,11 @@ async fn submit_job(data: web::Data<AppState>, req: web::Json<PrintRequest>) ->
         file_content: req.file_content.to_string(),
         color: req.color,
         problem_name: req.problem_name.to_string(),
+        document: None,
+        language,
     };
 
-    match data.spooling.submit_job(raw_job) {
+    match enqueue(&data, raw_job) {
         Ok(job_id) => HttpResponse::Ok().json(json!({
             "status": "success",
             "message": "打印任务提交成功",
@@ -58,6 +104,11 @@ async fn submit_job(data: web::Data<AppState>, req: web::Json<PrintRequest>) ->
                 "job_id": job_id
             }
         })),
+        Err(e @ SubmitError::QuotaExceeded(_)) => HttpResponse::TooManyRequests().json(json!({
+            "status": "error",
+            "code": "quota_exceeded",
+            "message": format!("提交失败: {}", e)
+        })),
         Err(e) => HttpResponse::ServiceUnavailable().json(json!({
             "status": "error",
             "message": format!("提交失败: {}", e)
@@ -65,18 +116,208 @@ async fn submit_job(data: web::Data<AppState>, req: web::Json<PrintRequest>) ->
     }
 }
 
+/// 记录轨迹后把任务交给假脱机系统，/print 和 IPP 提交共用
+fn enqueue(data: &AppState, raw_job: rawJob) -> Result<usize, SubmitError> {
+    if let Some(trace) = &data.trace
+        && let Err(e) = trace.record(&raw_job)
+    {
+        eprintln!("写入轨迹文件失败：{}", e);
+    }
+    data.spooler.submit_job(raw_job)
+}
+
+/// IPP 打印入口：lp、CUPS 和系统打印对话框以 application/ipp 提交
+async fn ipp_print(data: web::Data<AppState>, http_req: HttpRequest, body: Bytes) -> impl Responder {
+    let request = match IppMessage::decode(&body) {
+        Ok(request) => request,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let client = ipp_server::Client {
+        peer: http_req.peer_addr().map(|addr| addr.ip()),
+        printer_uri: format!("ipp://{}{}", http_req.connection_info().host(), http_req.path()),
+    };
+    let response = data.ipp.handle(data.spooler.as_ref(), &client, &request, |raw_job| {
+        enqueue(&data, raw_job)
+    });
+    HttpResponse::Ok()
+        .content_type("application/ipp")
+        .body(response.encode())
+}
+
+/// 指定 --ipp 时挂载 /ipp/print
+fn ipp_routes(cfg: &mut web::ServiceConfig, enabled: bool) {
+    if enabled {
+        cfg.service(
+            web::resource("/ipp/print")
+                .app_data(web::PayloadConfig::new(submission::MAX_DOCUMENT))
+                .route(web::post().to(ipp_print)),
+        );
+    }
+}
+
+/// 校验管理员口令（请求头 X-Admin-Token），未配置口令时拒绝所有管理请求
+fn check_admin(req: &HttpRequest, data: &AppState) -> Result<(), HttpResponse> {
+    let Some(token) = &data.admin_token else {
+        return Err(HttpResponse::Forbidden().json(json!({
+            "status": "error",
+            "message": "未设置 SPOOLING_ADMIN_TOKEN，管理接口已禁用"
+        })));
+    };
+    let provided = req
+        .headers()
+        .get("X-Admin-Token")
+        .and_then(|value| value.to_str().ok());
+    if provided == Some(token.as_str()) {
+        Ok(())
+    } else {
+        Err(HttpResponse::Forbidden().json(json!({
+            "status": "error",
+            "message": "需要管理员权限"
+        })))
+    }
+}
+
+/// 按请求头 X-Team-Token 中的口令确定发出请求的队伍
+fn caller_team<'a>(req: &HttpRequest, data: &'a AppState) -> Option<&'a str> {
+    let provided = req
+        .headers()
+        .get("X-Team-Token")
+        .and_then(|value| value.to_str().ok())
+        .filter(|token| !token.is_empty())?;
+    data.team_tokens
+        .iter()
+        .find(|(_, token)| token.as_str() == provided)
+        .map(|(team_name, _)| team_name.as_str())
+}
+
+#[derive(serde::Deserialize)]
+struct QuotaQuery {
+    team_name: Option<String>,
+}
+
+/// 查询配额使用情况，指定 team_name 时只返回该队伍
+async fn get_quota(data: web::Data<AppState>, query: web::Query<QuotaQuery>) -> impl Responder {
+    let Some(quota) = data.spooler.quota() else {
+        return no_quota();
+    };
+    let teams: Vec<String> = match &query.team_name {
+        Some(team_name) => vec![team_name.clone()],
+        None => quota.all_usage().into_keys().collect(),
+    };
+    let usage: Vec<_> = teams
+        .iter()
+        .map(|team_name| {
+            let (usage, limits) = quota.usage_of(team_name);
+            json!({
+                "team_name": team_name,
+                "usage": usage,
+                "limits": limits,
+            })
+        })
+        .collect();
+    HttpResponse::Ok().json(json!({
+        "status": "success",
+        "data": usage
+    }))
+}
+
+#[derive(serde::Deserialize)]
+struct QuotaResetRequest {
+    team_name: Option<String>,
+}
+
+/// 重置配额计数（管理员），不指定 team_name 时重置所有队伍
+async fn reset_quota(
+    data: web::Data<AppState>,
+    http_req: HttpRequest,
+    req: web::Json<QuotaResetRequest>,
+) -> impl Responder {
+    if let Err(resp) = check_admin(&http_req, &data) {
+        return resp;
+    }
+    if data.spooler.quota().is_none() {
+        return no_quota();
+    }
+    if let Err(e) = data.spooler.reset_quota(req.team_name.as_deref()) {
+        return HttpResponse::InternalServerError().json(json!({
+            "status": "error",
+            "message": format!("重置失败: {}", e)
+        }));
+    }
+    HttpResponse::Ok().json(json!({
+        "status": "success",
+    }))
+}
+
+fn no_quota() -> HttpResponse {
+    HttpResponse::NotFound().json(json!({
+        "status": "error",
+        "message": "当前模式不支持配额"
+    }))
+}
+
 /// 获取spooling系统运行状态
 async fn get_status(data: web::Data<AppState>) -> impl Responder {
-    let status = data.spooling.get_status();
+    let status = data.spooler.get_status();
+    HttpResponse::Ok().json(json!({
+        "status": "success",
+        "data": status,
+        "printers": data.printers.info()
+    }))
+}
+
+/// 对比模式下两种系统的接收率和延迟
+async fn compare(data: web::Data<AppState>) -> impl Responder {
+    match &data.comparison {
+        Some(comparison) => HttpResponse::Ok().json(json!({
+            "status": "success",
+            "data": comparison.report()
+        })),
+        None => HttpResponse::NotFound().json(json!({
+            "status": "error",
+            "message": "未启用对比模式（--mode compare）"
+        })),
+    }
+}
+
+/// 获取各打印机状态
+async fn get_printers(data: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(json!({
+        "status": "success",
+        "data": data.printers.info()
+    }))
+}
+
+#[derive(serde::Deserialize)]
+struct FaultRequest {
+    name: String,
+    fault: Option<PrinterFault>, // 为空时排除故障
+}
+
+/// 注入或排除打印机故障（管理员）
+async fn set_printer_fault(
+    data: web::Data<AppState>,
+    http_req: HttpRequest,
+    req: web::Json<FaultRequest>,
+) -> impl Responder {
+    if let Err(resp) = check_admin(&http_req, &data) {
+        return resp;
+    }
+    if !data.printers.set_fault(&req.name, req.fault) {
+        return HttpResponse::NotFound().json(json!({
+            "status": "error",
+            "message": format!("打印机 {} 不存在", req.name)
+        }));
+    }
     HttpResponse::Ok().json(json!({
         "status": "success",
-        "data": status
+        "data": data.printers.info()
     }))
 }
 
 /// 返回完成任务的id
 async fn get_active_id(data: web::Data<AppState>) -> impl Responder {
-    let ids = data.spooling.get_active_job_id();
+    let ids = data.spooler.get_active_job_id();
     HttpResponse::Ok().json(json!({
         "status": "success",
         "data": {
@@ -85,14 +326,15 @@ async fn get_active_id(data: web::Data<AppState>) -> impl Responder {
     }))
 }
 
-/// 返回总任务和打印完的任务
-async fn count_task() -> impl Responder {
-    let (all_task, completed_task) = job::stats();
+/// 返回总任务和打印完的任务，breakdown 为各项统计
+async fn count_task(data: web::Data<AppState>) -> impl Responder {
+    let stats = data.spooler.stats();
     HttpResponse::Ok().json(json!({
         "status": "success",
         "data": {
-            "all_task":all_task,
-            "completed_task":completed_task
+            "all_task":stats.accepted,
+            "completed_task":stats.printed,
+            "breakdown":stats
         }
     }))
 }
@@ -102,15 +344,13 @@ struct JobIdRequest {
     id: u64,
 }
 async fn get_job_info(data: web::Data<AppState>, req: web::Json<JobIdRequest>) -> impl Responder {
-    let job_id = req.id;
-    let status_map = data.spooling.status_map.lock().unwrap();
-
-    if let Some(job) = status_map.get(&job_id) {
+    if let Some(job) = data.spooler.get_job(req.id) {
         let fmt = |dt: &DateTime<_>| dt.format("%Y/%m/%d %H:%M:%S").to_string();
 
         let json = json!({
             "job_id": job.job_id,
             "priority": job.priority,
+            "effective_priority": data.spooler.effective_priority(&job),
             "team_name": job.team_name,
             "file_name": job.file_name,
             "problem_name": job.problem_name,
@@ -136,8 +376,7 @@ async fn get_job_info(data: web::Data<AppState>, req: web::Json<JobIdRequest>) -
 
 // 获取所有job的信息
 async fn get_all_info(data: web::Data<AppState>) -> impl Responder {
-    let status_map = data.spooling.status_map.lock().unwrap();
-    let jobs: Vec<_> = status_map.values().cloned().collect();
+    let jobs = data.spooler.list_jobs();
     HttpResponse::Ok().json(json!({
         "status": "success",
         "data": jobs
@@ -149,11 +388,9 @@ async fn download_file(
     data: web::Data<AppState>,
     req: web::Json<JobIdRequest>,
 ) -> actix_web::Result<NamedFile> {
-    let job_id = req.id;
-    let status_map = data.spooling.status_map.lock().unwrap();
-    if let Some(job) = status_map.get(&job_id) {
+    if let Some(job) = data.spooler.get_job(req.id) {
         let file_path = PathBuf::from(format!("./output/{}.pdf", job.file_name));
-        print!("./output/{}", job.clone().file_name);
+        println!("[INFO] 下载 Job {} 的文件 {}", job.job_id, file_path.display());
         Ok(NamedFile::open(file_path)?)
     } else {
         // 返回404
@@ -163,14 +400,12 @@ async fn download_file(
 
 async fn download_all_files(data: web::Data<AppState>) -> Result<HttpResponse, actix_web::Error> {
     // 拿所有任务，包括未完成的
-    let jobs: Vec<_> = {
-        let status_map = data.spooling.status_map.lock().unwrap();
-        status_map
-            .values()
-            .filter(|job| job.status == job::JobStatus::Completed)
-            .cloned()
-            .collect()
-    };
+    let jobs: Vec<_> = data
+        .spooler
+        .list_jobs()
+        .into_iter()
+        .filter(|job| job.status == job::JobStatus::Completed)
+        .collect();
 
     let mut cursor = Cursor::new(Vec::new());
     {
@@ -212,22 +447,291 @@ async fn download_all_files(data: web::Data<AppState>) -> Result<HttpResponse, a
         .streaming(stream))
 }
 
+/// 暂停打印（管理员），仍然接收任务
+async fn pause_spooler(data: web::Data<AppState>, http_req: HttpRequest) -> impl Responder {
+    if let Err(resp) = check_admin(&http_req, &data) {
+        return resp;
+    }
+    mode_response(data.spooler.pause(), &data)
+}
+
+/// 恢复打印（管理员）
+async fn resume_spooler(data: web::Data<AppState>, http_req: HttpRequest) -> impl Responder {
+    if let Err(resp) = check_admin(&http_req, &data) {
+        return resp;
+    }
+    mode_response(data.spooler.resume(), &data)
+}
+
+/// 排空（管理员）：不再接收新任务，剩余任务打印完后停止
+async fn drain_spooler(data: web::Data<AppState>, http_req: HttpRequest) -> impl Responder {
+    if let Err(resp) = check_admin(&http_req, &data) {
+        return resp;
+    }
+    let result = data.spooler.start_drain();
+    if result.is_ok() {
+        let spooler = data.spooler.clone();
+        std::thread::spawn(move || spooler.drain(None));
+    }
+    mode_response(result, &data)
+}
+
+fn mode_response(result: Result<(), SpoolMode>, data: &AppState) -> HttpResponse {
+    match result {
+        Ok(()) => HttpResponse::Ok().json(json!({
+            "status": "success",
+            "data": { "mode": data.spooler.mode() }
+        })),
+        Err(mode) => HttpResponse::Conflict().json(json!({
+            "status": "error",
+            "message": format!("当前模式 {:?} 下不能执行该操作", mode)
+        })),
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct CancelRequest {
+    id: u64,
+}
+
+/// 取消任务：队伍凭口令只能取消自己的任务，管理员可以取消任意任务
+async fn cancel_job(
+    data: web::Data<AppState>,
+    http_req: HttpRequest,
+    req: web::Json<CancelRequest>,
+) -> impl Responder {
+    if check_admin(&http_req, &data).is_err() {
+        let Some(team_name) = caller_team(&http_req, &data) else {
+            return HttpResponse::Forbidden().json(json!({
+                "status": "error",
+                "message": "需要队伍口令（X-Team-Token）或管理员口令"
+            }));
+        };
+        if data
+            .spooler
+            .get_job(req.id)
+            .is_some_and(|job| job.team_name != team_name)
+        {
+            return HttpResponse::Forbidden().json(json!({
+                "status": "error",
+                "message": "只能取消本队伍的任务"
+            }));
+        }
+    }
+
+    match data.spooler.cancel(req.id) {
+        Ok(()) => HttpResponse::Ok().json(json!({
+            "status": "success",
+            "message": "任务已取消"
+        })),
+        Err(e @ CancelError::NotFound) => HttpResponse::NotFound().json(json!({
+            "status": "error",
+            "message": format!("取消失败: {}", e)
+        })),
+        Err(e @ CancelError::Unrecorded(_)) => HttpResponse::InternalServerError().json(json!({
+            "status": "error",
+            "message": format!("取消失败: {}", e)
+        })),
+        Err(e) => HttpResponse::Conflict().json(json!({
+            "status": "error",
+            "message": format!("取消失败: {}", e)
+        })),
+    }
+}
+
 #[derive(serde::Deserialize)]
 struct DeleteRequest {
     job_ids: Vec<u64>,
 }
 async fn clear_all(data: web::Data<AppState>, req: web::Json<DeleteRequest>) -> impl Responder {
-    let mut status_map = data.spooling.status_map.lock().unwrap();
-    for job_id in &req.job_ids{
-        status_map.remove(&job_id);
-    }
+    data.spooler.delete_jobs(&req.job_ids);
     HttpResponse::Ok().json(json!({
         "status": "success",
     }))
 }
 
+/// 读取命令行参数的值，支持 `--name value` 和 `--name=value`
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if let Some(value) = arg.strip_prefix(name).and_then(|rest| rest.strip_prefix('=')) {
+            return Some(value.to_string());
+        }
+        if arg == name {
+            return Some(args.next().unwrap_or_default());
+        }
+    }
+    None
+}
+
+/// 解析命令行参数，缺省时使用 `default`，无法解析时退出
+fn parse_arg<T: std::str::FromStr>(name: &str, default: T) -> T
+where
+    T::Err: std::fmt::Display,
+{
+    match arg_value(name) {
+        Some(value) => value.parse().unwrap_or_else(|e| {
+            eprintln!("错误：参数 {} 无效：{}", name, e);
+            std::process::exit(1);
+        }),
+        None => default,
+    }
+}
+
+/// 假脱机系统配置：读取 spooler.json，命令行参数 --mode、--policy 覆盖其中的设置
+///
+/// --policy 与 spooler.json 中的策略相同时保留其中的参数（老化、权重等），否则参数取默认值
+fn load_spooler_config() -> SpoolerConfig {
+    let mut config = match fs::read_to_string("spooler.json") {
+        Ok(content) => serde_json::from_str::<SpoolerConfig>(&content).unwrap_or_else(|e| {
+            eprintln!("错误：spooler.json 解析失败：{}", e);
+            std::process::exit(1);
+        }),
+        Err(_) => SpoolerConfig::default(),
+    };
+    config.mode = parse_arg("--mode", config.mode);
+    if let Some(value) = arg_value("--policy") {
+        let policy: PolicyKind = value.parse().unwrap_or_else(|e| {
+            eprintln!("错误：{}", e);
+            std::process::exit(1);
+        });
+        if policy.kind_name() != config.policy.kind_name() {
+            config.policy = policy;
+        }
+    }
+    config
+}
+
+/// 读取队伍配额配置，不存在时不限制
+fn load_quota_config() -> QuotaConfig {
+    match fs::read_to_string("quota.json") {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            eprintln!("错误：quota.json 解析失败：{}", e);
+            std::process::exit(1);
+        }),
+        Err(_) => QuotaConfig::default(),
+    }
+}
+
+/// 读取队伍口令（队伍名 -> 口令），不存在时队伍不能通过 HTTP 取消任务
+fn load_team_tokens() -> HashMap<String, String> {
+    match fs::read_to_string("teams.json") {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            eprintln!("错误：teams.json 解析失败：{}", e);
+            std::process::exit(1);
+        }),
+        Err(_) => HashMap::new(),
+    }
+}
+
+/// 读取 IPP 服务配置，不存在时直接用用户名作队伍名
+fn load_ipp_config() -> IppServerConfig {
+    match fs::read_to_string("ipp.json") {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            eprintln!("错误：ipp.json 解析失败：{}", e);
+            std::process::exit(1);
+        }),
+        Err(_) => IppServerConfig::default(),
+    }
+}
+
+/// 读取 LPD 服务配置，不存在时接受任意队列、直接用用户名作队伍名
+fn load_lpd_config() -> LpdServerConfig {
+    match fs::read_to_string("lpd.json") {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            eprintln!("错误：lpd.json 解析失败：{}", e);
+            std::process::exit(1);
+        }),
+        Err(_) => LpdServerConfig::default(),
+    }
+}
+
+/// 读取打印机池配置，不存在时使用一台彩色打印机
+fn load_pool_config() -> PoolConfig {
+    let pool_config: PoolConfig = match fs::read_to_string("printers.json") {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            eprintln!("错误：printers.json 解析失败：{}", e);
+            std::process::exit(1);
+        }),
+        Err(_) => PoolConfig {
+            color_fallback: ColorFallback::Wait,
+            printers: vec![PrinterConfig {
+                name: "printer-1".to_string(),
+                color: true,
+                location: "default".to_string(),
+                speed: PrintSpeed::default(),
+                faults: FaultConfig::default(),
+                backend: BackendConfig::default(),
+            }],
+        },
+    };
+    if pool_config.printers.is_empty() {
+        eprintln!("错误：printers.json 中至少需要一台打印机！");
+        std::process::exit(1);
+    }
+    if let Err(e) = pool_config.validate() {
+        eprintln!("错误：printers.json 配置无效：{}", e);
+        std::process::exit(1);
+    }
+    pool_config
+}
+
+/// 模拟模式：在虚拟时间中运行负载，把结果以 JSON 输出后退出，不启动 HTTP 服务
+fn run_simulation() -> std::io::Result<()> {
+    let spooler_config = load_spooler_config();
+    let config = SimConfig {
+        spooling: true,
+        buffer_sizes: BUFFER_SIZES,
+        policy: spooler_config.policy,
+        quota: load_quota_config(),
+        pool: load_pool_config(),
+        sample_ms: parse_arg("--sample-ms", 60_000),
+        seed: parse_arg("--seed", 42),
+    };
+    // 负载来源：轨迹文件、负载配置，或固定间隔的任务流
+    let workload = if let Some(path) = arg_value("--trace") {
+        workload::load_trace(&path)?
+    } else if let Some(path) = arg_value("--workload") {
+        let workload_config: WorkloadConfig = serde_json::from_str(&fs::read_to_string(&path)?)
+            .unwrap_or_else(|e| {
+                eprintln!("错误：{} 解析失败：{}", path, e);
+                std::process::exit(1);
+            });
+        workload::generate(&workload_config)
+    } else {
+        sim::steady_workload(parse_arg("--jobs", 1000), parse_arg("--interval-ms", 1000))
+    };
+    if let Some(path) = arg_value("--save-trace") {
+        workload::save_trace(&path, &workload)?;
+    }
+
+    let started = std::time::Instant::now();
+    let no_spooling = || SimConfig {
+        spooling: false,
+        ..config.clone()
+    };
+    let report = match spooler_config.mode {
+        SpoolerKind::Spooling => json!(sim::simulate(config.clone(), workload)),
+        SpoolerKind::NoSpooling => json!(sim::simulate(no_spooling(), workload)),
+        SpoolerKind::Compare => json!({
+            "spooling": sim::simulate(config.clone(), workload.clone()),
+            "no_spooling": sim::simulate(no_spooling(), workload),
+        }),
+    };
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    eprintln!("模拟完成，用时 {:?}", started.elapsed());
+    Ok(())
+}
+
+/// 输入缓冲区、输入井、输出井、输出缓冲区的容量
+const BUFFER_SIZES: [usize; 4] = [10, 10, 10, 10];
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    if std::env::args().any(|arg| arg == "--simulate") {
+        return run_simulation();
+    }
+
     // 检查 fonts 文件夹是否存在
     if !Path::new("fonts").exists() {
         eprintln!("错误：fonts 文件夹不存在，请先准备字体文件！");
@@ -240,27 +744,100 @@ async fn main() -> std::io::Result<()> {
         println!("output 文件夹不存在，已自动创建。");
     }
 
-    // 创建打印机和 SPOOLing 系统
-    let printer = Arc::new(Printer::new());
-    let spooling = Arc::new(SPOOLing::new(10, 10, 10, 10));
-
-    // 启动 SPOOLing 工作线程
-    spooling.clone().start_workers(printer);
+    let quota_config = load_quota_config();
+    let pool_config = load_pool_config();
+
+    // 创建打印机池和假脱机系统
+    let spooler_config = load_spooler_config();
+    let kind = spooler_config.mode;
+    println!("假脱机模式：{}", kind);
+    let ids = Arc::new(JobIdAllocator::open("next_job_id")?);
+    let clock = clock::system();
+    let direct_pool = |config: &PoolConfig| {
+        // 对比模式下 NoSPOOLing 使用同样配置的另一组打印机；一律生成 PDF 到单独的目录，
+        // 避免真实打印机（raw、lpr、ipp）把每份任务打印两次
+        let mut config = config.clone();
+        for printer in &mut config.printers {
+            printer.name = format!("{}-direct", printer.name);
+            printer.backend = BackendConfig::Pdf {
+                dir: PathBuf::from("./output/direct"),
+            };
+        }
+        let pool = Arc::new(PrinterPool::new(config, clock.clone()));
+        pool.start_background();
+        pool
+    };
+    let printers = Arc::new(PrinterPool::new(pool_config.clone(), clock.clone()));
+    printers.start_background();
+    let (spooler, comparison): (Arc<dyn Spooler>, _) = match kind {
+        SpoolerKind::Spooling | SpoolerKind::Compare => {
+            let [input_buffer, input_well, output_well, output_buffer] = BUFFER_SIZES;
+            let spooling = SPOOLing::new(
+                input_buffer,
+                input_well,
+                output_well,
+                output_buffer,
+                spooler_config.policy,
+                QuotaManager::new(quota_config),
+                ids.clone(),
+                Some(SpoolDir::open("spool")?),
+                Some(Journal::open("journal", clock.clone())?),
+                clock.clone(),
+            );
+            // 启动 SPOOLing 工作线程
+            spooling.start_workers(printers.clone());
+            let spooling: Arc<dyn Spooler> = Arc::new(spooling);
+
+            if kind == SpoolerKind::Compare {
+                let no_spooling = Arc::new(NoSPOOLing::new(direct_pool(&pool_config), ids));
+                let comparison = Arc::new(Comparison::new(spooling, no_spooling));
+                (comparison.clone() as Arc<dyn Spooler>, Some(comparison))
+            } else {
+                (spooling, None)
+            }
+        }
+        SpoolerKind::NoSpooling => (Arc::new(NoSPOOLing::new(printers.clone(), ids)) as _, None),
+    };
 
+    let admin_token = std::env::var("SPOOLING_ADMIN_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty());
+    if admin_token.is_none() {
+        println!("未设置 SPOOLING_ADMIN_TOKEN，管理接口已禁用");
+    }
     let app_state = web::Data::new(AppState {
-        spooling: spooling.clone(),
+        spooler: spooler.clone(),
+        comparison,
+        trace: arg_value("--record-trace")
+            .map(TraceRecorder::open)
+            .transpose()?,
+        ipp: IppServer::new(load_ipp_config()),
+        printers: printers.clone(),
+        admin_token,
+        team_tokens: load_team_tokens(),
     });
 
-    // let nospooling = Arc::new(NoSPOOLing::new(printer));
-    // let app_state = web::Data::new(AppState {
-    //     spooling: nospooling,
-    // });
+    // 指定 --lpd 时同时接受 lpr 提交
+    if let Some(address) = arg_value("--lpd") {
+        let state = app_state.clone();
+        let lpd = LpdServer::new(
+            load_lpd_config(),
+            spooler.clone(),
+            Arc::new(move |raw_job| enqueue(&state, raw_job)),
+        );
+        Arc::new(lpd).start(&address)?;
+    }
+
+    // 指定 --ipp 时才开放 /ipp/print，它不需要登录即可提交大文档
+    let ipp_enabled = std::env::args().any(|arg| arg == "--ipp");
 
     HttpServer::new(move || {
         App::new()
             .app_data(app_state.clone())
             .route("/print", web::post().to(submit_job))
             .route("/status", web::get().to(get_status)) // 改为 GET 路由
+            .route("/printers", web::get().to(get_printers))
+            .route("/compare", web::get().to(compare))
             .route("/get_active_id", web::get().to(get_active_id))
             .route("/count_task", web::get().to(count_task))
             .route("/get_job_info", web::post().to(get_job_info))
@@ -268,8 +845,162 @@ async fn main() -> std::io::Result<()> {
             .route("/download_file", web::post().to(download_file))
             .route("/download_all", web::get().to(download_all_files))
             .route("/clear", web::post().to(clear_all))
+            .route("/cancel", web::post().to(cancel_job))
+            .route("/quota", web::get().to(get_quota))
+            .route("/admin/quota/reset", web::post().to(reset_quota))
+            .route("/admin/pause", web::post().to(pause_spooler))
+            .route("/admin/resume", web::post().to(resume_spooler))
+            .route("/admin/drain", web::post().to(drain_spooler))
+            .route("/admin/printers/fault", web::post().to(set_printer_fault))
+            .configure(|cfg| ipp_routes(cfg, ipp_enabled))
     })
-    .bind("127.0.0.1:8080")?
+    .bind(arg_value("--bind").unwrap_or_else(|| "127.0.0.1:8080".to_string()))?
     .run()
-    .await
+    .await?;
+
+    // 服务关闭后打印完剩余任务再退出，超时后剩余任务留待重启后继续打印
+    println!("正在排空假脱机系统……");
+    spooler.drain(Some(std::time::Duration::from_secs(parse_arg(
+        "--drain-timeout",
+        60,
+    ))));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+
+    /// 不启动工作线程的 SPOOLing，提交的任务停在输入缓冲区
+    fn state(admin_token: Option<&str>) -> web::Data<AppState> {
+        let printers = Arc::new(PrinterPool::new(
+            PoolConfig {
+                color_fallback: ColorFallback::Wait,
+                printers: Vec::new(),
+            },
+            clock::system(),
+        ));
+        let spooling = SPOOLing::new(
+            10,
+            10,
+            10,
+            10,
+            PolicyKind::Priority,
+            QuotaManager::new(QuotaConfig::default()),
+            Arc::new(JobIdAllocator::in_memory()),
+            None,
+            None,
+            clock::system(),
+        );
+        web::Data::new(AppState {
+            spooler: Arc::new(spooling),
+            comparison: None,
+            trace: None,
+            ipp: IppServer::new(IppServerConfig::default()),
+            printers,
+            admin_token: admin_token.map(str::to_string),
+            team_tokens: HashMap::from([
+                ("team1".to_string(), "t1".to_string()),
+                ("team2".to_string(), "t2".to_string()),
+            ]),
+        })
+    }
+
+    #[actix_web::test]
+    async fn ipp_endpoint_is_mounted_only_with_the_flag() {
+        let mut request = IppMessage::new(ipp::GET_PRINTER_ATTRIBUTES, 1);
+        request.groups.push(ipp::operation_group());
+        let body = request.encode();
+
+        for enabled in [false, true] {
+            let app = test::init_service(
+                App::new()
+                    .app_data(state(None))
+                    .configure(|cfg| ipp_routes(cfg, enabled)),
+            )
+            .await;
+            let response = test::call_service(
+                &app,
+                test::TestRequest::post()
+                    .uri("/ipp/print")
+                    .insert_header(("Content-Type", "application/ipp"))
+                    .set_payload(body.clone())
+                    .to_request(),
+            )
+            .await;
+            if !enabled {
+                assert_eq!(response.status(), StatusCode::NOT_FOUND);
+                continue;
+            }
+            assert_eq!(response.status(), StatusCode::OK);
+            let response = IppMessage::decode(&test::read_body(response).await).unwrap();
+            assert_eq!(response.code, ipp::SUCCESSFUL_OK);
+            assert!(response.value("printer-state").is_some());
+        }
+    }
+
+    fn submit(data: &AppState, team_name: &str) -> u64 {
+        let raw_job = rawJob {
+            priority: 1,
+            team_name: team_name.to_string(),
+            submit_time: Utc::now(),
+            file_content: "int main() {}".to_string(),
+            color: false,
+            problem_name: "A".to_string(),
+            document: None,
+            language: None,
+        };
+        data.spooler.submit_job(raw_job).unwrap() as u64
+    }
+
+    #[actix_web::test]
+    async fn teams_cancel_own_jobs_and_admin_cancels_any() {
+        let data = state(Some("admin"));
+        let app = test::init_service(
+            App::new()
+                .app_data(data.clone())
+                .route("/cancel", web::post().to(cancel_job)),
+        )
+        .await;
+        let team1 = [submit(&data, "team1"), submit(&data, "team1")];
+        let team2 = submit(&data, "team2");
+
+        let cancel = |id: u64, header: Option<(&'static str, &'static str)>| {
+            let mut request = test::TestRequest::post()
+                .uri("/cancel")
+                .set_json(json!({ "id": id }));
+            if let Some(header) = header {
+                request = request.insert_header(header);
+            }
+            test::call_service(&app, request.to_request())
+        };
+        let status = |id: u64| data.spooler.get_job(id).unwrap().status;
+
+        // 没有口令或口令不对
+        assert_eq!(cancel(team1[0], None).await.status(), StatusCode::FORBIDDEN);
+        let response = cancel(team1[0], Some(("X-Team-Token", "wrong"))).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        // 其他队伍的任务
+        let response = cancel(team1[0], Some(("X-Team-Token", "t2"))).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(status(team1[0]), job::JobStatus::Waiting);
+        // 本队伍的任务
+        let response = cancel(team1[0], Some(("X-Team-Token", "t1"))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(status(team1[0]), job::JobStatus::Cancelled);
+        let response = cancel(team1[0], Some(("X-Team-Token", "t1"))).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let response = cancel(99, Some(("X-Team-Token", "t1"))).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        // 管理员可以取消任意队伍的任务，队伍口令不能当作管理员口令
+        let response = cancel(team2, Some(("X-Admin-Token", "t1"))).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        for id in [team1[1], team2] {
+            let response = cancel(id, Some(("X-Admin-Token", "admin"))).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(status(id), job::JobStatus::Cancelled);
+        }
+    }
 }
@@ -1,80 +1,122 @@
 use crate::job::{Job, JobStatus};
-use crate::osim::SPOOLing::rawJob;
-use crate::printer::{Printer, PrinterStatus};
+use crate::osim::job_id::JobIdAllocator;
+use crate::osim::stats::{SpoolStats, StatsSnapshot};
+use crate::osim::SPOOLing::{CancelError, SpoolMode, SubmitError, discard_document, rawJob};
+use crate::osim::spooler::Spooler;
+use crate::printer::{DispatchError, PrintCallback, PrinterPool};
 use chrono::{DateTime, Utc};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 故障中断的任务找不到其他空闲打印机时失败，模拟中同样处理
+pub fn fail_unrerouted(job: &mut Job, now: DateTime<Utc>) {
+    job.fail("打印机故障".to_string(), now);
+}
 
 #[derive(Clone)]
 pub struct NoSPOOLing {
     pub status_map: Arc<Mutex<HashMap<u64, Job>>>,
-    printer: Arc<Printer>,
+    pool: Arc<PrinterPool>,
+    ids: Arc<JobIdAllocator>,
+    stats: Arc<SpoolStats>,
 }
 impl NoSPOOLing {
-    pub fn new(printer: Arc<Printer>) -> Self {
+    pub fn new(pool: Arc<PrinterPool>, ids: Arc<JobIdAllocator>) -> Self {
         Self {
             status_map: Arc::new(Mutex::new(HashMap::new())),
-            printer: printer,
+            pool,
+            ids,
+            stats: Arc::new(SpoolStats::new()),
+        }
+    }
+
+    /// 写入任务状态并更新统计计数
+    fn record(status_map: &Mutex<HashMap<u64, Job>>, stats: &SpoolStats, job: &Job) {
+        let mut status_map = status_map.lock().unwrap();
+        let previous = status_map.get(&(job.job_id as u64));
+        if job.status.is_finished() && !previous.is_some_and(|previous| previous.status.is_finished()) {
+            job.discard_document();
         }
+        stats.observe(previous, job);
+        status_map.insert(job.job_id as u64, job.clone());
     }
-    pub fn get_status(&self) -> String {
-        let status = self.printer.clone().get_status();
-        match status {
-            PrinterStatus::Free => {
-                format!("OK")
+
+    /// 打印机回调时写回状态表；打印机故障中断的任务换一台空闲打印机，没有时失败
+    fn on_event(
+        pool: Arc<PrinterPool>,
+        status_map: Arc<Mutex<HashMap<u64, Job>>>,
+        stats: Arc<SpoolStats>,
+    ) -> PrintCallback {
+        Arc::new(move |job: &Job| {
+            Self::record(&status_map, &stats, job);
+            if job.status != JobStatus::Waiting {
+                return;
             }
-            PrinterStatus::Printing => {
-                format!("OK")
+            let on_event = Self::on_event(pool.clone(), status_map.clone(), stats.clone());
+            match pool.submit_task(job.clone(), on_event) {
+                Ok(dispatched) => {
+                    println!("任务 {} 改由打印机 {} 打印", job.job_id, dispatched.printer)
+                }
+                Err(DispatchError::Busy(mut job) | DispatchError::Rejected { mut job, .. }) => {
+                    println!("任务 {} 打印中断，没有其他空闲打印机", job.job_id);
+                    fail_unrerouted(&mut job, pool.clock().now());
+                    Self::record(&status_map, &stats, &job);
+                }
             }
-        }
+        })
     }
-    pub fn submit_job(&self, data: rawJob) -> Result<usize, String> {
-        // 创建新的 Job
-        let mut job = Job::new(
-            data.priority,
-            data.team_name,
-            data.submit_time,
-            data.file_content,
-            data.color,
-            data.problem_name,
-        );
+}
 
+impl Spooler for NoSPOOLing {
+    fn get_status(&self) -> String {
+        if self.pool.all_free() {
+            "OK".to_string()
+        } else {
+            "打印机忙碌".to_string()
+        }
+    }
+    /// 交给打印机失败的任务在写入提交失败状态时删除文档
+    fn submit_job(&self, data: rawJob) -> Result<usize, SubmitError> {
+        let document = data.document.clone();
+        let job = data.admit(None, &self.ids).inspect_err(|_| {
+            self.stats.reject();
+            discard_document(document.as_ref());
+        })?;
         let job_id = job.job_id;
-        job.status = JobStatus::Waiting;
-        let mut status_map = self.status_map.clone();
-        status_map
-            .lock()
-            .unwrap()
-            .insert(job.job_id as u64, job.clone());
+        Self::record(&self.status_map, &self.stats, &job);
+
+        let on_event =
+            Self::on_event(self.pool.clone(), self.status_map.clone(), self.stats.clone());
 
-        // 尝试推入输入缓冲区
-        match self.printer.submit_task(job.clone()) {
-            Ok(_) => {
-                let job_id = job.job_id.clone();
-                job.status = JobStatus::Completed;
-                let mut status_map = self.status_map.clone();
-                status_map
-                    .lock()
-                    .unwrap()
-                    .insert(job.clone().job_id as u64, job.clone());
-                println!("任务 {} 已开始打印", job_id);
+        // 直接交给打印机
+        match self.pool.submit_task(job.clone(), on_event) {
+            Ok(dispatched) => {
+                println!("任务 {} 已交给打印机 {}", job_id, dispatched.printer);
                 Ok(job_id)
             }
-            Err(mut job) => {
-                println!("任务 {} 提交失败", job_id);
+            Err(err) => {
+                let (mut job, error) = match err {
+                    DispatchError::Busy(job) => {
+                        println!("任务 {} 提交失败", job_id);
+                        (job, SubmitError::BufferFull)
+                    }
+                    DispatchError::Rejected { job, reason } => {
+                        println!("任务 {} 被拒绝：{}", job_id, reason);
+                        (job, SubmitError::Unprintable(reason))
+                    }
+                };
 
                 job.status = crate::job::JobStatus::SubmitFailed;
-                status_map
-                    .lock()
-                    .unwrap()
-                    .insert(job.job_id as u64, job.clone());
+                Self::record(&self.status_map, &self.stats, &job);
 
-                Err("缓冲区已满".to_string())
+                Err(error)
             }
         }
     }
     /// 获取所有提交成功的任务id
-    pub fn get_active_job_id(&self) -> Vec<u64> {
+    fn get_active_job_id(&self) -> Vec<u64> {
         let status_map = self.status_map.lock().unwrap();
         status_map
             .iter()
@@ -87,4 +129,56 @@ impl NoSPOOLing {
             })
             .collect()
     }
+
+    fn get_job(&self, job_id: u64) -> Option<Job> {
+        self.status_map.lock().unwrap().get(&job_id).cloned()
+    }
+
+    fn list_jobs(&self) -> Vec<Job> {
+        self.status_map.lock().unwrap().values().cloned().collect()
+    }
+
+    fn delete_jobs(&self, job_ids: &[u64]) {
+        let mut status_map = self.status_map.lock().unwrap();
+        for job_id in job_ids {
+            status_map.remove(job_id);
+        }
+    }
+
+    /// 任务提交后立即开始打印，只能中止正在打印的任务
+    fn cancel(&self, job_id: u64) -> Result<(), CancelError> {
+        let job = self.get_job(job_id).ok_or(CancelError::NotFound)?;
+        if job.status.is_finished() {
+            return Err(CancelError::AlreadyFinished);
+        }
+        // 打印机中止后通过回调写入已取消状态；找不到说明刚好打印结束
+        if !self.pool.cancel(job.job_id) {
+            return Err(CancelError::AlreadyFinished);
+        }
+        println!("[INFO] Job {} 正在打印，已通知打印机中止", job_id);
+        Ok(())
+    }
+
+    fn stats(&self) -> StatsSnapshot {
+        self.stats.snapshot()
+    }
+
+    /// 等待所有已交给打印机的任务结束；超时后不再等待，未结束的任务不会保留
+    fn drain(&self, timeout: Option<Duration>) -> SpoolMode {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        while !self
+            .status_map
+            .lock()
+            .unwrap()
+            .values()
+            .all(|job| job.status.is_finished())
+        {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                println!("[WARN] 排空超时，仍有任务没有打印完");
+                break;
+            }
+            thread::sleep(Duration::from_millis(200));
+        }
+        SpoolMode::Stopped
+    }
 }
@@ -1,13 +1,24 @@
-use crate::job::{Job, JobStatus};
-use crate::printer::Printer;
+use crate::clock::Clock;
+use crate::highlight::Language;
+use crate::job::{self, Document, Job, JobStatus};
+use crate::printer::{DispatchError, PrintCallback, PrinterPool};
+use crate::osim::policy::{AgingConfig, PolicyKind, SchedulingPolicy};
+use crate::osim::quota::{QuotaManager, QuotaViolation, UsageLedger};
+use crate::osim::job_id::JobIdAllocator;
+use crate::osim::journal::{self, Journal, JournalEvent};
+use crate::osim::spooler::Spooler;
+use crate::osim::stats::{SpoolStats, StatsSnapshot};
+use crate::osim::spool_dir::{SpoolDir, SpoolStage};
 use chrono::{DateTime, Utc};
-use std::cmp::Ordering;
-use std::cmp::Reverse; // 用于反转比较实现小根堆
-use std::collections::BinaryHeap;
-use std::collections::{HashMap, VecDeque};
-use std::sync::{Arc, Condvar, Mutex};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
 use std::thread;
+use std::time::{Duration, Instant};
 
+#[allow(non_camel_case_types)]
+#[derive(Clone)]
 pub struct rawJob {
     pub priority: u32,
     pub team_name: String,
@@ -15,50 +26,163 @@ pub struct rawJob {
     pub file_content: String,
     pub color: bool,
     pub problem_name: String,
+    pub document: Option<Document>, // 现成的 PDF 文档
+    pub language: Option<Language>, // 源代码语言，用于语法高亮
+}
+
+impl rawJob {
+    /// 估算的打印页数，附带文档时使用文档的页数
+    pub fn page_count(&self) -> usize {
+        match &self.document {
+            Some(document) => document.pages.max(1),
+            None => job::page_count(&self.file_content),
+        }
+    }
+
+    /// 接收这次提交：预留配额（`quota` 为 None 时不限制），分配任务id，生成等待中的任务。
+    /// 分配id失败时撤销配额预留。SPOOLing、NoSPOOLing 和离散事件模拟共用
+    pub fn admit(self, quota: Option<&QuotaManager>, ids: &JobIdAllocator) -> Result<Job, SubmitError> {
+        let pages = self.page_count();
+        if let Some(quota) = quota {
+            quota
+                .try_reserve(&self.team_name, pages)
+                .map_err(SubmitError::QuotaExceeded)?;
+        }
+        let job_id = ids.allocate().map_err(|e| {
+            if let Some(quota) = quota {
+                quota.rollback(&self.team_name, pages);
+            }
+            SubmitError::IdUnavailable(e.to_string())
+        })?;
+
+        let mut job = Job::new(
+            job_id,
+            self.priority,
+            self.team_name,
+            self.submit_time,
+            self.file_content,
+            self.color,
+            self.problem_name,
+        );
+        job.document = self.document;
+        job.language = self.language;
+        job.status = JobStatus::Waiting;
+        Ok(job)
+    }
+}
+
+/// 删除没有被接收的提交附带的文档
+pub fn discard_document(document: Option<&Document>) {
+    if let Some(document) = document
+        && let Err(e) = document.discard()
+    {
+        eprintln!("[ERROR] 删除文档 {} 失败：{}", document.path, e);
+    }
 }
 
-// 实现 PartialEq 和 Eq
-impl PartialEq for rawJob {
-    fn eq(&self, other: &Self) -> bool {
-        self.priority == other.priority && self.submit_time == other.submit_time
+/// 已接收的任务没能进入输入缓冲区：撤销配额预留，标记为提交失败
+pub fn bounce(quota: &QuotaManager, job: &mut Job) {
+    quota.rollback(&job.team_name, job.page_count());
+    job.status = JobStatus::SubmitFailed;
+}
+
+/// 提交失败的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubmitError {
+    BufferFull,                    // 缓冲区已满
+    QuotaExceeded(QuotaViolation), // 超出队伍配额
+    NotAccepting(SpoolMode),       // 系统正在排空或已停止
+    IdUnavailable(String),         // 无法分配任务id
+    Unprintable(String),           // 没有能打印该任务的打印机
+    Unrecorded(String),            // 无法写入预写日志或假脱机目录
+}
+
+impl fmt::Display for SubmitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubmitError::BufferFull => write!(f, "缓冲区已满"),
+            SubmitError::QuotaExceeded(violation) => write!(f, "超出配额: {}", violation),
+            SubmitError::NotAccepting(mode) => write!(f, "系统当前不接收任务（{:?}）", mode),
+            SubmitError::IdUnavailable(e) => write!(f, "无法分配任务id: {}", e),
+            SubmitError::Unprintable(reason) => write!(f, "无法打印: {}", reason),
+            SubmitError::Unrecorded(e) => write!(f, "无法保存任务: {}", e),
+        }
     }
 }
 
-impl Eq for rawJob {}
+/// 假脱机系统运行模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpoolMode {
+    Running,  // 正常运行
+    Paused,   // 暂停打印，仍接收任务进入井中
+    Draining, // 不再接收新任务，打印完剩余任务后停止
+    Stopped,  // 已停止
+}
+
+/// 运行模式控制：暂停时输出侧的工作线程在此等待
+struct Control {
+    mode: Mutex<SpoolMode>,
+    changed: Condvar,
+}
 
-// 实现 PartialOrd 和 Ord
-impl PartialOrd for rawJob {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+impl Control {
+    fn new() -> Self {
+        Control {
+            mode: Mutex::new(SpoolMode::Running),
+            changed: Condvar::new(),
+        }
     }
+
+    fn mode(&self) -> SpoolMode {
+        *self.mode.lock().unwrap()
+    }
+
+    fn set(&self, mode: SpoolMode) {
+        *self.mode.lock().unwrap() = mode;
+        self.changed.notify_all();
+    }
+
+    /// 暂停或停止期间阻塞，直到允许继续向打印机输送任务
+    fn wait_printing_allowed(&self) {
+        let mut mode = self.mode.lock().unwrap();
+        while matches!(*mode, SpoolMode::Paused | SpoolMode::Stopped) {
+            mode = self.changed.wait(mode).unwrap();
+        }
+    }
+}
+
+/// 取消失败的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CancelError {
+    NotFound,           // 任务不存在
+    AlreadyFinished,    // 任务已经结束
+    Unrecorded(String), // 无法写入预写日志
 }
 
-impl Ord for rawJob {
-    fn cmp(&self, other: &Self) -> Ordering {
-        // 先比较优先级（注意：priority越小优先级越高）
-        match self.priority.cmp(&other.priority) {
-            // 如果优先级相同，再比较提交时间（越早的优先级越高）
-            Ordering::Equal => other.submit_time.cmp(&self.submit_time),
-            // 如果优先级不同，直接返回优先级比较结果
-            ordering => ordering,
+impl fmt::Display for CancelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CancelError::NotFound => write!(f, "任务不存在"),
+            CancelError::AlreadyFinished => write!(f, "任务已经结束"),
+            CancelError::Unrecorded(e) => write!(f, "无法写入预写日志: {}", e),
         }
     }
 }
+
 #[derive(Clone)]
 pub struct Buffer<T> {
     queue: Arc<Mutex<VecDeque<T>>>,
     max_size: usize,
     ready: Arc<Condvar>,
-    name: String,
 }
 
 impl<T> Buffer<T> {
-    pub fn new(name: &str, max_size: usize) -> Self {
+    pub fn new(max_size: usize) -> Self {
         Buffer {
             queue: Arc::new(Mutex::new(VecDeque::with_capacity(max_size))),
             max_size,
             ready: Arc::new(Condvar::new()),
-            name: name.to_string(),
         }
     }
 
@@ -92,19 +216,21 @@ impl<T> Buffer<T> {
 
     // 阻塞取出
     pub fn pop(&self) -> T {
-        // print!("1-------------\n");
         let mut queue = self.queue.lock().unwrap();
-        // print!("2-------------\n");
         while queue.is_empty() {
-            // print!("3-------------\n");
             queue = self.ready.wait(queue).unwrap();
-            // print!("4-------------\n");
         }
-        // print!("5-------------\n");
-
         let item = queue.pop_front().unwrap();
         self.ready.notify_one();
-        // queue.pop_front().unwrap()
+        item
+    }
+
+    // 取出第一个满足条件的元素
+    pub fn remove_where(&self, pred: impl Fn(&T) -> bool) -> Option<T> {
+        let mut queue = self.queue.lock().unwrap();
+        let idx = queue.iter().position(pred)?;
+        let item = queue.remove(idx);
+        self.ready.notify_one();
         item
     }
 
@@ -119,144 +245,444 @@ impl<T> Buffer<T> {
     }
 }
 
-/// 基于小根堆实现的井结构  
+/// 井结构，出队顺序由调度策略决定
 #[derive(Clone)]
-pub struct HeapWell<T: Ord> {
-    heap: Arc<Mutex<BinaryHeap<Reverse<T>>>>, // 使用Reverse实现小根堆
+pub struct HeapWell {
+    jobs: Arc<Mutex<Vec<Job>>>, // 按到达顺序保存
+    policy: Arc<Mutex<Box<dyn SchedulingPolicy>>>,
     ready: Arc<Condvar>,
     max_size: usize,
-    name: String,
+    clock: Arc<dyn Clock>, // 策略按此时间计算老化
 }
 
-impl<T: Ord> HeapWell<T> {
-    pub fn new(name: &str, max_size: usize) -> Self {
+impl HeapWell {
+    pub fn new(
+        max_size: usize,
+        policy: Box<dyn SchedulingPolicy>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
         HeapWell {
-            heap: Arc::new(Mutex::new(BinaryHeap::with_capacity(max_size))),
+            jobs: Arc::new(Mutex::new(Vec::with_capacity(max_size))),
+            policy: Arc::new(Mutex::new(policy)),
             ready: Arc::new(Condvar::new()),
             max_size,
-            name: name.to_string(),
+            clock,
         }
     }
 
-    /// 插入元素（按小根堆排序）
-    pub fn push(&self, item: T) -> Result<(), T> {
-        let mut heap = self.heap.lock().unwrap();
-        if heap.len() >= self.max_size {
-            return Err(item);
+    /// 按策略取出下一个任务
+    fn take_next(&self, jobs: &mut Vec<Job>) -> Option<Job> {
+        let idx = self.policy.lock().unwrap().select(jobs, self.clock.now())?;
+        Some(jobs.remove(idx))
+    }
+
+    /// 插入元素，井满时原样退回任务
+    pub fn push(&self, item: Job) -> Result<(), Box<Job>> {
+        let mut jobs = self.jobs.lock().unwrap();
+        if jobs.len() >= self.max_size {
+            return Err(Box::new(item));
         }
-        heap.push(Reverse(item));
+        jobs.push(item);
         self.ready.notify_one();
         Ok(())
     }
 
     /// 阻塞插入
-    pub fn push_blocking(&self, item: T) {
-        let mut heap = self.heap.lock().unwrap();
-        while heap.len() >= self.max_size {
-            heap = self.ready.wait(heap).unwrap();
+    pub fn push_blocking(&self, item: Job) {
+        let mut jobs = self.jobs.lock().unwrap();
+        while jobs.len() >= self.max_size {
+            jobs = self.ready.wait(jobs).unwrap();
         }
-        heap.push(Reverse(item));
+        jobs.push(item);
         self.ready.notify_one();
     }
 
-    /// 弹出最小元素
-    pub fn pop(&self) -> Option<T> {
-        let mut heap = self.heap.lock().unwrap();
-        heap.pop().map(|Reverse(item)| item)
+    /// 按策略弹出下一个任务
+    pub fn pop(&self) -> Option<Job> {
+        let mut jobs = self.jobs.lock().unwrap();
+        self.take_next(&mut jobs)
     }
 
     /// 阻塞弹出
-    pub fn pop_blocking(&self) -> T {
-        let mut heap = self.heap.lock().unwrap();
-        while heap.is_empty() {
-            heap = self.ready.wait(heap).unwrap();
+    pub fn pop_blocking(&self) -> Job {
+        let mut jobs = self.jobs.lock().unwrap();
+        loop {
+            if let Some(job) = self.take_next(&mut jobs) {
+                self.ready.notify_one();
+                return job;
+            }
+            jobs = self.ready.wait(jobs).unwrap();
         }
+    }
+
+    /// 放回恢复出的任务，不受容量限制
+    pub fn restore(&self, item: Job) {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.push(item);
         self.ready.notify_one();
-        heap.pop().unwrap().0
     }
 
-    /// 查看最小元素但不移除
-    pub fn peek(&self) -> Option<T>
-    where
-        T: Clone,
-    {
-        let heap = self.heap.lock().unwrap();
-        heap.peek().map(|Reverse(item)| item.clone())
+    /// 移除指定任务
+    pub fn remove(&self, job_id: usize) -> Option<Job> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let idx = jobs.iter().position(|job| job.job_id == job_id)?;
+        let job = jobs.remove(idx);
+        self.ready.notify_one();
+        Some(job)
+    }
+
+    /// 当前使用的调度策略名称
+    pub fn policy_name(&self) -> &'static str {
+        self.policy.lock().unwrap().name()
     }
 
     /// 当前大小
     pub fn len(&self) -> usize {
-        self.heap.lock().unwrap().len()
+        self.jobs.lock().unwrap().len()
+    }
+
+    /// 各队伍在井中排队的任务数
+    pub fn team_depths(&self) -> BTreeMap<String, usize> {
+        let jobs = self.jobs.lock().unwrap();
+        let mut depths = BTreeMap::new();
+        for job in jobs.iter() {
+            *depths.entry(job.team_name.clone()).or_insert(0) += 1;
+        }
+        depths
     }
 
     /// 是否为空
     pub fn is_empty(&self) -> bool {
-        self.heap.lock().unwrap().is_empty()
+        self.jobs.lock().unwrap().is_empty()
+    }
+
+    /// 容量
+    pub fn capacity(&self) -> usize {
+        self.max_size
     }
 }
 
 pub struct SPOOLing {
     input_buffer: Buffer<Job>,
-    input_well: HeapWell<Job>,
-    output_well: HeapWell<Job>,
+    input_well: HeapWell,
+    output_well: HeapWell,
     output_buffer: Buffer<Job>,
     pub status_map: Arc<Mutex<HashMap<u64, Job>>>,
+    aging: Option<AgingConfig>,
+    pub quota: Arc<QuotaManager>,
+    cancelled: Arc<Mutex<HashSet<usize>>>, // 已请求取消、但还在工作线程手中的任务
+    printers: OnceLock<Arc<PrinterPool>>,
+    control: Arc<Control>,
+    clock: Arc<dyn Clock>,
+    ids: Arc<JobIdAllocator>,
+    recorder: Recorder,
+}
+
+/// 状态表的写入口：每次变更先写预写日志再修改状态表，
+/// 同时维护队伍配额和假脱机目录
+#[derive(Clone)]
+struct Recorder {
+    status_map: Arc<Mutex<HashMap<u64, Job>>>,
+    quota: Arc<QuotaManager>,
+    ledger: Arc<Mutex<UsageLedger>>, // 按日志推算的配额使用情况，压缩时写入快照
+    stats: Arc<SpoolStats>,
+    spool: Option<Arc<SpoolDir>>,
+    journal: Option<Arc<Journal>>,
+}
+
+impl Recorder {
+    /// 在持有状态表锁时写日志并修改状态表和统计计数，日志达到阈值时顺带压缩。
+    /// 写日志失败时不做修改并返回错误，保证状态表不会超前于日志
+    fn apply(&self, map: &mut HashMap<u64, Job>, event: JournalEvent) -> io::Result<()> {
+        if let Some(journal) = &self.journal
+            && let Err(e) = journal.append(event.clone())
+        {
+            eprintln!("[ERROR] 写入预写日志失败，变更未生效：{}", e);
+            return Err(e);
+        }
+        let mut ledger = self.ledger.lock().unwrap();
+        journal::observe_quota(&mut ledger, map, &event);
+        journal::apply(map, &self.stats, event);
+        if let Some(journal) = &self.journal
+            && journal.needs_compaction()
+            && let Err(e) = journal.compact(map.values(), self.stats.snapshot(), ledger.clone())
+        {
+            eprintln!("[ERROR] 压缩预写日志失败：{}", e);
+        }
+        Ok(())
+    }
+
+    /// 立即把状态表写成快照，退出前调用，重启时不必重放日志
+    fn checkpoint(&self) -> io::Result<()> {
+        let map = self.status_map.lock().unwrap();
+        let ledger = self.ledger.lock().unwrap();
+        match &self.journal {
+            Some(journal) => journal.compact(map.values(), self.stats.snapshot(), ledger.clone()),
+            None => Ok(()),
+        }
+    }
+
+    /// 直接写入任务状态
+    fn insert(&self, job: &Job) -> io::Result<()> {
+        let mut map = self.status_map.lock().unwrap();
+        self.apply(&mut map, JournalEvent::Upsert(job.clone()))
+    }
+
+    /// 记录一次没有生成任务的拒绝
+    fn reject(&self) -> io::Result<()> {
+        let mut map = self.status_map.lock().unwrap();
+        self.apply(&mut map, JournalEvent::Rejected)
+    }
+
+    /// 重置配额计数并写入日志
+    fn reset_quota(&self, team_name: Option<&str>) -> io::Result<()> {
+        let mut map = self.status_map.lock().unwrap();
+        self.apply(&mut map, JournalEvent::QuotaReset(team_name.map(str::to_string)))?;
+        self.quota.reset(team_name);
+        Ok(())
+    }
+
+    /// 从状态表删除任务
+    fn delete(&self, job_id: u64) -> io::Result<()> {
+        let mut map = self.status_map.lock().unwrap();
+        if map.contains_key(&job_id) {
+            self.apply(&mut map, JournalEvent::Delete(job_id))?;
+        }
+        Ok(())
+    }
+
+    /// 写入任务的最新状态；已取消的任务不再被后续的打印事件覆盖，
+    /// 任务第一次进入结束状态时释放队伍的未完成任务名额并删除假脱机文件。
+    /// 写日志失败时任务保持原状态，假脱机文件保留，重启后重新处理
+    fn record(&self, job: &Job) -> io::Result<()> {
+        let mut map = self.status_map.lock().unwrap();
+        let previous = map.get(&(job.job_id as u64)).map(|job| &job.status);
+        if previous == Some(&JobStatus::Cancelled) {
+            return Ok(());
+        }
+        let finishing =
+            job.status.is_finished() && !previous.is_some_and(|status| status.is_finished());
+        self.apply(&mut map, JournalEvent::Upsert(job.clone()))?;
+        if finishing {
+            self.quota.release(&job.team_name);
+            self.unspool(job.job_id);
+            job.discard_document();
+        }
+        Ok(())
+    }
+
+    /// 把任务写入假脱机目录
+    fn spool(&self, stage: SpoolStage, job: &Job) -> io::Result<()> {
+        match &self.spool {
+            Some(spool) => spool.store(stage, job).inspect_err(|e| {
+                eprintln!("[ERROR] 写入 Job {} 的假脱机文件失败：{}", job.job_id, e);
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// 删除任务的假脱机文件
+    fn unspool(&self, job_id: usize) {
+        if let Some(spool) = &self.spool
+            && let Err(e) = spool.remove(job_id)
+        {
+            eprintln!("[ERROR] 删除 Job {} 的假脱机文件失败：{}", job_id, e);
+        }
+    }
+}
+
+/// 格式化任务内容：在开头加上队伍名和提交时间组成的信息头
+pub fn format_header(job: &mut Job) {
+    job.file_content = format!(
+        "\\\\ team_name: {}\n\\\\ submit_time: {}\n\n{}",
+        job.team_name, job.submit_time, job.file_content
+    );
+}
+
+/// 工作线程取出任务后检查是否已被取消，已取消则丢弃
+fn take_cancelled(cancelled: &Mutex<HashSet<usize>>, job: &Job) -> bool {
+    let taken = cancelled.lock().unwrap().remove(&job.job_id);
+    if taken {
+        println!("[INFO] Job {} 已取消，丢弃", job.job_id);
+    }
+    taken
 }
 
 impl SPOOLing {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         input_buffer_size: usize,
         input_well_size: usize,
         output_well_size: usize,
         output_buffer_size: usize,
+        policy: PolicyKind,
+        quota: QuotaManager,
+        ids: Arc<JobIdAllocator>,
+        spool: Option<SpoolDir>,
+        journal: Option<Journal>,
+        clock: Arc<dyn Clock>,
     ) -> Self {
-        SPOOLing {
-            input_buffer: Buffer::new("input_buffer", input_buffer_size),
-            input_well: HeapWell::new("input_well", input_well_size),
-            output_well: HeapWell::new("output_well", output_well_size),
-            output_buffer: Buffer::new("output_buffer", output_buffer_size),
-            status_map: Arc::new(Mutex::new(HashMap::new())),
+        let status_map = Arc::new(Mutex::new(HashMap::new()));
+        let quota = Arc::new(quota);
+        let recorder = Recorder {
+            status_map: status_map.clone(),
+            quota: quota.clone(),
+            ledger: Arc::new(Mutex::new(UsageLedger::default())),
+            stats: Arc::new(SpoolStats::new()),
+            spool: spool.map(Arc::new),
+            journal: journal.map(Arc::new),
+        };
+        let spooling = SPOOLing {
+            input_buffer: Buffer::new(input_buffer_size),
+            input_well: HeapWell::new(
+                input_well_size,
+                policy.build(),
+                clock.clone(),
+            ),
+            output_well: HeapWell::new(
+                output_well_size,
+                policy.build(),
+                clock.clone(),
+            ),
+            output_buffer: Buffer::new(output_buffer_size),
+            status_map,
+            aging: policy.aging(),
+            quota,
+            cancelled: Arc::new(Mutex::new(HashSet::new())),
+            printers: OnceLock::new(),
+            control: Arc::new(Control::new()),
+            clock,
+            ids,
+            recorder,
+        };
+        spooling.replay_journal();
+        spooling.recover();
+        spooling
+    }
+
+    /// 重放预写日志，重建状态表、统计计数和配额使用情况
+    fn replay_journal(&self) {
+        let Some(journal) = &self.recorder.journal else {
+            return;
+        };
+        match journal.replay(&self.recorder.stats) {
+            Ok((jobs, ledger)) => {
+                println!("[INFO] 从预写日志恢复 {} 个任务的状态", jobs.len());
+                for &job_id in jobs.keys() {
+                    self.ids.observe(job_id as usize);
+                }
+                *self.status_map.lock().unwrap() = jobs;
+                self.quota.restore(&ledger);
+                *self.recorder.ledger.lock().unwrap() = ledger;
+            }
+            Err(e) => eprintln!("[ERROR] 重放预写日志失败：{}", e),
         }
     }
 
-    pub fn get_status(&self) -> String {
-        let input_buffer_len = self.input_buffer.size();
-        let input_well_len = self.input_well.len();
-        let output_well_len = self.output_well.len();
-        let output_buffer_len = self.output_buffer.size();
-        let status_map_len = self.status_map.lock().unwrap().len();
+    /// 从假脱机目录恢复上次未完成的任务：保留任务id和到达顺序，
+    /// 未格式化的任务放回输入井，其余（包括打印到一半的）放回输出井重新打印。
+    /// 状态表中未结束、却没有假脱机文件的任务无法恢复，标记为失败
+    fn recover(&self) {
+        let records = match &self.recorder.spool {
+            Some(spool) => spool.load_all().unwrap_or_else(|e| {
+                eprintln!("[ERROR] 读取假脱机目录失败：{}", e);
+                Vec::new()
+            }),
+            None => Vec::new(),
+        };
+        let recovered: HashSet<u64> = records
+            .iter()
+            .map(|record| record.job.job_id as u64)
+            .collect();
 
-        format!(
-            "【系统状态】
-输入缓冲区: {} 个任务
-输入井: {} 个任务
-输出井: {} 个任务
-输出缓冲区: {} 个任务
-状态表: {} 个任务",
-            input_buffer_len, input_well_len, output_well_len, output_buffer_len, status_map_len
-        )
+        let lost: Vec<Job> = self
+            .status_map
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|job| !job.status.is_finished() && !recovered.contains(&(job.job_id as u64)))
+            .cloned()
+            .collect();
+        for mut job in lost {
+            println!("[INFO] Job {} 无法恢复，标记为失败", job.job_id);
+            job.fail("系统重启时任务丢失".to_string(), self.clock.now());
+            let _ = self.recorder.record(&job); // 失败时已在 apply 中报告，任务保持原状态
+        }
+
+        for record in records {
+            let mut job = record.job;
+            job.status = JobStatus::Waiting;
+            job.start_print_time = None;
+            job.end_print_time = None;
+            self.ids.observe(job.job_id);
+            // 状态表中有记录的任务，配额已随预写日志恢复；没有记录时（例如没有日志）重新预留，
+            // 超出配额也照常打印，保证结束时的释放有对应的预留
+            let known = self
+                .status_map
+                .lock()
+                .unwrap()
+                .contains_key(&(job.job_id as u64));
+            if !known {
+                self.quota.reserve(&job.team_name, job.page_count());
+            }
+            let _ = self.recorder.insert(&job);
+
+            println!("[INFO] 从假脱机目录恢复 Job {}（{:?}）", job.job_id, record.stage);
+            match record.stage {
+                SpoolStage::Input => self.input_well.restore(job),
+                SpoolStage::Output => self.output_well.restore(job),
+            }
+        }
     }
 
-    pub fn submit_job(&self, data: rawJob) -> Result<usize, String> {
-        // 创建新的 Job
-        let mut job = Job::new(
-            data.priority,
-            data.team_name,
-            data.submit_time,
-            data.file_content,
-            data.color,
-            data.problem_name,
-        );
+    /// 所有缓冲区和井都为空，且没有正在打印的任务
+    pub fn is_idle(&self) -> bool {
+        self.input_buffer.size() == 0
+            && self.input_well.is_empty()
+            && self.output_well.is_empty()
+            && self.output_buffer.size() == 0
+            && self
+                .status_map
+                .lock()
+                .unwrap()
+                .values()
+                .all(|job| job.status.is_finished())
+    }
 
+    /// 接收一次提交：写入预写日志和假脱机目录后推入输入缓冲区
+    fn accept(&self, data: rawJob) -> Result<usize, SubmitError> {
+        // 排空或停止后不再接收任务
+        match self.mode() {
+            mode @ (SpoolMode::Draining | SpoolMode::Stopped) => {
+                let _ = self.recorder.reject();
+                return Err(SubmitError::NotAccepting(mode));
+            }
+            SpoolMode::Running | SpoolMode::Paused => {}
+        }
+
+        let mut job = match data.admit(Some(&self.quota), &self.ids) {
+            Ok(job) => job,
+            Err(e) => {
+                println!("任务提交被拒绝：{}", e);
+                let _ = self.recorder.reject();
+                return Err(e);
+            }
+        };
         let job_id = job.job_id;
-        job.status = JobStatus::Waiting;
-        let status_map = self.status_map.clone();
-        status_map
-            .lock()
-            .unwrap()
-            .insert(job.job_id as u64, job.clone());
 
-        // 尝试推入输入缓冲区
+        // 日志写不进去就不接收任务，避免接收了却无法在重启后恢复
+        if let Err(e) = self.recorder.insert(&job) {
+            self.quota.rollback(&job.team_name, job.page_count());
+            return Err(SubmitError::Unrecorded(e.to_string()));
+        }
+
+        // 先落盘再推入输入缓冲区，保证被接收的任务都能恢复；写不进去就不接收
+        if let Err(e) = self.recorder.spool(SpoolStage::Input, &job) {
+            bounce(&self.quota, &mut job);
+            self.recorder.unspool(job_id);
+            let _ = self.recorder.insert(&job);
+            return Err(SubmitError::Unrecorded(e.to_string()));
+        }
         match self.input_buffer.try_push(job) {
             Ok(_) => {
                 println!("任务 {} 已提交到输入缓冲区", job_id);
@@ -264,26 +690,27 @@ impl SPOOLing {
             }
             Err(mut job) => {
                 println!("缓冲区已满，任务 {} 提交失败", job_id);
+                bounce(&self.quota, &mut job);
+                self.recorder.unspool(job_id);
+                let _ = self.recorder.insert(&job);
 
-                job.status = crate::job::JobStatus::SubmitFailed;
-                status_map
-                    .lock()
-                    .unwrap()
-                    .insert(job.job_id as u64, job.clone());
-
-                Err("缓冲区已满".to_string())
+                Err(SubmitError::BufferFull)
             }
         }
     }
 
-    pub fn start_workers(&self, printer: Arc<Printer>) {
+    pub fn start_workers(&self, pool: Arc<PrinterPool>) {
         // 输入缓冲区 → 输入井
         {
             let input_buffer = self.input_buffer.clone();
             let input_well = self.input_well.clone();
+            let cancelled = self.cancelled.clone();
             thread::spawn(move || {
                 loop {
                     let job = input_buffer.pop(); // 阻塞
+                    if take_cancelled(&cancelled, &job) {
+                        continue;
+                    }
                     println!("[INFO] 输入缓冲区弹出 Job {}，准备放入输入井", job.job_id);
                     input_well.push_blocking(job);
                     println!("[INFO] Job 已成功进入输入井");
@@ -295,23 +722,22 @@ impl SPOOLing {
         {
             let input_well = self.input_well.clone();
             let output_well = self.output_well.clone();
-            let status_map = self.status_map.clone();
+            let recorder = self.recorder.clone();
+            let cancelled = self.cancelled.clone();
             thread::spawn(move || {
                 loop {
                     let mut job = input_well.pop_blocking(); // 阻塞
+                    if take_cancelled(&cancelled, &job) {
+                        continue;
+                    }
                     println!("[INFO] 输入井中取出 Job {}，准备格式化内容", job.job_id);
 
-                    let formatted = format!(
-                        "\\\\ team_name: {}\n\\\\ submit_time: {}\n\n{}",
-                        job.team_name, job.submit_time, job.file_content
-                    );
-                    job.file_content = formatted;
+                    format_header(&mut job);
 
                     println!("[INFO] Job {} 格式化完成，状态写入状态表", job.job_id);
-                    status_map
-                        .lock()
-                        .unwrap()
-                        .insert(job.job_id as u64, job.clone());
+                    // 失败时保留输入阶段的假脱机文件，重启后重新格式化
+                    let _ = recorder.spool(SpoolStage::Output, &job);
+                    let _ = recorder.record(&job);
 
                     let id = job.job_id;
                     output_well.push_blocking(job);
@@ -322,59 +748,138 @@ impl SPOOLing {
 
         // 输出井 → 输出缓冲区
         {
-let output_well = self.output_well.clone();
-let output_buffer = self.output_buffer.clone();
-thread::spawn(move || {
-    loop {
-        let job = output_well.pop_blocking(); // 阻塞
-        println!("[INFO] 输出井中弹出 Job {}，推入输出缓冲区", job.job_id);
-        output_buffer.push(job);
-    }
-});
+            let output_well = self.output_well.clone();
+            let output_buffer = self.output_buffer.clone();
+            let cancelled = self.cancelled.clone();
+            let control = self.control.clone();
+            thread::spawn(move || {
+                loop {
+                    let job = output_well.pop_blocking(); // 阻塞
+                    control.wait_printing_allowed(); // 暂停时不向输出缓冲区输送
+                    if take_cancelled(&cancelled, &job) {
+                        continue;
+                    }
+                    println!("[INFO] 输出井中弹出 Job {}，推入输出缓冲区", job.job_id);
+                    output_buffer.push(job);
+                }
+            });
         }
 
         // 输出缓冲区 → 打印机
         {
-let output_buffer = self.output_buffer.clone();
-let printer_arc = printer.clone();
-let status_map = self.status_map.clone();
+            let output_buffer = self.output_buffer.clone();
+            let output_well = self.output_well.clone();
+            let pool = pool.clone();
+            let recorder = self.recorder.clone();
+            let cancelled = self.cancelled.clone();
+            let control = self.control.clone();
+            let clock = self.clock.clone();
+            let _ = self.printers.set(pool.clone());
 
-thread::spawn(move || {
-    loop {
-        let job = output_buffer.pop(); // 阻塞
-        let job_id = job.job_id;
-        let mut job_clone = job.clone();
-        let printer_clone = printer_arc.clone();
-        let status_map = status_map.clone();
+            // 打印机在开始打印、打印完成、失败或取消时回调，写回状态表
+            let on_event: PrintCallback = Arc::new(move |job: &Job| {
+                match &job.status {
+                    JobStatus::Printing => println!("[INFO] Job {} 开始打印", job.job_id),
+                    JobStatus::Completed => {
+                        println!("[SUCCESS] Job {} 打印成功，状态更新为已完成", job.job_id)
+                    }
+                    JobStatus::Failed { reason } => {
+                        println!("[ERROR] Job {} 打印失败：{}", job.job_id, reason)
+                    }
+                    _ => {}
+                }
+                let _ = recorder.record(job);
+                // 打印机故障中断的任务放回输出井，等待重新分派
+                if job.status == JobStatus::Waiting {
+                    println!("[WARN] Job {} 打印中断，放回输出井", job.job_id);
+                    output_well.restore(job.clone());
+                }
+            });
 
-        println!("[INFO] 打印线程启动：Job {}", job_id);
-        let start_time = Some(Utc::now());
-        match printer_clone.submit_task(job_clone.clone()) {
-            Ok(_) => {
-                job_clone.start_print_time = start_time;
-                // 打印完成
-                job_clone.complete();
-                job_clone.end_print_time = Some(Utc::now());
-                println!("[SUCCESS] Job {} 打印成功，状态更新为已完成", job_id);
-                let mut map = status_map.lock().unwrap();
-                map.insert(job_id.try_into().unwrap(), job_clone.clone());
-            }
-            Err(_) => {
-                // job_clone.start_print_time = Some(Utc::now());
-                job_clone.status = JobStatus::SubmitFailed;
-                // job_clone.end_print_time = Some(Utc::now());
-                println!("[ERROR] Job {} 打印失败，状态更新为失败", job_id);
-                let mut map = status_map.lock().unwrap();
-                map.insert(job_id.try_into().unwrap(), job_clone.clone());
-            }
+            thread::spawn(move || {
+                loop {
+                    let job = output_buffer.pop(); // 阻塞
+                    control.wait_printing_allowed(); // 暂停时不再交给打印机
+                    if take_cancelled(&cancelled, &job) {
+                        continue;
+                    }
+                    let job_id = job.job_id;
+
+                    println!("[INFO] 打印线程启动：Job {}", job_id);
+                    // 等待打印机空闲，忙碌不会导致任务失败
+                    match pool.dispatch_blocking(job, on_event.clone()) {
+                        Ok(dispatched) => {
+                            println!("[INFO] Job {} 已交给打印机 {}", job_id, dispatched.printer);
+                            // 等待打印机期间被取消的任务，交给打印机后立即中止
+                            if cancelled.lock().unwrap().remove(&job_id) {
+                                pool.cancel(job_id);
+                            }
+                        }
+                        Err(err) => {
+                            let (mut job, reason) = match err {
+                                DispatchError::Busy(job) => (*job, "打印机忙碌".to_string()),
+                                DispatchError::Rejected { job, reason } => (*job, reason),
+                            };
+                            println!("[ERROR] Job {} 被拒绝：{}，状态更新为失败", job_id, reason);
+                            job.fail(reason, clock.now());
+                            on_event(&job);
+                        }
+                    }
+                }
+            });
         }
     }
-});
-        }
+
+}
+
+impl Spooler for SPOOLing {
+    fn submit_job(&self, data: rawJob) -> Result<usize, SubmitError> {
+        let document = data.document.clone();
+        self.accept(data)
+            .inspect_err(|_| discard_document(document.as_ref()))
+    }
+
+    fn get_status(&self) -> String {
+        let input_buffer_len = self.input_buffer.size();
+        let input_well_len = self.input_well.len();
+        let output_well_len = self.output_well.len();
+        let output_buffer_len = self.output_buffer.size();
+        let status_map_len = self.status_map.lock().unwrap().len();
+        let team_depths = self
+            .input_well
+            .team_depths()
+            .iter()
+            .map(|(team, depth)| format!("{}={}", team, depth))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!(
+            "【系统状态】
+运行模式: {:?}
+调度策略: {}
+输入缓冲区: {}/{} 个任务
+输入井: {}/{} 个任务
+输入井各队伍排队: {}
+输出井: {}/{} 个任务
+输出缓冲区: {}/{} 个任务
+状态表: {} 个任务",
+            self.mode(),
+            self.input_well.policy_name(),
+            input_buffer_len,
+            self.input_buffer.capacity(),
+            input_well_len,
+            self.input_well.capacity(),
+            team_depths,
+            output_well_len,
+            self.output_well.capacity(),
+            output_buffer_len,
+            self.output_buffer.capacity(),
+            status_map_len
+        )
     }
 
     /// 获取所有提交成功的任务id
-    pub fn get_active_job_id(&self) -> Vec<u64> {
+    fn get_active_job_id(&self) -> Vec<u64> {
         let status_map = self.status_map.lock().unwrap();
         status_map
             .iter()
@@ -387,4 +892,408 @@ thread::spawn(move || {
             })
             .collect()
     }
+
+    fn get_job(&self, job_id: u64) -> Option<Job> {
+        self.status_map.lock().unwrap().get(&job_id).cloned()
+    }
+
+    fn list_jobs(&self) -> Vec<Job> {
+        self.status_map.lock().unwrap().values().cloned().collect()
+    }
+
+    /// 从状态表删除任务记录
+    fn delete_jobs(&self, job_ids: &[u64]) {
+        for &job_id in job_ids {
+            let _ = self.recorder.delete(job_id);
+        }
+    }
+
+    /// 取消任务：从所在的缓冲区或井中移除，正在打印的则中止打印
+    fn cancel(&self, job_id: u64) -> Result<(), CancelError> {
+        let job = {
+            let status_map = self.status_map.lock().unwrap();
+            let job = status_map.get(&job_id).ok_or(CancelError::NotFound)?;
+            if job.status.is_finished() {
+                return Err(CancelError::AlreadyFinished);
+            }
+            job.clone()
+        };
+        let id = job.job_id;
+
+        // 先登记，保证此刻在工作线程手中的任务在下一站被丢弃
+        self.cancelled.lock().unwrap().insert(id);
+
+        let removed = self
+            .input_buffer
+            .remove_where(|job| job.job_id == id)
+            .or_else(|| self.input_well.remove(id))
+            .or_else(|| self.output_well.remove(id))
+            .or_else(|| self.output_buffer.remove_where(|job| job.job_id == id));
+        let mut job = match removed {
+            Some(job) => {
+                self.cancelled.lock().unwrap().remove(&id);
+                job
+            }
+            None => {
+                if let Some(pool) = self.printers.get()
+                    && pool.cancel(id)
+                {
+                    // 打印机中止后通过回调写入已取消状态
+                    self.cancelled.lock().unwrap().remove(&id);
+                    println!("[INFO] Job {} 正在打印，已通知打印机中止", id);
+                    return Ok(());
+                }
+                job
+            }
+        };
+
+        println!("[INFO] Job {} 已取消", id);
+        job.cancel(self.clock.now());
+        self.recorder
+            .record(&job)
+            .map_err(|e| CancelError::Unrecorded(e.to_string()))
+    }
+
+    /// 本系统的统计计数
+    fn stats(&self) -> StatsSnapshot {
+        self.recorder.stats.snapshot()
+    }
+
+    /// 任务当前的有效优先级：仍在排队的任务按老化配置计算，其余为原始优先级
+    fn effective_priority(&self, job: &Job) -> u32 {
+        match &self.aging {
+            Some(aging) if job.status == JobStatus::Waiting => {
+                aging.effective_priority(job, self.clock.now())
+            }
+            _ => job.priority,
+        }
+    }
+
+    fn quota(&self) -> Option<&QuotaManager> {
+        Some(&self.quota)
+    }
+
+    fn reset_quota(&self, team_name: Option<&str>) -> io::Result<()> {
+        self.recorder.reset_quota(team_name)
+    }
+
+    /// 当前运行模式
+    fn mode(&self) -> SpoolMode {
+        self.control.mode()
+    }
+
+    /// 暂停打印，任务仍然可以提交并进入井中
+    fn pause(&self) -> Result<(), SpoolMode> {
+        match self.mode() {
+            SpoolMode::Running | SpoolMode::Paused => {
+                self.control.set(SpoolMode::Paused);
+                Ok(())
+            }
+            mode => Err(mode),
+        }
+    }
+
+    /// 从暂停或排空中恢复正常运行
+    fn resume(&self) -> Result<(), SpoolMode> {
+        match self.mode() {
+            SpoolMode::Stopped => Err(SpoolMode::Stopped),
+            _ => {
+                self.control.set(SpoolMode::Running);
+                Ok(())
+            }
+        }
+    }
+
+    /// 开始排空：不再接收新任务，暂停状态也会恢复打印
+    fn start_drain(&self) -> Result<(), SpoolMode> {
+        match self.mode() {
+            SpoolMode::Stopped => Err(SpoolMode::Stopped),
+            _ => {
+                self.control.set(SpoolMode::Draining);
+                Ok(())
+            }
+        }
+    }
+
+    /// 排空并停止：阻塞直到剩余任务全部结束，或排空被恢复运行打断。
+    /// 超过 `timeout` 仍有任务没结束时（例如打印机一直故障）停止向打印机输送任务，
+    /// 把状态表写成快照后返回；剩下的任务留在假脱机目录中，重启后继续打印
+    fn drain(&self, timeout: Option<Duration>) -> SpoolMode {
+        if self.start_drain().is_err() {
+            return SpoolMode::Stopped;
+        }
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        while !self.is_idle() {
+            if self.mode() != SpoolMode::Draining {
+                return self.mode();
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                self.control.set(SpoolMode::Stopped);
+                let held = self
+                    .status_map
+                    .lock()
+                    .unwrap()
+                    .values()
+                    .filter(|job| !job.status.is_finished())
+                    .count();
+                if let Err(e) = self.recorder.checkpoint() {
+                    eprintln!("[ERROR] 写入快照失败，重启后将重放预写日志：{}", e);
+                }
+                println!("[WARN] 排空超时，{} 个任务保留在假脱机目录中，重启后继续打印", held);
+                return SpoolMode::Stopped;
+            }
+            thread::sleep(Duration::from_millis(200));
+        }
+        self.control.set(SpoolMode::Stopped);
+        println!("[INFO] 假脱机系统已排空并停止");
+        SpoolMode::Stopped
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::clock;
+    use crate::backend::BackendConfig;
+    use crate::osim::quota::{QuotaConfig, QuotaLimits, TeamUsage};
+    use crate::printer::{
+        FaultConfig, PoolConfig, PrintSpeed, PrinterConfig, PrinterFault, PrinterStatus,
+    };
+    use std::path::{Path, PathBuf};
+
+    /// 每个测试独占的运行目录，开始前清空
+    pub(crate) fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("spooling-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// 在 `dir` 中打开带假脱机目录和预写日志的 SPOOLing，不启动工作线程
+    pub(crate) fn open(dir: &Path, quota: QuotaConfig) -> SPOOLing {
+        SPOOLing::new(
+            10,
+            10,
+            10,
+            10,
+            PolicyKind::Priority,
+            QuotaManager::new(quota),
+            Arc::new(JobIdAllocator::open(dir.join("next_job_id")).unwrap()),
+            Some(SpoolDir::open(dir.join("spool")).unwrap()),
+            Some(Journal::open(dir.join("journal"), clock::system()).unwrap()),
+            clock::system(),
+        )
+    }
+
+    /// 只生成 PDF 的黑白打印机池，每份任务耗时 `print_ms` 毫秒
+    pub(crate) fn pool(dir: &Path, names: &[&str], print_ms: u64) -> Arc<PrinterPool> {
+        let printers = names
+            .iter()
+            .map(|name| PrinterConfig {
+                name: name.to_string(),
+                color: false,
+                location: String::new(),
+                speed: PrintSpeed {
+                    warmup_ms: print_ms,
+                    mono_page_ms: 0,
+                    color_page_ms: 0,
+                    jitter: 0.0,
+                },
+                faults: FaultConfig::default(),
+                backend: BackendConfig::Pdf {
+                    dir: dir.join("output"),
+                },
+            })
+            .collect();
+        Arc::new(PrinterPool::new(
+            PoolConfig {
+                color_fallback: Default::default(),
+                printers,
+            },
+            clock::system(),
+        ))
+    }
+
+    /// 等待任务进入满足条件的状态，最多等 10 秒
+    fn wait_for(spooling: &SPOOLing, job_id: usize, done: impl Fn(&JobStatus) -> bool) -> Job {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let job = spooling.status_map.lock().unwrap()[&(job_id as u64)].clone();
+            if done(&job.status) {
+                return job;
+            }
+            assert!(Instant::now() < deadline, "Job {} 停在 {:?}", job_id, job.status);
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    pub(crate) fn raw_job(team_name: &str) -> rawJob {
+        rawJob {
+            priority: 1,
+            team_name: team_name.to_string(),
+            submit_time: Utc::now(),
+            file_content: "int main() {}".to_string(),
+            color: false,
+            problem_name: "A".to_string(),
+            document: None,
+            language: None,
+        }
+    }
+
+    #[test]
+    fn quota_usage_survives_restart() {
+        let dir = scratch_dir("quota-restart");
+        let quota = QuotaConfig {
+            global: QuotaLimits {
+                max_jobs: Some(3),
+                ..QuotaLimits::default()
+            },
+            teams: HashMap::new(),
+        };
+
+        let spooling = open(&dir, quota.clone());
+        for _ in 0..3 {
+            spooling.submit_job(raw_job("team1")).unwrap();
+        }
+        spooling.submit_job(raw_job("team2")).unwrap();
+        assert!(matches!(
+            spooling.submit_job(raw_job("team1")),
+            Err(SubmitError::QuotaExceeded(QuotaViolation::Jobs { limit: 3 }))
+        ));
+        spooling.reset_quota(Some("team2")).unwrap();
+        drop(spooling);
+
+        // 重启后任务从假脱机目录恢复，配额仍按原来的使用情况计算，恢复的任务不会重复预留
+        let spooling = open(&dir, quota);
+        assert_eq!(
+            spooling.quota.usage_of("team1").0,
+            TeamUsage {
+                jobs: 3,
+                pages: 3,
+                in_flight: 3,
+            }
+        );
+        assert_eq!(
+            spooling.quota.usage_of("team2").0,
+            TeamUsage {
+                jobs: 0,
+                pages: 0,
+                in_flight: 1,
+            }
+        );
+        assert!(matches!(
+            spooling.submit_job(raw_job("team1")),
+            Err(SubmitError::QuotaExceeded(_))
+        ));
+
+        // 恢复的任务结束后只释放自己的名额
+        let job = spooling
+            .list_jobs()
+            .into_iter()
+            .find(|job| job.team_name == "team1")
+            .unwrap();
+        spooling.cancel(job.job_id as u64).unwrap();
+        assert_eq!(spooling.quota.usage_of("team1").0.in_flight, 2);
+        assert_eq!(spooling.quota.usage_of("team2").0.in_flight, 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn submit_is_refused_when_the_spool_file_cannot_be_written() {
+        let dir = scratch_dir("spool-fails");
+        let quota = QuotaConfig {
+            global: QuotaLimits {
+                max_jobs: Some(1),
+                ..QuotaLimits::default()
+            },
+            teams: HashMap::new(),
+        };
+        let spooling = open(&dir, quota);
+        // 假脱机目录被换成普通文件，之后的写入都会失败
+        std::fs::remove_dir_all(dir.join("spool")).unwrap();
+        std::fs::write(dir.join("spool"), b"").unwrap();
+
+        assert!(matches!(
+            spooling.submit_job(raw_job("team1")),
+            Err(SubmitError::Unrecorded(_))
+        ));
+        // 没有接收的任务不占用配额，也不会留在缓冲区中
+        assert_eq!(spooling.quota.usage_of("team1").0.jobs, 0);
+        assert_eq!(spooling.input_buffer.size(), 0);
+        assert!(spooling.get_active_job_id().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn faulted_printer_holds_jobs_until_cleared() {
+        let dir = scratch_dir("hold");
+        let spooling = open(&dir, QuotaConfig::default());
+        let pool = pool(&dir, &["p1"], 50);
+        pool.set_fault("p1", Some(PrinterFault::PaperOut));
+        spooling.start_workers(pool.clone());
+
+        // 打印机故障时任务保持等待，不会失败
+        let job_id = spooling.submit_job(raw_job("team1")).unwrap();
+        thread::sleep(Duration::from_millis(500));
+        wait_for(&spooling, job_id, |status| *status == JobStatus::Waiting);
+
+        // 故障排除后重试并打印完成
+        pool.set_fault("p1", None);
+        wait_for(&spooling, job_id, |status| *status == JobStatus::Completed);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn interrupted_job_is_rerouted_to_another_printer() {
+        let dir = scratch_dir("reroute");
+        let spooling = open(&dir, QuotaConfig::default());
+        let pool = pool(&dir, &["p1", "p2"], 1000);
+        spooling.start_workers(pool.clone());
+
+        let job_id = spooling.submit_job(raw_job("team1")).unwrap();
+        wait_for(&spooling, job_id, |status| *status == JobStatus::Printing);
+        let busy = pool
+            .info()
+            .into_iter()
+            .find(|printer| printer.status == PrinterStatus::Printing)
+            .unwrap();
+        pool.set_fault(&busy.name, Some(PrinterFault::Jammed));
+
+        // 中断的任务放回输出井，改由另一台打印机打印完成
+        wait_for(&spooling, job_id, |status| *status == JobStatus::Completed);
+        for printer in pool.info() {
+            let expected = usize::from(printer.name != busy.name);
+            assert_eq!(printer.printed_count, expected, "{}", printer.name);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn drain_gives_up_after_timeout_and_keeps_held_jobs() {
+        let dir = scratch_dir("drain-timeout");
+        let spooling = open(&dir, QuotaConfig::default());
+        let pool = pool(&dir, &["p1"], 50);
+        pool.set_fault("p1", Some(PrinterFault::Offline));
+        spooling.start_workers(pool);
+        let job_id = spooling.submit_job(raw_job("team1")).unwrap();
+
+        // 打印机一直故障时，排空在超时后返回而不是一直等待
+        let started = Instant::now();
+        assert_eq!(spooling.drain(Some(Duration::from_millis(500))), SpoolMode::Stopped);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(matches!(
+            spooling.submit_job(raw_job("team1")),
+            Err(SubmitError::NotAccepting(SpoolMode::Stopped))
+        ));
+        drop(spooling);
+
+        // 重启后任务从快照和假脱机目录恢复，仍在等待打印
+        let spooling = open(&dir, QuotaConfig::default());
+        let jobs = spooling.list_jobs();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].job_id, job_id);
+        assert_eq!(jobs[0].status, JobStatus::Waiting);
+        assert!(!spooling.is_idle());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -0,0 +1,338 @@
+use crate::job::{Job, JobStatus};
+use crate::osim::SPOOLing::{CancelError, SpoolMode, SubmitError, discard_document, rawJob};
+use crate::osim::quota::QuotaManager;
+use crate::osim::spooler::Spooler;
+use crate::osim::stats::StatsSnapshot;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// 已完成任务从提交到打印结束的耗时分位数（毫秒）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct LatencyPercentiles {
+    pub samples: usize,
+    pub p50: Option<i64>,
+    pub p90: Option<i64>,
+    pub p99: Option<i64>,
+    pub max: Option<i64>,
+}
+
+impl LatencyPercentiles {
+    /// 按最近秩法计算分位数
+    pub fn from_jobs<'a>(jobs: impl Iterator<Item = &'a Job>) -> Self {
+        let mut latencies: Vec<i64> = jobs
+            .filter(|job| job.status == JobStatus::Completed)
+            .filter_map(|job| {
+                let end = job.end_print_time?;
+                Some((end - job.submit_time).num_milliseconds())
+            })
+            .collect();
+        latencies.sort_unstable();
+
+        let percentile = |p: usize| -> Option<i64> {
+            if latencies.is_empty() {
+                return None;
+            }
+            let rank = (latencies.len() * p).div_ceil(100).max(1);
+            Some(latencies[rank - 1])
+        };
+        LatencyPercentiles {
+            samples: latencies.len(),
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: latencies.last().copied(),
+        }
+    }
+}
+
+/// 单个模式的对比数据
+#[derive(Debug, Clone, Serialize)]
+pub struct ModeReport {
+    pub stats: StatsSnapshot,
+    pub acceptance_rate: f64, // 被接收的提交占比，没有提交时为 1
+    pub latency: LatencyPercentiles,
+}
+
+impl ModeReport {
+    pub fn new(stats: StatsSnapshot, jobs: &[Job]) -> Self {
+        let acceptance_rate = if stats.submitted == 0 {
+            1.0
+        } else {
+            stats.accepted as f64 / stats.submitted as f64
+        };
+        ModeReport {
+            stats,
+            acceptance_rate,
+            latency: LatencyPercentiles::from_jobs(jobs.iter()),
+        }
+    }
+
+    fn of(spooler: &dyn Spooler) -> Self {
+        Self::new(spooler.stats(), &spooler.list_jobs())
+    }
+}
+
+/// 两种模式的对比结果
+#[derive(Debug, Clone, Serialize)]
+pub struct ComparisonReport {
+    pub spooling: ModeReport,
+    pub no_spooling: ModeReport,
+}
+
+/// 对比模式：每次提交同时交给 SPOOLing 和 NoSPOOLing（各自使用独立的打印机池），
+/// 查询、取消等其余接口只作用于 SPOOLing
+pub struct Comparison {
+    spooling: Arc<dyn Spooler>,
+    no_spooling: Arc<dyn Spooler>,
+}
+
+impl Comparison {
+    pub fn new(spooling: Arc<dyn Spooler>, no_spooling: Arc<dyn Spooler>) -> Self {
+        Comparison {
+            spooling,
+            no_spooling,
+        }
+    }
+
+    pub fn report(&self) -> ComparisonReport {
+        ComparisonReport {
+            spooling: ModeReport::of(self.spooling.as_ref()),
+            no_spooling: ModeReport::of(self.no_spooling.as_ref()),
+        }
+    }
+}
+
+impl Spooler for Comparison {
+    /// 返回 SPOOLing 的结果，NoSPOOLing 的结果只计入对比数据。
+    /// 因配额或排空被拒绝的提交与打印能力无关，不交给 NoSPOOLing。
+    /// 两边的任务结束时各自删除文档，所以交给 NoSPOOLing 的是文档的副本
+    fn submit_job(&self, data: rawJob) -> Result<usize, SubmitError> {
+        let mut mirror = data.clone();
+        if let Some(document) = &data.document {
+            match document.duplicate() {
+                Ok(copy) => mirror.document = Some(copy),
+                Err(e) => {
+                    println!("[对比] 复制文档 {} 失败，不交给 NoSPOOLing：{}", document.path, e);
+                    return self.spooling.submit_job(data);
+                }
+            }
+        }
+
+        let result = self.spooling.submit_job(data);
+        if matches!(
+            result,
+            Err(SubmitError::QuotaExceeded(_) | SubmitError::NotAccepting(_))
+        ) {
+            discard_document(mirror.document.as_ref());
+        } else if let Err(e) = self.no_spooling.submit_job(mirror) {
+            println!("[对比] NoSPOOLing 提交失败：{}", e);
+        }
+        result
+    }
+
+    fn get_status(&self) -> String {
+        format!(
+            "{}\n【对比】NoSPOOLing: {}",
+            self.spooling.get_status(),
+            self.no_spooling.get_status()
+        )
+    }
+
+    fn get_active_job_id(&self) -> Vec<u64> {
+        self.spooling.get_active_job_id()
+    }
+
+    fn get_job(&self, job_id: u64) -> Option<Job> {
+        self.spooling.get_job(job_id)
+    }
+
+    fn list_jobs(&self) -> Vec<Job> {
+        self.spooling.list_jobs()
+    }
+
+    fn delete_jobs(&self, job_ids: &[u64]) {
+        self.spooling.delete_jobs(job_ids)
+    }
+
+    fn cancel(&self, job_id: u64) -> Result<(), CancelError> {
+        self.spooling.cancel(job_id)
+    }
+
+    fn stats(&self) -> StatsSnapshot {
+        self.spooling.stats()
+    }
+
+    fn effective_priority(&self, job: &Job) -> u32 {
+        self.spooling.effective_priority(job)
+    }
+
+    fn quota(&self) -> Option<&QuotaManager> {
+        self.spooling.quota()
+    }
+
+    fn reset_quota(&self, team_name: Option<&str>) -> std::io::Result<()> {
+        self.spooling.reset_quota(team_name)
+    }
+
+    fn mode(&self) -> SpoolMode {
+        self.spooling.mode()
+    }
+
+    fn pause(&self) -> Result<(), SpoolMode> {
+        self.spooling.pause()
+    }
+
+    fn resume(&self) -> Result<(), SpoolMode> {
+        self.spooling.resume()
+    }
+
+    fn start_drain(&self) -> Result<(), SpoolMode> {
+        self.spooling.start_drain()
+    }
+
+    fn drain(&self, timeout: Option<Duration>) -> SpoolMode {
+        let started = Instant::now();
+        self.no_spooling.drain(timeout);
+        self.spooling
+            .drain(timeout.map(|timeout| timeout.saturating_sub(started.elapsed())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::Document;
+    use crate::osim::NoSPOOLing::NoSPOOLing;
+    use crate::osim::SPOOLing::tests::{open, pool, raw_job, scratch_dir};
+    use crate::osim::job_id::JobIdAllocator;
+    use crate::osim::quota::QuotaConfig;
+    use std::fs;
+    use std::thread;
+
+    /// 等待所有任务结束
+    fn wait_finished(spooler: &dyn Spooler) -> Vec<Job> {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let jobs = spooler.list_jobs();
+            if jobs.iter().all(|job| job.status.is_finished()) {
+                return jobs;
+            }
+            assert!(Instant::now() < deadline, "任务没有结束：{:?}", jobs);
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    /// 提交后 `latency_ms` 毫秒打印完成的任务
+    fn completed(job_id: usize, latency_ms: i64) -> Job {
+        let submit_time = chrono::Utc::now();
+        let mut job = Job::new(
+            job_id,
+            1,
+            "team1".to_string(),
+            submit_time,
+            String::new(),
+            false,
+            "A".to_string(),
+        );
+        job.complete(submit_time + chrono::Duration::milliseconds(latency_ms));
+        job
+    }
+
+    #[test]
+    fn percentiles_of_no_samples_are_empty() {
+        let mut cancelled = completed(1, 10);
+        cancelled.status = JobStatus::Cancelled;
+        assert_eq!(
+            LatencyPercentiles::from_jobs([cancelled].iter()),
+            LatencyPercentiles::default()
+        );
+    }
+
+    #[test]
+    fn single_sample_is_every_percentile() {
+        let latency = LatencyPercentiles::from_jobs([completed(1, 250)].iter());
+        assert_eq!(
+            latency,
+            LatencyPercentiles {
+                samples: 1,
+                p50: Some(250),
+                p90: Some(250),
+                p99: Some(250),
+                max: Some(250),
+            }
+        );
+    }
+
+    #[test]
+    fn percentiles_use_nearest_rank() {
+        // 1..=100 毫秒打乱顺序，第 p 个最小值即 p 分位数
+        let jobs: Vec<Job> = (1..=100)
+            .map(|i| completed(i, (i * 37 % 100 + 1) as i64))
+            .collect();
+        let latency = LatencyPercentiles::from_jobs(jobs.iter());
+        assert_eq!(latency.samples, 100);
+        assert_eq!(latency.p50, Some(50));
+        assert_eq!(latency.p90, Some(90));
+        assert_eq!(latency.p99, Some(99));
+        assert_eq!(latency.max, Some(100));
+
+        // 10 个样本：p50 取第 5 个，p90 取第 9 个，p99 向上取整为第 10 个
+        let latency = LatencyPercentiles::from_jobs(jobs.iter().filter(|job| job.job_id <= 10));
+        let mut expected: Vec<i64> = (1..=10).map(|i| (i * 37 % 100 + 1) as i64).collect();
+        expected.sort_unstable();
+        assert_eq!(latency.p50, Some(expected[4]));
+        assert_eq!(latency.p90, Some(expected[8]));
+        assert_eq!(latency.p99, Some(expected[9]));
+    }
+
+    #[test]
+    fn acceptance_rate_without_submissions_is_one() {
+        assert_eq!(ModeReport::new(StatsSnapshot::default(), &[]).acceptance_rate, 1.0);
+        let stats = StatsSnapshot {
+            submitted: 4,
+            accepted: 3,
+            ..StatsSnapshot::default()
+        };
+        assert_eq!(ModeReport::new(stats, &[]).acceptance_rate, 0.75);
+    }
+
+    #[test]
+    fn document_jobs_print_on_both_sides() {
+        let dir = scratch_dir("compare-document");
+        let spooling = open(&dir, QuotaConfig::default());
+        spooling.start_workers(pool(&dir.join("spooling"), &["p1"], 0));
+        let spooling: Arc<dyn Spooler> = Arc::new(spooling);
+        let no_spooling: Arc<dyn Spooler> = Arc::new(NoSPOOLing::new(
+            pool(&dir.join("no_spooling"), &["p1"], 0),
+            Arc::new(JobIdAllocator::in_memory()),
+        ));
+        let comparison = Comparison::new(spooling.clone(), no_spooling.clone());
+
+        let pdf = b"%PDF-1.4\n%mirrored\n".to_vec();
+        let path = dir.join("document.pdf");
+        fs::write(&path, &pdf).unwrap();
+        let mut data = raw_job("team1");
+        data.document = Some(Document {
+            path: path.to_string_lossy().into_owned(),
+            pages: 1,
+        });
+        // SPOOLing 暂停，保证 NoSPOOLing 先打印完并删除自己的文档
+        spooling.pause().unwrap();
+        comparison.submit_job(data).unwrap();
+        wait_finished(no_spooling.as_ref());
+        spooling.resume().unwrap();
+
+        for (side, spooler) in [("spooling", &spooling), ("no_spooling", &no_spooling)] {
+            let jobs = wait_finished(spooler.as_ref());
+            assert_eq!(jobs.len(), 1);
+            assert_eq!(jobs[0].status, JobStatus::Completed, "{}", side);
+            let output = dir.join(side).join("output").join(format!("{}.pdf", jobs[0].file_name));
+            assert_eq!(fs::read(output).unwrap(), pdf, "{}", side);
+        }
+        // 两份文档都已删除
+        assert!(!path.exists());
+        assert!(!path.with_extension("copy.pdf").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,149 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// 每次落盘预留的id数量，重启后未用完的id直接跳过
+pub const RESERVE_BLOCK: usize = 64;
+
+struct IdState {
+    next: usize,     // 下一个分配的id
+    reserved: usize, // 已落盘的上限，小于它的id可以直接分配
+}
+
+/// 任务id分配器：把已分配id的上限写入文件，重启后从上限继续，
+/// 保证任务id和输出文件名在多次运行之间不重复
+pub struct JobIdAllocator {
+    path: Option<PathBuf>,
+    state: Mutex<IdState>,
+}
+
+impl JobIdAllocator {
+    /// 从文件读取上次的上限，文件不存在时从 0 开始
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let start = match fs::read_to_string(&path) {
+            Ok(content) => content
+                .trim()
+                .parse()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e),
+        };
+        Ok(JobIdAllocator {
+            path: Some(path),
+            state: Mutex::new(IdState {
+                next: start,
+                reserved: start,
+            }),
+        })
+    }
+
+    /// 不落盘的分配器，每次运行都从 0 开始
+    pub fn in_memory() -> Self {
+        JobIdAllocator {
+            path: None,
+            state: Mutex::new(IdState {
+                next: 0,
+                reserved: usize::MAX,
+            }),
+        }
+    }
+
+    /// 分配一个新的任务id，需要时先把新的上限落盘
+    pub fn allocate(&self) -> io::Result<usize> {
+        let mut state = self.state.lock().unwrap();
+        if state.next >= state.reserved {
+            let reserved = state.next + RESERVE_BLOCK;
+            self.persist(reserved)?;
+            state.reserved = reserved;
+        }
+        let job_id = state.next;
+        state.next += 1;
+        Ok(job_id)
+    }
+
+    /// 恢复已有任务后调用，保证之后分配的id不与其重复
+    pub fn observe(&self, job_id: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.next = state.next.max(job_id + 1);
+    }
+
+    /// 先写临时文件再改名，避免崩溃时留下半个文件
+    fn persist(&self, reserved: usize) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, reserved.to_string())?;
+        fs::rename(&tmp, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_file(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("spooling-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("next_job_id")
+    }
+
+    fn allocate(ids: &JobIdAllocator, count: usize) -> Vec<usize> {
+        (0..count).map(|_| ids.allocate().unwrap()).collect()
+    }
+
+    #[test]
+    fn high_water_mark_is_persisted_ahead_of_allocation() {
+        let path = scratch_file("job-id-persist");
+        let ids = JobIdAllocator::open(&path).unwrap();
+        assert_eq!(allocate(&ids, 3), [0, 1, 2]);
+        assert_eq!(fs::read_to_string(&path).unwrap(), RESERVE_BLOCK.to_string());
+
+        // 用完一整块后才预留下一块
+        allocate(&ids, RESERVE_BLOCK - 3);
+        assert_eq!(fs::read_to_string(&path).unwrap(), RESERVE_BLOCK.to_string());
+        assert_eq!(ids.allocate().unwrap(), RESERVE_BLOCK);
+        assert_eq!(fs::read_to_string(&path).unwrap(), (2 * RESERVE_BLOCK).to_string());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn ids_never_repeat_after_restart() {
+        let path = scratch_file("job-id-restart");
+        let mut seen = Vec::new();
+        for _ in 0..3 {
+            // 每次只用掉预留块的一部分就“重启”
+            let ids = JobIdAllocator::open(&path).unwrap();
+            seen.extend(allocate(&ids, 5));
+        }
+        let mut unique = seen.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), seen.len());
+        assert!(seen.windows(2).all(|pair| pair[0] < pair[1]));
+        // 未用完的 id 被跳过：第二次运行从上次预留的上限开始
+        assert_eq!(seen[5], RESERVE_BLOCK);
+        assert_eq!(seen[10], 2 * RESERVE_BLOCK);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn observed_ids_are_not_reallocated() {
+        let ids = JobIdAllocator::in_memory();
+        ids.observe(41);
+        ids.observe(7);
+        assert_eq!(ids.allocate().unwrap(), 42);
+    }
+
+    #[test]
+    fn corrupt_high_water_mark_is_an_error() {
+        let path = scratch_file("job-id-corrupt");
+        fs::write(&path, "not a number").unwrap();
+        let err = JobIdAllocator::open(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,353 @@
+use crate::clock::Clock;
+use crate::job::Job;
+use crate::osim::quota::UsageLedger;
+use crate::osim::stats::{SpoolStats, StatsSnapshot};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// 日志条目数超过该值时压缩为快照
+pub const COMPACT_THRESHOLD: usize = 500;
+
+/// 状态表的一次变更
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum JournalEvent {
+    Upsert(Job), // 写入任务的最新状态
+    Delete(u64), // 从状态表删除任务
+    Rejected,    // 没有生成任务就被拒绝的提交，只影响统计
+    QuotaReset(Option<String>), // 管理员重置配额计数，None 表示所有队伍
+}
+
+/// 把一次变更应用到状态表和统计计数，运行时和重放日志共用
+pub fn apply(jobs: &mut HashMap<u64, Job>, stats: &SpoolStats, event: JournalEvent) {
+    match event {
+        JournalEvent::Upsert(job) => {
+            let id = job.job_id as u64;
+            stats.observe(jobs.get(&id), &job);
+            jobs.insert(id, job);
+        }
+        JournalEvent::Delete(id) => {
+            jobs.remove(&id);
+        }
+        JournalEvent::Rejected => stats.reject(),
+        JournalEvent::QuotaReset(_) => {}
+    }
+}
+
+/// 把一次变更应用到配额使用情况，需在 `apply` 之前调用（要用到状态表中原来的记录）
+pub fn observe_quota(ledger: &mut UsageLedger, jobs: &HashMap<u64, Job>, event: &JournalEvent) {
+    match event {
+        JournalEvent::Upsert(job) => ledger.observe(jobs.get(&(job.job_id as u64)), job),
+        JournalEvent::QuotaReset(team_name) => ledger.reset(team_name.as_deref()),
+        JournalEvent::Delete(_) | JournalEvent::Rejected => {}
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct JournalEntry {
+    #[serde(default)]
+    seq: u64, // 从 1 开始递增，压缩后继续；旧版本的条目没有序号，为 0
+    time: DateTime<Utc>,
+    event: JournalEvent,
+}
+
+/// 压缩后的快照：状态表、统计计数和配额使用情况
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Snapshot {
+    #[serde(default)]
+    seq: u64, // 快照已包含的最后一条日志的序号，重放时跳过序号不大于它的条目
+    #[serde(default)]
+    stats: StatsSnapshot,
+    #[serde(default)]
+    quota: UsageLedger,
+    jobs: Vec<Job>,
+}
+
+/// 预写日志：状态表的每次变更先追加到日志再生效，启动时重放恢复
+pub struct Journal {
+    dir: PathBuf,
+    log: Mutex<Log>,
+    entries: Mutex<usize>, // 上次压缩后追加的条目数
+    clock: Arc<dyn Clock>, // 条目时间戳取自此时钟
+}
+
+/// 日志文件和最后一条条目的序号
+struct Log {
+    file: File,
+    seq: u64,
+}
+
+impl Journal {
+    pub fn open(dir: impl AsRef<Path>, clock: Arc<dyn Clock>) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join("journal.log"))?;
+        Ok(Journal {
+            dir,
+            log: Mutex::new(Log { file: log, seq: 0 }),
+            entries: Mutex::new(0),
+            clock,
+        })
+    }
+
+    fn snapshot_path(&self) -> PathBuf {
+        self.dir.join("snapshot.json")
+    }
+
+    /// 追加一条变更并落盘
+    pub fn append(&self, event: JournalEvent) -> io::Result<()> {
+        let mut log = self.log.lock().unwrap();
+        let entry = JournalEntry {
+            seq: log.seq + 1,
+            time: self.clock.now(),
+            event,
+        };
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+
+        log.file.write_all(&line)?;
+        log.file.sync_data()?;
+        log.seq = entry.seq;
+        *self.entries.lock().unwrap() += 1;
+        Ok(())
+    }
+
+    /// 是否应该压缩
+    pub fn needs_compaction(&self) -> bool {
+        *self.entries.lock().unwrap() >= COMPACT_THRESHOLD
+    }
+
+    /// 把当前状态写成快照并清空日志，调用方需保证 `jobs` 等已包含所有追加过的变更。
+    ///
+    /// 快照落盘（文件和目录都 fsync）后才清空日志；清空前崩溃时，
+    /// 重放按快照中的序号跳过已包含的条目，不会重复计数
+    pub fn compact<'a>(
+        &self,
+        jobs: impl Iterator<Item = &'a Job>,
+        stats: StatsSnapshot,
+        quota: UsageLedger,
+    ) -> io::Result<()> {
+        // 持有日志锁，保证快照和清空日志之间没有新条目
+        let log = self.log.lock().unwrap();
+        let snapshot = Snapshot {
+            seq: log.seq,
+            stats,
+            quota,
+            jobs: jobs.cloned().collect(),
+        };
+        let tmp = self.dir.join("snapshot.json.tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&serde_json::to_vec(&snapshot)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp, self.snapshot_path())?;
+        File::open(&self.dir)?.sync_all()?;
+
+        log.file.set_len(0)?;
+        log.file.sync_all()?;
+        *self.entries.lock().unwrap() = 0;
+        Ok(())
+    }
+
+    /// 读取快照并重放日志，返回状态表和配额使用情况，统计计数写入 `stats`
+    pub fn replay(&self, stats: &SpoolStats) -> io::Result<(HashMap<u64, Job>, UsageLedger)> {
+        let snapshot: Snapshot = match fs::read(self.snapshot_path()) {
+            Ok(content) => serde_json::from_slice(&content)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Snapshot::default(),
+            Err(e) => return Err(e),
+        };
+
+        stats.restore(snapshot.stats);
+        let mut ledger = snapshot.quota;
+        let mut seq = snapshot.seq;
+        let mut jobs: HashMap<u64, Job> = snapshot
+            .jobs
+            .into_iter()
+            .map(|job| (job.job_id as u64, job))
+            .collect();
+
+        let file = File::open(self.dir.join("journal.log"))?;
+        let mut entries = 0;
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            // 崩溃时最后一行可能没写完，忽略无法解析的行
+            let entry: JournalEntry = match serde_json::from_str(&line) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    eprintln!("日志条目解析失败，已跳过：{}", e);
+                    continue;
+                }
+            };
+            entries += 1;
+            // 压缩时清空日志前崩溃留下的条目，已经包含在快照中
+            if entry.seq != 0 && entry.seq <= snapshot.seq {
+                continue;
+            }
+            seq = seq.max(entry.seq);
+            observe_quota(&mut ledger, &jobs, &entry.event);
+            apply(&mut jobs, stats, entry.event);
+        }
+        self.log.lock().unwrap().seq = seq;
+        *self.entries.lock().unwrap() = entries;
+        Ok((jobs, ledger))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::VirtualClock;
+    use crate::job::JobStatus;
+    use chrono::TimeZone;
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()
+    }
+
+    fn open(dir: &Path) -> Journal {
+        Journal::open(dir, Arc::new(VirtualClock::new(start()))).unwrap()
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("spooling-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn job(job_id: usize, status: JobStatus) -> Job {
+        let mut job = Job::new(
+            job_id,
+            1,
+            "team1".to_string(),
+            start(),
+            "int main() {}".to_string(),
+            false,
+            "A".to_string(),
+        );
+        job.status = status;
+        job
+    }
+
+    /// 与 SPOOLing 的 Recorder 相同：先写日志，再修改状态
+    struct State {
+        jobs: HashMap<u64, Job>,
+        stats: SpoolStats,
+        ledger: UsageLedger,
+    }
+
+    impl State {
+        fn new() -> Self {
+            State {
+                jobs: HashMap::new(),
+                stats: SpoolStats::new(),
+                ledger: UsageLedger::default(),
+            }
+        }
+
+        fn record(&mut self, journal: &Journal, event: JournalEvent) {
+            journal.append(event.clone()).unwrap();
+            observe_quota(&mut self.ledger, &self.jobs, &event);
+            apply(&mut self.jobs, &self.stats, event);
+        }
+
+        fn compact(&self, journal: &Journal) {
+            journal
+                .compact(self.jobs.values(), self.stats.snapshot(), self.ledger.clone())
+                .unwrap();
+        }
+
+        /// 重新打开日志目录重放，结果应与当前状态相同
+        fn assert_replayed(&self, dir: &Path) -> Journal {
+            let journal = open(dir);
+            let stats = SpoolStats::new();
+            let (jobs, ledger) = journal.replay(&stats).unwrap();
+            assert_eq!(stats.snapshot(), self.stats.snapshot());
+            assert_eq!(ledger, self.ledger);
+            let mut ids: Vec<_> = jobs.keys().copied().collect();
+            ids.sort();
+            let mut expected: Vec<_> = self.jobs.keys().copied().collect();
+            expected.sort();
+            assert_eq!(ids, expected);
+            for (id, job) in &jobs {
+                assert_eq!(job.status, self.jobs[id].status);
+            }
+            journal
+        }
+    }
+
+    #[test]
+    fn replays_snapshot_then_log() {
+        let dir = scratch_dir("journal-replay");
+        let journal = open(&dir);
+        let mut state = State::new();
+        state.record(&journal, JournalEvent::Upsert(job(1, JobStatus::Waiting)));
+        state.record(&journal, JournalEvent::Rejected);
+        state.record(&journal, JournalEvent::Upsert(job(1, JobStatus::Completed)));
+        state.compact(&journal);
+
+        state.record(&journal, JournalEvent::Upsert(job(2, JobStatus::Waiting)));
+        state.record(&journal, JournalEvent::Delete(1));
+        state.record(&journal, JournalEvent::QuotaReset(None));
+        drop(journal);
+
+        state.assert_replayed(&dir);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn replay_skips_entries_already_in_snapshot() {
+        let dir = scratch_dir("journal-crash");
+        let journal = open(&dir);
+        let mut state = State::new();
+        state.record(&journal, JournalEvent::Upsert(job(1, JobStatus::Waiting)));
+        state.record(&journal, JournalEvent::Rejected);
+        state.record(&journal, JournalEvent::Rejected);
+        state.record(&journal, JournalEvent::Upsert(job(1, JobStatus::Printing)));
+
+        // 模拟快照改名后、清空日志前崩溃：日志仍保留已写进快照的条目
+        let log_path = dir.join("journal.log");
+        let before = fs::read(&log_path).unwrap();
+        state.compact(&journal);
+        fs::write(&log_path, before).unwrap();
+        state.record(&journal, JournalEvent::Rejected);
+        state.record(&journal, JournalEvent::Upsert(job(1, JobStatus::Completed)));
+        drop(journal);
+
+        let journal = state.assert_replayed(&dir);
+        assert_eq!(state.stats.snapshot().rejected, 3);
+
+        // 之后追加的条目序号接在最大序号之后，再次重放也不会被跳过
+        state.record(&journal, JournalEvent::Rejected);
+        drop(journal);
+        state.assert_replayed(&dir);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn entries_are_stamped_with_the_injected_clock() {
+        let dir = scratch_dir("journal-clock");
+        let clock = Arc::new(VirtualClock::new(start()));
+        let journal = Journal::open(&dir, clock.clone()).unwrap();
+        journal.append(JournalEvent::Rejected).unwrap();
+        clock.advance_to(start() + chrono::Duration::minutes(5));
+        journal.append(JournalEvent::Delete(1)).unwrap();
+        drop(journal);
+
+        let times: Vec<_> = fs::read_to_string(dir.join("journal.log"))
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<JournalEntry>(line).unwrap().time)
+            .collect();
+        assert_eq!(times, [start(), start() + chrono::Duration::minutes(5)]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,2 +1,14 @@
+#[allow(non_snake_case)]
 pub mod SPOOLing;
-pub mod NoSPOOLing;
\ No newline at end of file
+#[allow(non_snake_case)]
+pub mod NoSPOOLing;
+pub mod policy;
+pub mod quota;
+pub mod spool_dir;
+pub mod journal;
+pub mod job_id;
+pub mod stats;
+pub mod spooler;
+pub mod compare;
+pub mod sim;
+pub mod workload;
@@ -0,0 +1,607 @@
+use crate::job::Job;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use std::str::FromStr;
+
+/// 井的调度策略：决定井中排队的任务以什么顺序被取出
+///
+/// 井按到达顺序保存任务，每次弹出时由策略从中挑出下一个任务的下标。
+pub trait SchedulingPolicy: Send {
+    /// 策略名称，用于状态展示
+    fn name(&self) -> &'static str;
+
+    /// 从按到达顺序排列的任务中选出下一个要调度的任务，返回其下标；`now` 为当前时间
+    fn select(&mut self, jobs: &[Job], now: DateTime<Utc>) -> Option<usize>;
+}
+
+/// 可在 `SPOOLing::new` 时选择的调度策略，写在 spooler.json 的 `policy` 中，例如 `{"kind": "fifo"}`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PolicyKind {
+    Fifo, // 先来先服务
+    #[default]
+    Priority, // 严格优先级（priority 越小越先）
+    AgingPriority(AgingConfig), // 带老化的优先级，等待越久优先级越高，参数与 kind 写在同一层
+    #[serde(rename = "sjf")]
+    ShortestJobFirst, // 短作业优先（按行数/页数）
+    RoundRobinByTeam, // 按队伍轮转
+    FairShare(FairShareConfig), // 按队伍加权公平调度，参数与 kind 写在同一层
+}
+
+impl PolicyKind {
+    pub fn build(&self) -> Box<dyn SchedulingPolicy> {
+        match self {
+            PolicyKind::Fifo => Box::new(Fifo),
+            PolicyKind::Priority => Box::new(StrictPriority { aging: None }),
+            PolicyKind::AgingPriority(aging) => Box::new(StrictPriority {
+                aging: Some(*aging),
+            }),
+            PolicyKind::ShortestJobFirst => Box::new(ShortestJobFirst),
+            PolicyKind::RoundRobinByTeam => Box::new(RoundRobinByTeam::default()),
+            PolicyKind::FairShare(config) => Box::new(FairShare::new(config.clone())),
+        }
+    }
+
+    /// 配置中使用的策略名称，与 `FromStr` 对应
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            PolicyKind::Fifo => "fifo",
+            PolicyKind::Priority => "priority",
+            PolicyKind::AgingPriority(_) => "aging_priority",
+            PolicyKind::ShortestJobFirst => "sjf",
+            PolicyKind::RoundRobinByTeam => "round_robin_by_team",
+            PolicyKind::FairShare(_) => "fair_share",
+        }
+    }
+
+    /// 策略使用的老化配置，没有老化时为 None
+    pub fn aging(&self) -> Option<AgingConfig> {
+        match self {
+            PolicyKind::AgingPriority(aging) => Some(*aging),
+            _ => None,
+        }
+    }
+}
+
+/// 按名称选择策略（命令行参数 --policy），老化和公平调度的参数取默认值
+impl FromStr for PolicyKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fifo" => Ok(PolicyKind::Fifo),
+            "priority" => Ok(PolicyKind::Priority),
+            "aging_priority" => Ok(PolicyKind::AgingPriority(AgingConfig::default())),
+            "sjf" => Ok(PolicyKind::ShortestJobFirst),
+            "round_robin_by_team" => Ok(PolicyKind::RoundRobinByTeam),
+            "fair_share" => Ok(PolicyKind::FairShare(FairShareConfig::default())),
+            _ => Err(format!(
+                "未知的调度策略 {}（可选 fifo、priority、aging_priority、sjf、round_robin_by_team、fair_share）",
+                s
+            )),
+        }
+    }
+}
+
+/// 优先级老化配置
+///
+/// 任务每等待一分钟，有效优先级数值减小 `boost_per_minute`，最多减小 `max_boost`。
+/// 配置写作 `{"kind": "aging_priority", "boost_per_minute": 1, "max_boost": 10}`，省略的字段取默认值
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct AgingConfig {
+    pub boost_per_minute: u32,
+    pub max_boost: u32,
+}
+
+impl Default for AgingConfig {
+    fn default() -> Self {
+        AgingConfig {
+            boost_per_minute: 1,
+            max_boost: 10,
+        }
+    }
+}
+
+impl AgingConfig {
+    /// 任务在 `now` 时刻的有效优先级
+    pub fn effective_priority(&self, job: &Job, now: DateTime<Utc>) -> u32 {
+        let waited_secs = (now - job.submit_time).num_seconds().max(0) as u64;
+        let boost = waited_secs * self.boost_per_minute as u64 / 60;
+        let boost = boost.min(self.max_boost as u64) as u32;
+        job.priority.saturating_sub(boost)
+    }
+}
+
+/// 公平调度的轮转方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum FairShareMode {
+    #[serde(rename = "wrr")]
+    WeightedRoundRobin, // 每轮按权重连续服务若干个任务
+    #[serde(rename = "drr")]
+    DeficitRoundRobin, // 每轮按权重累加页数额度，按页数扣除
+}
+
+/// 公平调度配置，例如 `{"kind": "fair_share", "mode": "wrr", "weights": {"team1": 2}}`，省略的字段取默认值
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct FairShareConfig {
+    pub mode: FairShareMode,
+    pub default_weight: u32,
+    pub weights: HashMap<String, u32>, // 按队伍覆盖的权重
+    pub quantum: usize,                // 赤字轮转每轮每单位权重增加的页数额度
+}
+
+impl Default for FairShareConfig {
+    fn default() -> Self {
+        FairShareConfig {
+            mode: FairShareMode::DeficitRoundRobin,
+            default_weight: 1,
+            weights: HashMap::new(),
+            quantum: 1,
+        }
+    }
+}
+
+impl FairShareConfig {
+    /// 队伍的权重，最小为 1
+    pub fn weight_of(&self, team_name: &str) -> u32 {
+        self.weights
+            .get(team_name)
+            .copied()
+            .unwrap_or(self.default_weight)
+            .max(1)
+    }
+}
+
+/// 到达顺序的兜底比较：先比提交时间，再比任务id
+fn arrival_order(a: &Job, b: &Job) -> Ordering {
+    a.submit_time
+        .cmp(&b.submit_time)
+        .then(a.job_id.cmp(&b.job_id))
+}
+
+/// 按 `cmp` 选出最小任务的下标，相等时取靠前的
+fn select_min_by<F>(jobs: &[Job], mut cmp: F) -> Option<usize>
+where
+    F: FnMut(&Job, &Job) -> Ordering,
+{
+    jobs.iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| cmp(a, b))
+        .map(|(idx, _)| idx)
+}
+
+/// 先来先服务
+pub struct Fifo;
+
+impl SchedulingPolicy for Fifo {
+    fn name(&self) -> &'static str {
+        "fifo"
+    }
+
+    fn select(&mut self, jobs: &[Job], _now: DateTime<Utc>) -> Option<usize> {
+        select_min_by(jobs, arrival_order)
+    }
+}
+
+/// 严格优先级：priority 越小优先级越高，相同优先级时越早提交越先
+///
+/// 配置了老化时每次弹出都按当前时间重新计算有效优先级。
+pub struct StrictPriority {
+    aging: Option<AgingConfig>,
+}
+
+impl SchedulingPolicy for StrictPriority {
+    fn name(&self) -> &'static str {
+        match self.aging {
+            Some(_) => "aging_priority",
+            None => "priority",
+        }
+    }
+
+    fn select(&mut self, jobs: &[Job], now: DateTime<Utc>) -> Option<usize> {
+        let priority = |job: &Job| match &self.aging {
+            Some(aging) => aging.effective_priority(job, now),
+            None => job.priority,
+        };
+        select_min_by(jobs, |a, b| {
+            priority(a)
+                .cmp(&priority(b))
+                .then_with(|| arrival_order(a, b))
+        })
+    }
+}
+
+/// 短作业优先：按页数（行数）从少到多调度
+pub struct ShortestJobFirst;
+
+impl SchedulingPolicy for ShortestJobFirst {
+    fn name(&self) -> &'static str {
+        "sjf"
+    }
+
+    fn select(&mut self, jobs: &[Job], _now: DateTime<Utc>) -> Option<usize> {
+        select_min_by(jobs, |a, b| {
+            (a.page_count(), a.line_count())
+                .cmp(&(b.page_count(), b.line_count()))
+                .then_with(|| arrival_order(a, b))
+        })
+    }
+}
+
+/// 按队伍轮转：每次换一个队伍，队伍内部先来先服务
+#[derive(Default)]
+pub struct RoundRobinByTeam {
+    last_team: Option<String>,
+}
+
+impl SchedulingPolicy for RoundRobinByTeam {
+    fn name(&self) -> &'static str {
+        "round_robin_by_team"
+    }
+
+    fn select(&mut self, jobs: &[Job], _now: DateTime<Utc>) -> Option<usize> {
+        let team = next_team(&queued_teams(jobs), self.last_team.as_deref())?;
+        let idx = team_head(jobs, &team);
+        self.last_team = Some(team);
+        idx
+    }
+}
+
+/// 井中有任务的队伍，按队伍名排序
+fn queued_teams(jobs: &[Job]) -> BTreeSet<&str> {
+    jobs.iter().map(|job| job.team_name.as_str()).collect()
+}
+
+/// 轮转到 `last` 之后的下一个队伍，到末尾后从头开始
+fn next_team(teams: &BTreeSet<&str>, last: Option<&str>) -> Option<String> {
+    let after = last.and_then(|last| teams.iter().find(|team| **team > last));
+    after.or(teams.first()).map(|team| team.to_string())
+}
+
+/// 队伍子队列的队首任务（该队伍最早到达的任务）
+fn team_head(jobs: &[Job], team_name: &str) -> Option<usize> {
+    jobs.iter()
+        .enumerate()
+        .filter(|(_, job)| job.team_name == team_name)
+        .min_by(|(_, a), (_, b)| arrival_order(a, b))
+        .map(|(idx, _)| idx)
+}
+
+/// 按队伍公平调度：每个队伍一个子队列，按权重轮转取出
+///
+/// 加权轮转下每个队伍每轮最多连续服务 `weight` 个任务；
+/// 赤字轮转下每轮为队伍累加 `quantum * weight` 页额度，队首任务页数不超过额度才能被取出。
+pub struct FairShare {
+    config: FairShareConfig,
+    current: Option<String>,
+    credit: usize,                    // 加权轮转：当前队伍本轮剩余次数
+    deficits: HashMap<String, usize>, // 赤字轮转：各队伍剩余页数额度
+}
+
+impl FairShare {
+    pub fn new(config: FairShareConfig) -> Self {
+        FairShare {
+            config,
+            current: None,
+            credit: 0,
+            deficits: HashMap::new(),
+        }
+    }
+
+    fn select_weighted(&mut self, jobs: &[Job]) -> Option<usize> {
+        let teams = queued_teams(jobs);
+        if let Some(team) = self.current.as_deref()
+            && self.credit > 0
+            && teams.contains(team)
+        {
+            self.credit -= 1;
+            return team_head(jobs, team);
+        }
+
+        let team = next_team(&teams, self.current.as_deref())?;
+        self.credit = self.config.weight_of(&team) as usize - 1;
+        let idx = team_head(jobs, &team);
+        self.current = Some(team);
+        idx
+    }
+
+    fn select_deficit(&mut self, jobs: &[Job]) -> Option<usize> {
+        let teams = queued_teams(jobs);
+        if teams.is_empty() {
+            return None;
+        }
+        // 子队列清空的队伍不保留额度
+        self.deficits
+            .retain(|team, _| teams.contains(team.as_str()));
+
+        loop {
+            if let Some(team) = self.current.as_deref()
+                && let Some(idx) = team_head(jobs, team)
+            {
+                let cost = jobs[idx].page_count();
+                let deficit = self.deficits.entry(team.to_string()).or_insert(0);
+                if cost <= *deficit {
+                    *deficit -= cost;
+                    return Some(idx);
+                }
+            }
+
+            // 额度不足，轮到下一个队伍并为其补充额度
+            let team = next_team(&teams, self.current.as_deref())?;
+            let quantum = self.config.quantum.max(1) * self.config.weight_of(&team) as usize;
+            *self.deficits.entry(team.clone()).or_insert(0) += quantum;
+            self.current = Some(team);
+        }
+    }
+}
+
+impl SchedulingPolicy for FairShare {
+    fn name(&self) -> &'static str {
+        match self.config.mode {
+            FairShareMode::WeightedRoundRobin => "fair_share_wrr",
+            FairShareMode::DeficitRoundRobin => "fair_share_drr",
+        }
+    }
+
+    fn select(&mut self, jobs: &[Job], _now: DateTime<Utc>) -> Option<usize> {
+        match self.config.mode {
+            FairShareMode::WeightedRoundRobin => self.select_weighted(jobs),
+            FairShareMode::DeficitRoundRobin => self.select_deficit(jobs),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osim::spooler::SpoolerConfig;
+
+    fn job(job_id: usize, team_name: &str, priority: u32, submit_time: DateTime<Utc>) -> Job {
+        Job::new(
+            job_id,
+            priority,
+            team_name.to_string(),
+            submit_time,
+            "int main() {}".to_string(),
+            false,
+            "A".to_string(),
+        )
+    }
+
+    /// 按策略依次取出 `count` 个任务，返回取出任务的队伍
+    fn dispatch(policy: &mut dyn SchedulingPolicy, jobs: &mut Vec<Job>, count: usize) -> Vec<String> {
+        let now = Utc::now();
+        (0..count)
+            .map(|_| {
+                let idx = policy.select(jobs, now).unwrap();
+                jobs.remove(idx).team_name
+            })
+            .collect()
+    }
+
+    /// 按策略取出所有任务，返回任务id的顺序
+    fn pop_order(policy: &mut dyn SchedulingPolicy, jobs: &mut Vec<Job>) -> Vec<usize> {
+        let now = Utc::now();
+        let mut order = Vec::new();
+        while let Some(idx) = policy.select(jobs, now) {
+            order.push(jobs.remove(idx).job_id);
+        }
+        order
+    }
+
+    #[test]
+    fn fifo_follows_submit_time_then_job_id() {
+        let now = Utc::now();
+        let second = chrono::Duration::seconds(1);
+        // 井中按放入顺序保存，恢复的任务可能排在较晚提交的任务之后
+        let mut jobs = vec![
+            job(3, "a", 0, now + second),
+            job(1, "b", 9, now),
+            job(2, "c", 5, now + second),
+            job(0, "a", 1, now + second * 2),
+        ];
+        assert_eq!(pop_order(&mut Fifo, &mut jobs), vec![1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn strict_priority_breaks_ties_by_arrival() {
+        let now = Utc::now();
+        let second = chrono::Duration::seconds(1);
+        let mut jobs = vec![
+            job(0, "a", 2, now),
+            job(1, "a", 1, now + second * 3),
+            job(2, "b", 1, now + second),
+            job(3, "b", 0, now + second * 4),
+            job(5, "c", 2, now),
+            job(4, "c", 1, now + second),
+        ];
+        let mut policy = PolicyKind::Priority.build();
+        assert_eq!(policy.name(), "priority");
+        assert_eq!(pop_order(policy.as_mut(), &mut jobs), vec![3, 2, 4, 1, 0, 5]);
+    }
+
+    #[test]
+    fn shortest_job_first_orders_by_pages_then_lines() {
+        let now = Utc::now();
+        let second = chrono::Duration::seconds(1);
+        let with_lines = |job_id: usize, lines: usize, submit_time: DateTime<Utc>| {
+            let mut job = job(job_id, "a", 0, submit_time);
+            job.file_content = "x\n".repeat(lines);
+            job
+        };
+        let mut document = with_lines(4, 1, now);
+        document.document = Some(crate::job::Document {
+            path: String::new(),
+            pages: 2,
+        });
+        let mut jobs = vec![
+            with_lines(0, 130, now),
+            with_lines(1, 10, now),
+            document,
+            with_lines(2, 5, now + second),
+            with_lines(3, 5, now),
+            with_lines(5, 61, now),
+        ];
+        // 附带文档的任务按文档页数计；页数相同时行数少的先，再按到达顺序
+        let mut policy = PolicyKind::ShortestJobFirst.build();
+        assert_eq!(pop_order(policy.as_mut(), &mut jobs), vec![3, 2, 1, 4, 5, 0]);
+    }
+
+    #[test]
+    fn round_robin_rotates_teams_in_name_order() {
+        let now = Utc::now();
+        let second = chrono::Duration::seconds(1);
+        let mut jobs = vec![
+            job(0, "c", 0, now),
+            job(1, "b", 0, now + second),
+            job(2, "a", 0, now + second * 2),
+            job(3, "c", 0, now + second * 3),
+            job(4, "b", 0, now + second * 4),
+            job(5, "c", 0, now + second * 5),
+        ];
+        let mut policy = PolicyKind::RoundRobinByTeam.build();
+        // a 取完后不再占用轮次，队伍内部先来先服务
+        assert_eq!(pop_order(policy.as_mut(), &mut jobs), vec![2, 1, 0, 4, 3, 5]);
+    }
+
+    #[test]
+    fn round_robin_skips_team_whose_last_job_was_removed() {
+        let now = Utc::now();
+        let mut jobs = vec![
+            job(0, "a", 0, now),
+            job(1, "b", 0, now),
+            job(2, "c", 0, now),
+            job(3, "a", 0, now),
+        ];
+        let mut policy = RoundRobinByTeam::default();
+        let idx = policy.select(&jobs, now).unwrap();
+        assert_eq!(jobs.remove(idx).job_id, 0);
+
+        // b 的唯一任务被取消，轮到 c
+        jobs.retain(|job| job.team_name != "b");
+        let idx = policy.select(&jobs, now).unwrap();
+        assert_eq!(jobs.remove(idx).job_id, 2);
+
+        // 上一个队伍 c 已经没有任务，从头轮到 a；井空时返回 None
+        let idx = policy.select(&jobs, now).unwrap();
+        assert_eq!(jobs.remove(idx).job_id, 3);
+        assert_eq!(policy.select(&jobs, now), None);
+
+        // 之后新到的任务仍按队伍名轮转，上一次是 a
+        jobs = vec![job(4, "a", 0, now), job(5, "d", 0, now)];
+        assert_eq!(pop_order(&mut policy, &mut jobs), vec![5, 4]);
+    }
+
+    /// 两个队伍各排 30 个单页任务，权重 2 的队伍应取得两倍的份额
+    fn weighted_share(mode: FairShareMode) {
+        let now = Utc::now();
+        let mut jobs: Vec<Job> = (0..60)
+            .map(|i| job(i, if i % 2 == 0 { "heavy" } else { "light" }, 1, now))
+            .collect();
+        let config = FairShareConfig {
+            mode,
+            weights: HashMap::from([("heavy".to_string(), 2)]),
+            ..FairShareConfig::default()
+        };
+        let mut policy = PolicyKind::FairShare(config).build();
+
+        let teams = dispatch(policy.as_mut(), &mut jobs, 30);
+        let heavy = teams.iter().filter(|team| *team == "heavy").count();
+        assert_eq!((heavy, teams.len() - heavy), (20, 10));
+        // 每三个任务中权重 2 的队伍占两个
+        for window in teams.chunks(3) {
+            assert_eq!(window.iter().filter(|team| *team == "heavy").count(), 2);
+        }
+    }
+
+    #[test]
+    fn weighted_round_robin_follows_weights() {
+        weighted_share(FairShareMode::WeightedRoundRobin);
+    }
+
+    #[test]
+    fn deficit_round_robin_follows_weights() {
+        weighted_share(FairShareMode::DeficitRoundRobin);
+    }
+
+    /// 每分钟到达一个最高优先级任务并取出一个，返回低优先级任务在第几轮被取出
+    fn overtaking_round(aging: AgingConfig, rounds: i64) -> Option<i64> {
+        let start = Utc::now();
+        let mut jobs = vec![job(0, "low", 5, start)];
+        let mut policy = PolicyKind::AgingPriority(aging).build();
+        for round in 0..rounds {
+            let arrival = start + chrono::Duration::minutes(round);
+            jobs.push(job(round as usize + 1, "high", 0, arrival));
+            let idx = policy
+                .select(&jobs, arrival + chrono::Duration::seconds(30))
+                .unwrap();
+            if jobs.remove(idx).team_name == "low" {
+                return Some(round);
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn aging_job_overtakes_once_boost_reaches_cap() {
+        // 优先级差 5，第 5 轮时已等待 5 分半，加成达到上限 5，与新任务持平后按到达顺序胜出
+        let aging = AgingConfig {
+            boost_per_minute: 1,
+            max_boost: 5,
+        };
+        assert_eq!(overtaking_round(aging, 20), Some(5));
+
+        // 每分钟加成 2 时，第 2 轮等待 2 分半即达到上限
+        let fast = AgingConfig {
+            boost_per_minute: 2,
+            max_boost: 5,
+        };
+        assert_eq!(overtaking_round(fast, 20), Some(2));
+    }
+
+    #[test]
+    fn aging_boost_is_capped() {
+        // 加成上限小于优先级差时，低优先级任务一直被跳过
+        let aging = AgingConfig {
+            boost_per_minute: 1,
+            max_boost: 4,
+        };
+        assert_eq!(overtaking_round(aging, 20), None);
+        let waited = job(0, "low", 5, Utc::now() - chrono::Duration::hours(1));
+        assert_eq!(aging.effective_priority(&waited, Utc::now()), 1);
+    }
+
+    #[test]
+    fn aging_loads_from_spooler_config() {
+        let config: SpoolerConfig = serde_json::from_str(
+            r#"{"policy": {"kind": "aging_priority", "boost_per_minute": 2}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.policy.aging(),
+            Some(AgingConfig {
+                boost_per_minute: 2,
+                max_boost: 10,
+            })
+        );
+    }
+
+    #[test]
+    fn fair_share_loads_from_spooler_config() {
+        let config: SpoolerConfig = serde_json::from_str(
+            r#"{"policy": {"kind": "fair_share", "mode": "wrr", "weights": {"team1": 2}}}"#,
+        )
+        .unwrap();
+        let PolicyKind::FairShare(fair_share) = config.policy else {
+            panic!("应为公平调度：{:?}", config.policy);
+        };
+        assert_eq!(fair_share.mode, FairShareMode::WeightedRoundRobin);
+        assert_eq!(fair_share.weight_of("team1"), 2);
+        assert_eq!(fair_share.weight_of("team2"), 1);
+        assert_eq!(fair_share.quantum, 1);
+    }
+}
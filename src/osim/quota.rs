@@ -0,0 +1,279 @@
+use crate::job::{Job, JobStatus};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Mutex;
+
+/// 单个队伍的配额上限，None 表示不限制
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaLimits {
+    pub max_jobs: Option<usize>,      // 整场比赛最多提交的任务数
+    pub max_pages: Option<usize>,     // 整场比赛最多打印的页数
+    pub max_in_flight: Option<usize>, // 同时未打印完的任务数
+}
+
+impl QuotaLimits {
+    /// 用 `other` 中设置了的字段覆盖当前配置
+    fn overridden_by(self, other: &QuotaLimits) -> QuotaLimits {
+        QuotaLimits {
+            max_jobs: other.max_jobs.or(self.max_jobs),
+            max_pages: other.max_pages.or(self.max_pages),
+            max_in_flight: other.max_in_flight.or(self.max_in_flight),
+        }
+    }
+}
+
+/// 配额配置：全局上限，可按队伍覆盖
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaConfig {
+    #[serde(default)]
+    pub global: QuotaLimits,
+    #[serde(default)]
+    pub teams: HashMap<String, QuotaLimits>,
+}
+
+impl QuotaConfig {
+    /// 队伍实际生效的配额
+    pub fn limits_of(&self, team_name: &str) -> QuotaLimits {
+        match self.teams.get(team_name) {
+            Some(team) => self.global.overridden_by(team),
+            None => self.global,
+        }
+    }
+}
+
+/// 队伍的配额使用情况
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TeamUsage {
+    pub jobs: usize,
+    pub pages: usize,
+    pub in_flight: usize,
+}
+
+/// 超出的配额种类
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuotaViolation {
+    Jobs { limit: usize },
+    Pages { limit: usize, requested: usize, used: usize },
+    InFlight { limit: usize },
+}
+
+impl fmt::Display for QuotaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuotaViolation::Jobs { limit } => write!(f, "任务数已达上限 {}", limit),
+            QuotaViolation::Pages {
+                limit,
+                requested,
+                used,
+            } => write!(
+                f,
+                "页数超出上限 {}（已用 {} 页，本次 {} 页）",
+                limit, used, requested
+            ),
+            QuotaViolation::InFlight { limit } => {
+                write!(f, "未完成任务数已达上限 {}", limit)
+            }
+        }
+    }
+}
+
+/// 按队伍统计并限制提交量
+pub struct QuotaManager {
+    config: QuotaConfig,
+    usage: Mutex<HashMap<String, TeamUsage>>,
+}
+
+impl QuotaManager {
+    pub fn new(config: QuotaConfig) -> Self {
+        QuotaManager {
+            config,
+            usage: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 检查配额并为一次提交预留额度
+    pub fn try_reserve(&self, team_name: &str, pages: usize) -> Result<(), QuotaViolation> {
+        let limits = self.config.limits_of(team_name);
+        let mut usage = self.usage.lock().unwrap();
+        let team = usage.entry(team_name.to_string()).or_default();
+
+        if let Some(limit) = limits.max_jobs
+            && team.jobs >= limit
+        {
+            return Err(QuotaViolation::Jobs { limit });
+        }
+        if let Some(limit) = limits.max_pages
+            && team.pages + pages > limit
+        {
+            return Err(QuotaViolation::Pages {
+                limit,
+                requested: pages,
+                used: team.pages,
+            });
+        }
+        if let Some(limit) = limits.max_in_flight
+            && team.in_flight >= limit
+        {
+            return Err(QuotaViolation::InFlight { limit });
+        }
+
+        Self::add(team, pages);
+        Ok(())
+    }
+
+    /// 不检查上限直接预留，用于恢复已被接收过的任务，保证之后的释放有对应的预留
+    pub fn reserve(&self, team_name: &str, pages: usize) {
+        let mut usage = self.usage.lock().unwrap();
+        Self::add(usage.entry(team_name.to_string()).or_default(), pages);
+    }
+
+    fn add(team: &mut TeamUsage, pages: usize) {
+        team.jobs += 1;
+        team.pages += pages;
+        team.in_flight += 1;
+    }
+
+    /// 撤销一次预留（任务最终没有被系统接收）
+    pub fn rollback(&self, team_name: &str, pages: usize) {
+        let mut usage = self.usage.lock().unwrap();
+        if let Some(team) = usage.get_mut(team_name) {
+            team.jobs = team.jobs.saturating_sub(1);
+            team.pages = team.pages.saturating_sub(pages);
+            team.in_flight = team.in_flight.saturating_sub(1);
+        }
+    }
+
+    /// 任务离开系统（打印完成或失败），释放未完成任务名额
+    pub fn release(&self, team_name: &str) {
+        let mut usage = self.usage.lock().unwrap();
+        if let Some(team) = usage.get_mut(team_name) {
+            team.in_flight = team.in_flight.saturating_sub(1);
+        }
+    }
+
+    /// 某队伍的使用情况和生效配额
+    pub fn usage_of(&self, team_name: &str) -> (TeamUsage, QuotaLimits) {
+        let usage = self.usage.lock().unwrap();
+        (
+            usage.get(team_name).copied().unwrap_or_default(),
+            self.config.limits_of(team_name),
+        )
+    }
+
+    /// 所有队伍的使用情况
+    pub fn all_usage(&self) -> BTreeMap<String, TeamUsage> {
+        let usage = self.usage.lock().unwrap();
+        usage
+            .iter()
+            .map(|(team, usage)| (team.clone(), *usage))
+            .collect()
+    }
+
+    /// 清零累计的任务数和页数，保留仍未完成的任务；`team_name` 为 None 时重置所有队伍
+    pub fn reset(&self, team_name: Option<&str>) {
+        reset_usage(self.usage.lock().unwrap().iter_mut(), team_name);
+    }
+
+    /// 用重放日志得到的使用情况覆盖当前计数
+    pub fn restore(&self, ledger: &UsageLedger) {
+        let mut usage = self.usage.lock().unwrap();
+        *usage = ledger
+            .0
+            .iter()
+            .map(|(team, usage)| (team.clone(), *usage))
+            .collect();
+    }
+}
+
+fn reset_usage<'a>(
+    usage: impl Iterator<Item = (&'a String, &'a mut TeamUsage)>,
+    team_name: Option<&str>,
+) {
+    for (team, team_usage) in usage {
+        if team_name.is_none_or(|name| name == team) {
+            team_usage.jobs = 0;
+            team_usage.pages = 0;
+        }
+    }
+}
+
+/// 按预写日志中的状态变化推算的配额使用情况，随快照持久化，重启后用来恢复 `QuotaManager`。
+///
+/// 规则与运行时相同：任务进入系统时预留，没能放进缓冲区时撤销，第一次结束时释放未完成名额。
+/// 运行时的预留发生在写日志之前，所以快照只能从日志推算，不能直接取 `QuotaManager` 的计数
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageLedger(BTreeMap<String, TeamUsage>);
+
+impl UsageLedger {
+    /// 根据任务状态的变化更新使用情况，`previous` 为状态表中原来的记录
+    pub fn observe(&mut self, previous: Option<&Job>, job: &Job) {
+        let previous = previous.map(|job| &job.status);
+        if previous.is_some_and(|status| status.is_finished()) {
+            return;
+        }
+        match (previous, &job.status) {
+            (None, JobStatus::Waiting) => {
+                QuotaManager::add(self.0.entry(job.team_name.clone()).or_default(), job.page_count());
+            }
+            (Some(_), JobStatus::SubmitFailed) => {
+                if let Some(team) = self.0.get_mut(&job.team_name) {
+                    team.jobs = team.jobs.saturating_sub(1);
+                    team.pages = team.pages.saturating_sub(job.page_count());
+                    team.in_flight = team.in_flight.saturating_sub(1);
+                }
+            }
+            (Some(_), status) if status.is_finished() => {
+                if let Some(team) = self.0.get_mut(&job.team_name) {
+                    team.in_flight = team.in_flight.saturating_sub(1);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn reset(&mut self, team_name: Option<&str>) {
+        reset_usage(self.0.iter_mut(), team_name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager(limits: QuotaLimits) -> QuotaManager {
+        QuotaManager::new(QuotaConfig {
+            global: limits,
+            teams: HashMap::new(),
+        })
+    }
+
+    #[test]
+    fn recovered_jobs_keep_their_reservation() {
+        let quota = manager(QuotaLimits {
+            max_in_flight: Some(1),
+            ..QuotaLimits::default()
+        });
+        // 重启后恢复的两个任务都超出上限，仍然各占一个名额
+        quota.reserve("team1", 2);
+        quota.reserve("team1", 3);
+        assert_eq!(
+            quota.usage_of("team1").0,
+            TeamUsage {
+                jobs: 2,
+                pages: 5,
+                in_flight: 2,
+            }
+        );
+
+        // 其中一个结束后，另一个仍在打印，新的提交仍被拒绝
+        quota.release("team1");
+        assert_eq!(quota.usage_of("team1").0.in_flight, 1);
+        assert_eq!(
+            quota.try_reserve("team1", 1),
+            Err(QuotaViolation::InFlight { limit: 1 })
+        );
+        quota.release("team1");
+        assert_eq!(quota.try_reserve("team1", 1), Ok(()));
+    }
+}
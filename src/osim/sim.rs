@@ -0,0 +1,641 @@
+use crate::clock::{Clock, VirtualClock};
+use crate::job::{Job, JobStatus};
+use crate::osim::NoSPOOLing::fail_unrerouted;
+use crate::osim::SPOOLing::{Buffer, HeapWell, bounce, format_header, rawJob};
+use crate::osim::compare::ModeReport;
+use crate::osim::job_id::JobIdAllocator;
+use crate::osim::journal::{self, JournalEvent};
+use crate::osim::policy::PolicyKind;
+use crate::osim::quota::{QuotaConfig, QuotaManager};
+use crate::osim::stats::SpoolStats;
+use crate::printer::{
+    FaultPlan, FaultWindow, PoolConfig, PrinterConfig, PrinterFault, Route, Slot, route,
+};
+use crate::rng::Rng;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::Arc;
+
+/// 模拟的起始时间，固定以保证每次结果相同
+pub fn sim_epoch() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()
+}
+
+/// 模拟参数，与正常运行时的配置对应
+#[derive(Clone)]
+pub struct SimConfig {
+    pub spooling: bool,           // false 时模拟 NoSPOOLing
+    pub buffer_sizes: [usize; 4], // 输入缓冲区、输入井、输出井、输出缓冲区
+    pub policy: PolicyKind,
+    pub quota: QuotaConfig,
+    pub pool: PoolConfig,
+    pub sample_ms: i64, // 队列占用的采样间隔，0 表示不采样
+    pub seed: u64,      // 打印耗时抖动和随机故障的种子
+}
+
+/// 单台打印机的模拟结果
+#[derive(Debug, Clone, Serialize)]
+pub struct PrinterReport {
+    pub name: String,
+    pub printed: usize,
+    pub busy_ms: i64,     // 打印耗时总和
+    pub utilization: f64, // busy_ms 占 makespan 的比例
+    pub interrupted: usize, // 因故障中断的任务数
+    pub faulted_ms: i64,    // 处于故障状态的总时长
+}
+
+/// 各缓冲区和井中的任务数（包括工作线程手中等待放入的任务），以及正在打印的任务数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct StageCounts {
+    pub input_buffer: usize,
+    pub input_well: usize,
+    pub output_well: usize,
+    pub output_buffer: usize,
+    pub printing: usize,
+}
+
+impl StageCounts {
+    fn max(self, other: StageCounts) -> StageCounts {
+        StageCounts {
+            input_buffer: self.input_buffer.max(other.input_buffer),
+            input_well: self.input_well.max(other.input_well),
+            output_well: self.output_well.max(other.output_well),
+            output_buffer: self.output_buffer.max(other.output_buffer),
+            printing: self.printing.max(other.printing),
+        }
+    }
+}
+
+/// 某一时刻的队列占用
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Occupancy {
+    pub time_ms: i64, // 相对第一个任务到达的时间
+    #[serde(flatten)]
+    pub counts: StageCounts,
+}
+
+/// 按原因统计的拒绝次数
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Rejections {
+    pub quota: usize,       // 超出配额
+    pub buffer_full: usize, // 输入缓冲区满（SPOOLing）或打印机忙（NoSPOOLing）
+    pub no_printer: usize,  // 没有能打印该任务的打印机
+}
+
+/// 一次模拟的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct SimReport {
+    pub mode: &'static str,
+    pub policy: &'static str,
+    pub jobs: usize,
+    #[serde(flatten)]
+    pub report: ModeReport,
+    pub rejections: Rejections,
+    pub makespan_ms: i64,        // 第一个任务提交到最后一个任务结束
+    pub throughput_per_min: f64, // 每分钟打印完成的任务数
+    pub printers: Vec<PrinterReport>,
+    pub peak_occupancy: StageCounts,
+    pub occupancy: Vec<Occupancy>,
+}
+
+/// 按时间排序的事件；同一时刻先处理打印结束和故障，再按加入顺序处理
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Event {
+    Finish(usize, u64), // 打印机打印结束，第二项与打印机的 `token` 不同时表示该次打印已被中断
+    FaultEnd(usize),    // 打印机故障排除
+    FaultStart(usize),  // 打印机发生故障
+    Arrival(usize),     // 负载中的第几个任务到达
+}
+
+struct SimPrinter {
+    config: PrinterConfig,
+    printing: Option<Job>,
+    printed: usize,
+    busy_ms: i64,
+    busy_until: DateTime<Utc>,
+    token: u64,
+    rng: Rng,
+    plan: FaultPlan,
+    window: Option<FaultWindow>, // 下一次或当前的故障
+    fault: Option<PrinterFault>,
+    interrupted: usize,
+    faulted_ms: i64,
+}
+
+/// 离散事件模拟：单线程按虚拟时间推进，复用 SPOOLing 的缓冲区、井和调度策略。
+/// 工作线程的搬运不耗时，打印耗时按各打印机的 `PrintSpeed` 计算
+/// 不启动 SPOOLing 的工作线程和 `Printer`：`pump` 按同样的顺序重现四个工作线程的搬运，
+/// 打印由 `SimPrinter` 按事件推进，线程间的并发时序不在模拟范围内
+struct Engine {
+    config: SimConfig,
+    clock: Arc<VirtualClock>,
+    ids: JobIdAllocator,
+    quota: QuotaManager,
+    stats: SpoolStats,
+    jobs: HashMap<u64, Job>,
+    input_buffer: Buffer<Job>,
+    input_well: HeapWell,
+    output_well: HeapWell,
+    output_buffer: Buffer<Job>,
+    in_hand: [Option<Job>; 4], // 四个工作线程手中的任务，下游满时停在这里
+    printers: Vec<SimPrinter>,
+    rejections: Rejections,
+    pending_arrivals: usize,
+    events: BinaryHeap<Reverse<(DateTime<Utc>, Event, u64)>>,
+    seq: u64,
+}
+
+impl Engine {
+    fn new(config: SimConfig) -> Self {
+        let clock = Arc::new(VirtualClock::new(sim_epoch()));
+        let [input_buffer, input_well, output_well, output_buffer] = config.buffer_sizes;
+        Engine {
+            ids: JobIdAllocator::in_memory(),
+            quota: QuotaManager::new(config.quota.clone()),
+            stats: SpoolStats::new(),
+            jobs: HashMap::new(),
+            input_buffer: Buffer::new(input_buffer),
+            input_well: HeapWell::new(
+                input_well,
+                config.policy.build(),
+                clock.clone(),
+            ),
+            output_well: HeapWell::new(
+                output_well,
+                config.policy.build(),
+                clock.clone(),
+            ),
+            output_buffer: Buffer::new(output_buffer),
+            in_hand: Default::default(),
+            printers: config
+                .pool
+                .printers
+                .iter()
+                .enumerate()
+                .map(|(index, printer)| SimPrinter {
+                    config: printer.clone(),
+                    printing: None,
+                    printed: 0,
+                    busy_ms: 0,
+                    busy_until: sim_epoch(),
+                    token: 0,
+                    rng: Rng::new(config.seed.wrapping_add(index as u64)),
+                    plan: printer.faults.plan(Rng::new(
+                        config.seed ^ (index as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15),
+                    )),
+                    window: None,
+                    fault: None,
+                    interrupted: 0,
+                    faulted_ms: 0,
+                })
+                .collect(),
+            rejections: Rejections::default(),
+            pending_arrivals: 0,
+            events: BinaryHeap::new(),
+            seq: 0,
+            clock,
+            config,
+        }
+    }
+
+    fn schedule(&mut self, time: DateTime<Utc>, event: Event) {
+        self.seq += 1;
+        self.events.push(Reverse((time, event, self.seq)));
+    }
+
+    /// 写入任务状态，不释放配额
+    fn insert(&mut self, job: &Job) {
+        journal::apply(&mut self.jobs, &self.stats, JournalEvent::Upsert(job.clone()));
+    }
+
+    /// 写入任务状态，第一次进入结束状态时释放配额
+    fn record(&mut self, job: &Job) {
+        let was_finished = self
+            .jobs
+            .get(&(job.job_id as u64))
+            .is_some_and(|job| job.status.is_finished());
+        if job.status.is_finished() && !was_finished {
+            self.quota.release(&job.team_name);
+        }
+        self.insert(job);
+    }
+
+    /// 按 `printer::route` 的规则选择打印机。模拟是单线程的，选出的第一台总能接收任务
+    fn route(&self, color: bool) -> Route {
+        let slots: Vec<Slot> = self
+            .printers
+            .iter()
+            .map(|printer| Slot {
+                color: printer.config.color,
+                free: printer.printing.is_none() && printer.fault.is_none(),
+                printed: printer.printed,
+            })
+            .collect();
+        route(&slots, color, self.config.pool.color_fallback)
+    }
+
+    /// 交给选出的第一台打印机，降级时按黑白打印
+    fn dispatch(&mut self, order: &[usize], downgrade: bool, mut job: Job) {
+        job.color &= !downgrade;
+        self.start_printing(order[0], job);
+    }
+
+    fn start_printing(&mut self, index: usize, mut job: Job) {
+        job.start_printing(self.clock.now());
+        self.record(&job);
+        let printer = &mut self.printers[index];
+        let duration = printer
+            .config
+            .speed
+            .duration(job.page_count(), job.color, printer.rng.next_f64());
+        let duration = chrono::Duration::from_std(duration).unwrap_or(chrono::Duration::MAX);
+        printer.busy_ms += duration.num_milliseconds();
+        printer.busy_until = self.clock.now() + duration;
+        printer.token += 1;
+        printer.printing = Some(job);
+        let token = printer.token;
+        self.schedule(self.clock.now() + duration, Event::Finish(index, token));
+    }
+
+    fn finish(&mut self, index: usize, token: u64) {
+        let printer = &mut self.printers[index];
+        if printer.token != token {
+            return;
+        }
+        let Some(mut job) = printer.printing.take() else {
+            return;
+        };
+        printer.printed += 1;
+        job.complete(self.clock.now());
+        self.record(&job);
+    }
+
+    /// 没有待到达的任务，系统中也没有任务
+    fn settled(&self) -> bool {
+        self.pending_arrivals == 0 && self.counts() == StageCounts::default()
+    }
+
+    /// 安排打印机的下一次故障
+    fn schedule_fault(&mut self, index: usize) {
+        let printer = &mut self.printers[index];
+        printer.window = printer.plan.next();
+        if let Some(window) = printer.window {
+            let start = sim_epoch()
+                + chrono::Duration::from_std(window.start).unwrap_or(chrono::Duration::MAX);
+            self.schedule(start.max(self.clock.now()), Event::FaultStart(index));
+        }
+    }
+
+    /// 故障开始：中断正在打印的任务。系统中已经没有任务时不再模拟故障，否则随机故障永不结束
+    fn fault_start(&mut self, index: usize) {
+        if self.settled() {
+            return;
+        }
+        let Some(window) = self.printers[index].window else {
+            return;
+        };
+        let now = self.clock.now();
+        let printer = &mut self.printers[index];
+        printer.fault = Some(window.fault);
+        let duration = chrono::Duration::from_std(window.duration).unwrap_or(chrono::Duration::MAX);
+        printer.faulted_ms += duration.num_milliseconds();
+        self.schedule(now + duration, Event::FaultEnd(index));
+
+        let printer = &mut self.printers[index];
+        let Some(mut job) = printer.printing.take() else {
+            return;
+        };
+        printer.token += 1;
+        printer.interrupted += 1;
+        printer.busy_ms -= (printer.busy_until - now).num_milliseconds();
+        job.interrupt(format!("打印机 {} {}", printer.config.name, window.fault), now);
+        self.record(&job);
+        if job.status != JobStatus::Waiting {
+            return;
+        }
+
+        // 与正常运行时相同：SPOOLing 放回输出井，NoSPOOLing 换一台空闲打印机
+        if self.config.spooling {
+            self.output_well.restore(job);
+            return;
+        }
+        match self.route(job.color) {
+            Route::Printers { order, downgrade } => self.dispatch(&order, downgrade, job),
+            Route::Wait | Route::Reject(_) => {
+                fail_unrerouted(&mut job, now);
+                self.record(&job);
+            }
+        }
+    }
+
+    fn fault_end(&mut self, index: usize) {
+        self.printers[index].fault = None;
+        self.schedule_fault(index);
+    }
+
+    fn arrive(&mut self, data: rawJob) {
+        self.pending_arrivals -= 1;
+        let quota = self.config.spooling.then_some(&self.quota);
+        let mut job = match data.admit(quota, &self.ids) {
+            Ok(job) => job,
+            Err(_) => {
+                self.stats.reject();
+                self.rejections.quota += 1;
+                return;
+            }
+        };
+        self.insert(&job);
+
+        if self.config.spooling {
+            if let Err(mut job) = self.input_buffer.try_push(job) {
+                bounce(&self.quota, &mut job);
+                self.rejections.buffer_full += 1;
+                self.insert(&job);
+            }
+            return;
+        }
+
+        // NoSPOOLing：直接交给打印机，没有空闲打印机时提交失败
+        match self.route(job.color) {
+            Route::Printers { order, downgrade } => self.dispatch(&order, downgrade, job),
+            route => {
+                match route {
+                    Route::Reject(_) => self.rejections.no_printer += 1,
+                    _ => self.rejections.buffer_full += 1,
+                }
+                job.status = JobStatus::SubmitFailed;
+                self.insert(&job);
+            }
+        }
+    }
+
+    /// 让四个工作线程搬运任务，直到没有任务可以移动
+    fn pump(&mut self) {
+        if !self.config.spooling {
+            return;
+        }
+        loop {
+            let mut moved = false;
+
+            // 输出缓冲区 → 打印机
+            if self.in_hand[3].is_none() {
+                self.in_hand[3] = self.output_buffer.try_pop();
+                moved |= self.in_hand[3].is_some();
+            }
+            if let Some(mut job) = self.in_hand[3].take() {
+                match self.route(job.color) {
+                    Route::Printers { order, downgrade } => {
+                        self.dispatch(&order, downgrade, job);
+                        moved = true;
+                    }
+                    Route::Reject(reason) => {
+                        job.fail(reason, self.clock.now());
+                        self.record(&job);
+                        moved = true;
+                    }
+                    Route::Wait => self.in_hand[3] = Some(job),
+                }
+            }
+
+            // 输出井 → 输出缓冲区
+            if self.in_hand[2].is_none() {
+                self.in_hand[2] = self.output_well.pop();
+                moved |= self.in_hand[2].is_some();
+            }
+            if let Some(job) = self.in_hand[2].take() {
+                match self.output_buffer.try_push(job) {
+                    Ok(()) => moved = true,
+                    Err(job) => self.in_hand[2] = Some(job),
+                }
+            }
+
+            // 输入井 → 输出井，取出时格式化
+            if self.in_hand[1].is_none()
+                && let Some(mut job) = self.input_well.pop()
+            {
+                format_header(&mut job);
+                self.record(&job);
+                self.in_hand[1] = Some(job);
+                moved = true;
+            }
+            if let Some(job) = self.in_hand[1].take() {
+                match self.output_well.push(job) {
+                    Ok(()) => moved = true,
+                    Err(job) => self.in_hand[1] = Some(*job),
+                }
+            }
+
+            // 输入缓冲区 → 输入井
+            if self.in_hand[0].is_none() {
+                self.in_hand[0] = self.input_buffer.try_pop();
+                moved |= self.in_hand[0].is_some();
+            }
+            if let Some(job) = self.in_hand[0].take() {
+                match self.input_well.push(job) {
+                    Ok(()) => moved = true,
+                    Err(job) => self.in_hand[0] = Some(*job),
+                }
+            }
+
+            if !moved {
+                break;
+            }
+        }
+    }
+
+    fn counts(&self) -> StageCounts {
+        let held = |stage: usize| usize::from(self.in_hand[stage].is_some());
+        StageCounts {
+            input_buffer: self.input_buffer.size() + held(0),
+            input_well: self.input_well.len() + held(1),
+            output_well: self.output_well.len() + held(2),
+            output_buffer: self.output_buffer.size() + held(3),
+            printing: self
+                .printers
+                .iter()
+                .filter(|printer| printer.printing.is_some())
+                .count(),
+        }
+    }
+
+    fn run(mut self, workload: Vec<rawJob>) -> SimReport {
+        let total = workload.len();
+        let first_arrival = workload.iter().map(|job| job.submit_time).min();
+        let mut workload: Vec<Option<rawJob>> = workload.into_iter().map(Some).collect();
+        for (index, job) in workload.iter().enumerate() {
+            let time = job.as_ref().map(|job| job.submit_time).unwrap();
+            self.schedule(time, Event::Arrival(index));
+        }
+        self.pending_arrivals = total;
+        for index in 0..self.printers.len() {
+            self.schedule_fault(index);
+        }
+
+        // 从第一个任务到达开始采样；两次事件之间状态不变，采样点取上一次事件处理后的状态
+        let start = first_arrival.unwrap_or_else(sim_epoch);
+        let sample = chrono::Duration::milliseconds(self.config.sample_ms);
+        let mut next_sample = start;
+        let mut occupancy = Vec::new();
+        let mut peak = StageCounts::default();
+        let mut take_samples = |until: DateTime<Utc>, counts: StageCounts, inclusive: bool| {
+            while sample > chrono::Duration::zero()
+                && (next_sample < until || (inclusive && next_sample == until))
+            {
+                occupancy.push(Occupancy {
+                    time_ms: (next_sample - start).num_milliseconds(),
+                    counts,
+                });
+                next_sample += sample;
+            }
+        };
+
+        while let Some(Reverse((time, event, _))) = self.events.pop() {
+            take_samples(time, self.counts(), false);
+            self.clock.advance_to(time);
+            match event {
+                Event::Arrival(index) => {
+                    if let Some(data) = workload[index].take() {
+                        self.arrive(data);
+                    }
+                }
+                Event::Finish(index, token) => self.finish(index, token),
+                Event::FaultStart(index) => self.fault_start(index),
+                Event::FaultEnd(index) => self.fault_end(index),
+            }
+            self.pump();
+            peak = peak.max(self.counts());
+        }
+        take_samples(self.clock.now(), self.counts(), true);
+
+        let jobs: Vec<Job> = self.jobs.values().cloned().collect();
+        let makespan_ms = first_arrival.map_or(0, |start| {
+            jobs.iter()
+                .filter_map(|job| job.end_print_time)
+                .max()
+                .map_or(0, |end| (end - start).num_milliseconds())
+        });
+        let stats = self.stats.snapshot();
+        let throughput_per_min = if makespan_ms > 0 {
+            stats.printed as f64 * 60_000.0 / makespan_ms as f64
+        } else {
+            0.0
+        };
+        SimReport {
+            mode: if self.config.spooling {
+                "spooling"
+            } else {
+                "no_spooling"
+            },
+            policy: self.input_well.policy_name(),
+            jobs: total,
+            report: ModeReport::new(stats, &jobs),
+            rejections: self.rejections,
+            makespan_ms,
+            throughput_per_min,
+            printers: self
+                .printers
+                .iter()
+                .map(|printer| PrinterReport {
+                    name: printer.config.name.clone(),
+                    printed: printer.printed,
+                    busy_ms: printer.busy_ms,
+                    utilization: if makespan_ms > 0 {
+                        printer.busy_ms as f64 / makespan_ms as f64
+                    } else {
+                        0.0
+                    },
+                    interrupted: printer.interrupted,
+                    faulted_ms: printer.faulted_ms,
+                })
+                .collect(),
+            peak_occupancy: peak,
+            occupancy,
+        }
+    }
+}
+
+/// 在虚拟时间中运行一组任务，同样的配置和负载总是得到同样的结果
+pub fn simulate(config: SimConfig, workload: Vec<rawJob>) -> SimReport {
+    Engine::new(config).run(workload)
+}
+
+/// 固定间隔到达的简单负载：队伍、优先级、长度和颜色按序号轮换
+pub fn steady_workload(count: usize, interval_ms: i64) -> Vec<rawJob> {
+    (0..count)
+        .map(|i| rawJob {
+            priority: (i * 7 % 5) as u32,
+            team_name: format!("team-{}", i % 8),
+            submit_time: sim_epoch() + chrono::Duration::milliseconds(interval_ms * i as i64),
+            file_content: (0..(i * 13 % 120 + 1))
+                .map(|line| format!("line {}", line))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            color: i % 4 == 0,
+            problem_name: format!("{}", (b'A' + (i % 12) as u8) as char),
+            document: None,
+            language: None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 带抖动和随机故障的打印机池、一个很紧的配额，覆盖重新分派、降级和配额拒绝
+    fn config(spooling: bool, seed: u64) -> SimConfig {
+        let pool = serde_json::from_value(serde_json::json!({
+            "color_fallback": "downgrade",
+            "printers": [
+                {
+                    "name": "mono", "color": false, "location": "A",
+                    "speed": { "warmup_ms": 1000, "mono_page_ms": 2000, "color_page_ms": 3000, "jitter": 0.3 },
+                    "faults": { "random": { "mean_interval_secs": 120.0, "mean_repair_secs": 30.0 } }
+                },
+                {
+                    "name": "color", "color": true, "location": "B",
+                    "speed": { "warmup_ms": 1000, "mono_page_ms": 2000, "color_page_ms": 3000, "jitter": 0.3 },
+                    "faults": { "random": { "mean_interval_secs": 180.0, "mean_repair_secs": 45.0 } }
+                }
+            ]
+        }))
+        .unwrap();
+        let quota = serde_json::from_value(serde_json::json!({
+            "global": { "max_in_flight": 2 }
+        }))
+        .unwrap();
+        SimConfig {
+            spooling,
+            buffer_sizes: [4, 8, 8, 2],
+            policy: PolicyKind::default(),
+            quota,
+            pool,
+            sample_ms: 30_000,
+            seed,
+        }
+    }
+
+    fn run(spooling: bool, seed: u64) -> serde_json::Value {
+        serde_json::to_value(simulate(config(spooling, seed), steady_workload(200, 4_000))).unwrap()
+    }
+
+    #[test]
+    fn same_seed_gives_identical_reports() {
+        for spooling in [true, false] {
+            let first = run(spooling, 7);
+            assert_eq!(first, run(spooling, 7));
+            assert_eq!(first["jobs"], 200);
+            // 确认负载确实经过了故障中断
+            let interrupted: u64 = first["printers"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|printer| printer["interrupted"].as_u64().unwrap())
+                .sum();
+            assert!(interrupted > 0, "{}", first);
+        }
+        assert_ne!(run(true, 7), run(true, 8));
+    }
+}
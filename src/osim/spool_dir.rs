@@ -0,0 +1,82 @@
+use crate::job::Job;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// 任务在假脱机系统中所处的阶段，决定恢复时放回哪个井
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpoolStage {
+    Input,  // 尚未格式化，位于输入缓冲区或输入井
+    Output, // 已格式化，位于输出井、输出缓冲区或打印机
+}
+
+/// 假脱机目录中每个任务对应的文件内容
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpoolRecord {
+    pub stage: SpoolStage,
+    pub job: Job,
+}
+
+/// 磁盘上的假脱机目录：每个未完成的任务一个文件，任务结束后删除
+pub struct SpoolDir {
+    root: PathBuf,
+}
+
+impl SpoolDir {
+    pub fn open(root: impl AsRef<Path>) -> io::Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        fs::create_dir_all(&root)?;
+        Ok(SpoolDir { root })
+    }
+
+    fn path_of(&self, job_id: usize) -> PathBuf {
+        self.root.join(format!("{:08}.json", job_id))
+    }
+
+    /// 写入任务，先写临时文件再改名，避免崩溃时留下半个文件；
+    /// 文件和目录都 fsync 后才返回，断电后已接收的任务仍能恢复
+    pub fn store(&self, stage: SpoolStage, job: &Job) -> io::Result<()> {
+        let record = SpoolRecord {
+            stage,
+            job: job.clone(),
+        };
+        let content = serde_json::to_vec(&record)?;
+        let path = self.path_of(job.job_id);
+        let tmp = path.with_extension("json.tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&content)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &path)?;
+        File::open(&self.root)?.sync_all()
+    }
+
+    /// 删除任务文件，文件不存在时忽略
+    pub fn remove(&self, job_id: usize) -> io::Result<()> {
+        match fs::remove_file(self.path_of(job_id)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// 读取目录中所有任务，按任务id排序；损坏的文件跳过并报告
+    pub fn load_all(&self) -> io::Result<Vec<SpoolRecord>> {
+        let mut records = Vec::new();
+        for entry in fs::read_dir(&self.root)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let parsed = fs::read(&path).and_then(|content| {
+                serde_json::from_slice::<SpoolRecord>(&content).map_err(io::Error::from)
+            });
+            match parsed {
+                Ok(record) => records.push(record),
+                Err(e) => eprintln!("假脱机文件 {} 读取失败，已跳过：{}", path.display(), e),
+            }
+        }
+        records.sort_by_key(|record| record.job.job_id);
+        Ok(records)
+    }
+}
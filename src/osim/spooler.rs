@@ -0,0 +1,121 @@
+use crate::job::Job;
+use crate::osim::SPOOLing::{CancelError, SpoolMode, SubmitError, rawJob};
+use crate::osim::policy::PolicyKind;
+use crate::osim::quota::QuotaManager;
+use crate::osim::stats::StatsSnapshot;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// HTTP 层使用的假脱机系统接口，SPOOLing 和 NoSPOOLing 都实现它。
+/// 只有 SPOOLing 支持的功能（配额、暂停、排空）提供默认实现
+pub trait Spooler: Send + Sync {
+    /// 提交任务，成功时返回任务id。附带的文档从此由假脱机系统负责：
+    /// 任务结束或提交失败时由它删除，调用方不再删除
+    fn submit_job(&self, data: rawJob) -> Result<usize, SubmitError>;
+
+    /// 系统运行状态的文字描述
+    fn get_status(&self) -> String;
+
+    /// 所有提交成功的任务id
+    fn get_active_job_id(&self) -> Vec<u64>;
+
+    /// 查询单个任务
+    fn get_job(&self, job_id: u64) -> Option<Job>;
+
+    /// 所有任务
+    fn list_jobs(&self) -> Vec<Job>;
+
+    /// 从状态表删除任务记录
+    fn delete_jobs(&self, job_ids: &[u64]);
+
+    /// 取消尚未结束的任务
+    fn cancel(&self, job_id: u64) -> Result<(), CancelError>;
+
+    /// 统计计数
+    fn stats(&self) -> StatsSnapshot;
+
+    /// 任务当前的有效优先级
+    fn effective_priority(&self, job: &Job) -> u32 {
+        job.priority
+    }
+
+    /// 队伍配额，不支持配额时为 None
+    fn quota(&self) -> Option<&QuotaManager> {
+        None
+    }
+
+    /// 重置配额计数，`team_name` 为 None 时重置所有队伍；只在 `quota` 不为 None 时调用
+    fn reset_quota(&self, _team_name: Option<&str>) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    fn mode(&self) -> SpoolMode {
+        SpoolMode::Running
+    }
+
+    /// 暂停打印，不支持时返回当前模式
+    fn pause(&self) -> Result<(), SpoolMode> {
+        Err(self.mode())
+    }
+
+    /// 恢复打印，不支持时返回当前模式
+    fn resume(&self) -> Result<(), SpoolMode> {
+        Err(self.mode())
+    }
+
+    /// 开始排空，不支持时返回当前模式
+    fn start_drain(&self) -> Result<(), SpoolMode> {
+        Err(self.mode())
+    }
+
+    /// 阻塞直到剩余任务处理完，`timeout` 为 None 时一直等待
+    fn drain(&self, timeout: Option<Duration>) -> SpoolMode;
+}
+
+/// 启动时选择的假脱机系统
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpoolerKind {
+    #[default]
+    Spooling, // 输入输出井 + 缓冲区
+    NoSpooling, // 直接交给打印机，打印机忙时提交失败
+    Compare,    // 同时运行两种模式，对比接收率和延迟
+}
+
+impl FromStr for SpoolerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "spooling" => Ok(SpoolerKind::Spooling),
+            "no_spooling" | "nospooling" => Ok(SpoolerKind::NoSpooling),
+            "compare" => Ok(SpoolerKind::Compare),
+            _ => Err(format!(
+                "未知的假脱机模式 {}（可选 spooling、no_spooling、compare）",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SpoolerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpoolerKind::Spooling => write!(f, "spooling"),
+            SpoolerKind::NoSpooling => write!(f, "no_spooling"),
+            SpoolerKind::Compare => write!(f, "compare"),
+        }
+    }
+}
+
+/// 假脱机系统配置（spooler.json），命令行参数 --mode、--policy 优先
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SpoolerConfig {
+    #[serde(default)]
+    pub mode: SpoolerKind,
+    #[serde(default)]
+    pub policy: PolicyKind, // 井的调度策略，默认严格优先级
+}
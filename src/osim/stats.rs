@@ -0,0 +1,198 @@
+use crate::job::{Job, JobStatus};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// 统计计数的快照，用于接口返回和日志压缩
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatsSnapshot {
+    pub submitted: usize, // 收到的提交请求
+    pub accepted: usize,  // 被系统接收的任务
+    pub rejected: usize,  // 被拒绝的提交（配额、缓冲区满、不接收任务等）
+    pub printed: usize,   // 打印完成
+    pub failed: usize,    // 打印失败
+    pub cancelled: usize, // 已取消
+    pub pages: usize,     // 被接收任务的估算页数
+    pub bytes: usize,     // 被接收任务的内容字节数
+}
+
+/// 每个假脱机系统各自持有的统计计数
+#[derive(Debug, Default)]
+pub struct SpoolStats {
+    submitted: AtomicUsize,
+    accepted: AtomicUsize,
+    rejected: AtomicUsize,
+    printed: AtomicUsize,
+    failed: AtomicUsize,
+    cancelled: AtomicUsize,
+    pages: AtomicUsize,
+    bytes: AtomicUsize,
+}
+
+impl SpoolStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            submitted: self.submitted.load(Ordering::SeqCst),
+            accepted: self.accepted.load(Ordering::SeqCst),
+            rejected: self.rejected.load(Ordering::SeqCst),
+            printed: self.printed.load(Ordering::SeqCst),
+            failed: self.failed.load(Ordering::SeqCst),
+            cancelled: self.cancelled.load(Ordering::SeqCst),
+            pages: self.pages.load(Ordering::SeqCst),
+            bytes: self.bytes.load(Ordering::SeqCst),
+        }
+    }
+
+    /// 用快照覆盖当前计数（重放日志时使用）
+    pub fn restore(&self, snapshot: StatsSnapshot) {
+        self.submitted.store(snapshot.submitted, Ordering::SeqCst);
+        self.accepted.store(snapshot.accepted, Ordering::SeqCst);
+        self.rejected.store(snapshot.rejected, Ordering::SeqCst);
+        self.printed.store(snapshot.printed, Ordering::SeqCst);
+        self.failed.store(snapshot.failed, Ordering::SeqCst);
+        self.cancelled.store(snapshot.cancelled, Ordering::SeqCst);
+        self.pages.store(snapshot.pages, Ordering::SeqCst);
+        self.bytes.store(snapshot.bytes, Ordering::SeqCst);
+    }
+
+    /// 还没有生成任务就被拒绝的提交
+    pub fn reject(&self) {
+        self.submitted.fetch_add(1, Ordering::SeqCst);
+        self.rejected.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// 根据任务状态的变化更新计数，`previous` 为状态表中原来的记录
+    pub fn observe(&self, previous: Option<&Job>, job: &Job) {
+        let previous = previous.map(|job| &job.status);
+        if previous.is_some_and(|status| status.is_finished()) {
+            return;
+        }
+
+        match (previous, &job.status) {
+            (None, JobStatus::SubmitFailed) => self.reject(),
+            (None, JobStatus::Waiting) => {
+                self.submitted.fetch_add(1, Ordering::SeqCst);
+                self.accepted.fetch_add(1, Ordering::SeqCst);
+                self.pages.fetch_add(job.page_count(), Ordering::SeqCst);
+                self.bytes.fetch_add(job.file_content.len(), Ordering::SeqCst);
+            }
+            // 进入系统后又没能放进缓冲区，改记为被拒绝
+            (Some(_), JobStatus::SubmitFailed) => {
+                self.accepted.fetch_sub(1, Ordering::SeqCst);
+                self.pages.fetch_sub(job.page_count(), Ordering::SeqCst);
+                self.bytes.fetch_sub(job.file_content.len(), Ordering::SeqCst);
+                self.rejected.fetch_add(1, Ordering::SeqCst);
+            }
+            (Some(_), JobStatus::Completed) => {
+                self.printed.fetch_add(1, Ordering::SeqCst);
+            }
+            (Some(_), JobStatus::Failed { .. }) => {
+                self.failed.fetch_add(1, Ordering::SeqCst);
+            }
+            (Some(_), JobStatus::Cancelled) => {
+                self.cancelled.fetch_add(1, Ordering::SeqCst);
+            }
+            // 已从状态表删除的任务不再计数
+            (None, _) => {}
+            (Some(_), JobStatus::Waiting | JobStatus::Printing) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn job(lines: usize) -> Job {
+        let content = vec!["x"; lines].join("\n");
+        Job::new(1, 1, "team1".to_string(), Utc::now(), content, false, "A".to_string())
+    }
+
+    fn with_status(job: &Job, status: JobStatus) -> Job {
+        let mut job = job.clone();
+        job.status = status;
+        job
+    }
+
+    #[test]
+    fn counts_accepted_pages_and_bytes() {
+        let stats = SpoolStats::new();
+        let short = job(10);
+        let long = job(130);
+        stats.observe(None, &short);
+        stats.observe(None, &long);
+        stats.reject();
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.submitted, 3);
+        assert_eq!(snapshot.accepted, 2);
+        assert_eq!(snapshot.rejected, 1);
+        assert_eq!(snapshot.pages, 1 + 3);
+        assert_eq!(snapshot.bytes, short.file_content.len() + long.file_content.len());
+    }
+
+    #[test]
+    fn each_job_finishes_once() {
+        let stats = SpoolStats::new();
+        let waiting = job(1);
+        let printing = with_status(&waiting, JobStatus::Printing);
+        let completed = with_status(&waiting, JobStatus::Completed);
+        stats.observe(None, &waiting);
+        stats.observe(Some(&waiting), &printing);
+        stats.observe(Some(&printing), &completed);
+        // 已结束的任务再次更新（例如取消已完成的任务）不重复计数
+        stats.observe(Some(&completed), &with_status(&waiting, JobStatus::Cancelled));
+
+        let failed = with_status(&waiting, JobStatus::Failed { reason: "卡纸".to_string() });
+        stats.observe(Some(&waiting), &failed);
+        stats.observe(Some(&waiting), &with_status(&waiting, JobStatus::Cancelled));
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.accepted, 1);
+        assert_eq!(snapshot.printed, 1);
+        assert_eq!(snapshot.failed, 1);
+        assert_eq!(snapshot.cancelled, 1);
+    }
+
+    #[test]
+    fn submit_failure_after_acceptance_is_counted_as_rejected() {
+        let stats = SpoolStats::new();
+        let waiting = job(70);
+        stats.observe(None, &waiting);
+        stats.observe(Some(&waiting), &with_status(&waiting, JobStatus::SubmitFailed));
+        stats.observe(None, &with_status(&waiting, JobStatus::SubmitFailed));
+
+        assert_eq!(
+            stats.snapshot(),
+            StatsSnapshot {
+                submitted: 2,
+                rejected: 2,
+                ..StatsSnapshot::default()
+            }
+        );
+    }
+
+    #[test]
+    fn restore_replaces_counters() {
+        let stats = SpoolStats::new();
+        stats.reject();
+        let snapshot = StatsSnapshot {
+            submitted: 5,
+            accepted: 4,
+            rejected: 1,
+            printed: 2,
+            failed: 1,
+            cancelled: 1,
+            pages: 9,
+            bytes: 300,
+        };
+        stats.restore(snapshot);
+        assert_eq!(stats.snapshot(), snapshot);
+        // 每个统计对象的计数互不影响
+        assert_eq!(SpoolStats::new().snapshot(), StatsSnapshot::default());
+    }
+}
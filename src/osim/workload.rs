@@ -0,0 +1,366 @@
+use crate::highlight::Language;
+use crate::job::Document;
+use crate::osim::SPOOLing::rawJob;
+use crate::osim::sim::sim_epoch;
+use crate::rng::Rng;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// 一段时间内到达率放大，例如比赛结束前的提交高峰
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Burst {
+    pub start_secs: u64,
+    pub duration_secs: u64,
+    pub multiplier: f64,
+}
+
+/// 文件长度（行数）：均值为 `mean` 的指数分布，截断到 [min, max]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineDistribution {
+    pub min: usize,
+    pub mean: f64,
+    pub max: usize,
+}
+
+/// 负载生成配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkloadConfig {
+    pub seed: u64,
+    pub duration_secs: u64, // 在 [0, duration_secs) 内生成到达
+    pub rate_per_min: f64,  // 泊松到达的平均速率
+    pub bursts: Vec<Burst>, // 到达率放大的时段
+    pub teams: usize,       // 队伍数
+    pub team_skew: f64,     // 队伍提交量的 Zipf 指数，0 表示均匀
+    pub lines: LineDistribution,
+    pub line_width: usize, // 每行的最大字符数
+    pub color_ratio: f64,  // 彩色任务占比
+    pub priorities: u32,   // 优先级取值 0..priorities
+    pub problems: usize,   // 题目数
+}
+
+impl Default for WorkloadConfig {
+    fn default() -> Self {
+        WorkloadConfig {
+            seed: 42,
+            duration_secs: 3600,
+            rate_per_min: 10.0,
+            bursts: Vec::new(),
+            teams: 20,
+            team_skew: 0.0,
+            lines: LineDistribution {
+                min: 1,
+                mean: 60.0,
+                max: 600,
+            },
+            line_width: 80,
+            color_ratio: 0.2,
+            priorities: 3,
+            problems: 12,
+        }
+    }
+}
+
+impl WorkloadConfig {
+    /// 某一时刻的到达率放大倍数，多个高峰重叠时取最大
+    fn multiplier_at(&self, secs: f64) -> f64 {
+        self.bursts
+            .iter()
+            .filter(|burst| {
+                let start = burst.start_secs as f64;
+                secs >= start && secs < start + burst.duration_secs as f64
+            })
+            .map(|burst| burst.multiplier)
+            .fold(1.0, f64::max)
+    }
+}
+
+/// 生成按到达时间排序的任务流。到达过程为非齐次泊松过程，用稀疏化方法生成
+pub fn generate(config: &WorkloadConfig) -> Vec<rawJob> {
+    let mut rng = Rng::new(config.seed);
+    let peak = config
+        .bursts
+        .iter()
+        .map(|burst| burst.multiplier)
+        .fold(1.0, f64::max);
+    let peak_rate = config.rate_per_min * peak / 60.0; // 每秒
+    if peak_rate <= 0.0 || config.teams == 0 {
+        return Vec::new();
+    }
+    let team_weights: Vec<f64> = (1..=config.teams)
+        .map(|rank| 1.0 / (rank as f64).powf(config.team_skew))
+        .collect();
+
+    let mut jobs = Vec::new();
+    let mut secs = 0.0;
+    loop {
+        secs += rng.exponential(1.0 / peak_rate);
+        if secs >= config.duration_secs as f64 {
+            break;
+        }
+        if rng.next_f64() * peak >= config.multiplier_at(secs) {
+            continue;
+        }
+
+        let team = rng.weighted(&team_weights);
+        let lines = (rng.exponential(config.lines.mean).round() as usize)
+            .clamp(config.lines.min.max(1), config.lines.max.max(1));
+        let file_content = (0..lines)
+            .map(|line| {
+                let width = rng.range(0, config.line_width.max(1));
+                let text: String = (0..width)
+                    .map(|_| (b'a' + rng.range(0, 25) as u8) as char)
+                    .collect();
+                format!("{:>4} {}", line + 1, text)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        jobs.push(rawJob {
+            priority: rng.range(0, config.priorities.max(1) as usize - 1) as u32,
+            team_name: format!("team-{:02}", team + 1),
+            submit_time: sim_epoch() + Duration::milliseconds((secs * 1000.0) as i64),
+            file_content,
+            color: rng.next_f64() < config.color_ratio,
+            problem_name: ((b'A' + rng.range(0, config.problems.clamp(1, 26) - 1) as u8) as char)
+                .to_string(),
+            document: None,
+            language: None,
+        });
+    }
+    jobs
+}
+
+/// 轨迹文件中的一条提交记录（JSON Lines，每行一条）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceRecord {
+    pub time: DateTime<Utc>,
+    pub priority: u32,
+    pub team_name: String,
+    pub problem_name: String,
+    pub color: bool,
+    pub file_content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pages: Option<usize>, // 附带 PDF 文档（IPP、LPD 提交）时为文档页数，文档本身不记录
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<Language>,
+}
+
+impl From<&rawJob> for TraceRecord {
+    fn from(job: &rawJob) -> Self {
+        TraceRecord {
+            time: job.submit_time,
+            priority: job.priority,
+            team_name: job.team_name.clone(),
+            problem_name: job.problem_name.clone(),
+            color: job.color,
+            file_content: job.file_content.clone(),
+            pages: job.document.as_ref().map(|document| document.pages),
+            language: job.language,
+        }
+    }
+}
+
+/// 记录真实的 /print 请求
+pub struct TraceRecorder {
+    file: Mutex<File>,
+}
+
+impl TraceRecorder {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(TraceRecorder {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn record(&self, job: &rawJob) -> io::Result<()> {
+        let mut line = serde_json::to_vec(&TraceRecord::from(job))?;
+        line.push(b'\n');
+        self.file.lock().unwrap().write_all(&line)
+    }
+}
+
+/// 把任务流写成轨迹文件
+pub fn save_trace(path: impl AsRef<Path>, jobs: &[rawJob]) -> io::Result<()> {
+    let mut content = Vec::new();
+    for job in jobs {
+        serde_json::to_writer(&mut content, &TraceRecord::from(job))?;
+        content.push(b'\n');
+    }
+    fs::write(path, content)
+}
+
+/// 读取轨迹文件，保持相邻提交的时间间隔，整体平移到模拟起始时间。
+/// 记录了文档页数的提交重放为同样页数的文档任务（模拟只用到页数，文档路径为空）
+pub fn load_trace(path: impl AsRef<Path>) -> io::Result<Vec<rawJob>> {
+    let mut records = Vec::new();
+    for (index, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: TraceRecord = serde_json::from_str(&line).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("第 {} 行解析失败：{}", index + 1, e),
+            )
+        })?;
+        records.push(record);
+    }
+    records.sort_by_key(|record| record.time);
+
+    let Some(start) = records.first().map(|record| record.time) else {
+        return Ok(Vec::new());
+    };
+    Ok(records
+        .into_iter()
+        .map(|record| rawJob {
+            priority: record.priority,
+            team_name: record.team_name,
+            submit_time: sim_epoch() + (record.time - start),
+            file_content: record.file_content,
+            color: record.color,
+            problem_name: record.problem_name,
+            document: record.pages.map(|pages| Document {
+                path: String::new(),
+                pages,
+            }),
+            language: record.language,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(seed: u64) -> WorkloadConfig {
+        WorkloadConfig {
+            seed,
+            duration_secs: 1800,
+            rate_per_min: 6.0,
+            bursts: vec![Burst {
+                start_secs: 1200,
+                duration_secs: 300,
+                multiplier: 5.0,
+            }],
+            teams: 10,
+            team_skew: 1.5,
+            lines: LineDistribution {
+                min: 5,
+                mean: 40.0,
+                max: 100,
+            },
+            ..WorkloadConfig::default()
+        }
+    }
+
+    fn records(jobs: &[rawJob]) -> Vec<String> {
+        jobs.iter()
+            .map(|job| serde_json::to_string(&TraceRecord::from(job)).unwrap())
+            .collect()
+    }
+
+    fn scratch_file(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("spooling-{}-{}.jsonl", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn generator_is_seeded() {
+        let jobs = generate(&config(7));
+        assert_eq!(records(&jobs), records(&generate(&config(7))));
+        assert_ne!(records(&jobs), records(&generate(&config(8))));
+    }
+
+    #[test]
+    fn generator_follows_rate_bursts_and_skew() {
+        let jobs = generate(&config(7));
+        let secs = |job: &rawJob| (job.submit_time - sim_epoch()).num_seconds();
+        assert!(jobs.windows(2).all(|pair| pair[0].submit_time <= pair[1].submit_time));
+        assert!(jobs.iter().all(|job| (0..1800).contains(&secs(job))));
+
+        // 高峰前 20 分钟平均每分钟 6 个，高峰 5 分钟内每分钟约 30 个
+        let before = jobs.iter().filter(|job| secs(job) < 1200).count();
+        let burst = jobs.iter().filter(|job| (1200..1500).contains(&secs(job))).count();
+        assert!((80..160).contains(&before), "高峰前 {} 个", before);
+        assert!((100..200).contains(&burst), "高峰中 {} 个", burst);
+
+        // Zipf 分布下排名第一的队伍提交最多
+        let count = |team: &str| jobs.iter().filter(|job| job.team_name == team).count();
+        assert!(count("team-01") > count("team-02"));
+        assert!(count("team-01") > 3 * count("team-10"));
+
+        for job in &jobs {
+            assert!((5..=100).contains(&job.file_content.lines().count()));
+            assert!(job.priority < 3);
+            assert!(job.problem_name.as_str() >= "A" && job.problem_name.as_str() <= "L");
+        }
+    }
+
+    #[test]
+    fn trace_round_trip_keeps_submissions() {
+        let path = scratch_file("trace");
+        let mut jobs = generate(&WorkloadConfig {
+            duration_secs: 600,
+            ..config(3)
+        });
+        // IPP 或 LPD 提交的 PDF 只记录页数
+        jobs[0].document = Some(Document {
+            path: "./documents/ipp_1.pdf".to_string(),
+            pages: 12,
+        });
+        jobs[1].language = Some(Language::Rust);
+        save_trace(&path, &jobs).unwrap();
+
+        // 重放时第一次提交平移到模拟起始时间
+        let loaded = load_trace(&path).unwrap();
+        let first = jobs[0].submit_time;
+        for job in &mut jobs {
+            job.submit_time = sim_epoch() + (job.submit_time - first);
+        }
+        assert_eq!(records(&loaded), records(&jobs));
+        assert_eq!(loaded[0].page_count(), 12);
+        assert_eq!(loaded[1].language, Some(Language::Rust));
+        assert!(loaded[1].document.is_none());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn recorded_trace_is_shifted_to_the_sim_epoch() {
+        let path = scratch_file("recorded");
+        let recorder = TraceRecorder::open(&path).unwrap();
+        let start = Utc::now();
+        let job = |secs: i64, pages: Option<usize>| rawJob {
+            priority: 1,
+            team_name: "team1".to_string(),
+            submit_time: start + Duration::seconds(secs),
+            file_content: String::new(),
+            color: false,
+            problem_name: "A".to_string(),
+            document: pages.map(|pages| Document {
+                path: "./documents/lpd_1.pdf".to_string(),
+                pages,
+            }),
+            language: None,
+        };
+        // 到达顺序被打乱时按时间排序
+        recorder.record(&job(30, None)).unwrap();
+        recorder.record(&job(10, Some(4))).unwrap();
+        drop(recorder);
+
+        let loaded = load_trace(&path).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].submit_time, sim_epoch());
+        assert_eq!(loaded[1].submit_time, sim_epoch() + Duration::seconds(20));
+        assert_eq!(loaded[0].page_count(), 4);
+        assert_eq!(loaded[1].page_count(), 1);
+        let _ = fs::remove_file(&path);
+    }
+}
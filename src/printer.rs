@@ -1,112 +1,874 @@
+use crate::backend::{BackendConfig, PrintControl, PrintOutcome, PrinterBackend};
+use crate::clock::Clock;
 use crate::job::{Job};
+use crate::rng::Rng;
 
-use genpdf::*;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
 use std::thread;
 use std::time::Duration;
 
-#[derive(Debug, PartialEq, Eq)]
+//后端能报告状态时查询打印机状态的间隔
+const STATE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+//打印机在任务开始打印、打印完成或失败时回调，参数为更新状态后的任务
+pub type PrintCallback = Arc<dyn Fn(&Job) + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum PrinterStatus {
     Free=0,   // 等待打印
     Printing=1,  // 正在打印
+    Offline=2,   // 离线
+    PaperOut=3,  // 缺纸
+    Jammed=4,    // 卡纸
+    Error=5,     // 其他故障
+}
+
+//可注入的打印机故障，故障期间打印机不接收任务，正在打印的任务被中断
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PrinterFault {
+    Offline,
+    PaperOut,
+    Jammed,
+    Error,
+}
+
+impl PrinterFault {
+    pub const ALL: [PrinterFault; 4] = [
+        PrinterFault::Offline,
+        PrinterFault::PaperOut,
+        PrinterFault::Jammed,
+        PrinterFault::Error,
+    ];
+}
+
+impl From<PrinterFault> for PrinterStatus {
+    fn from(fault: PrinterFault) -> Self {
+        match fault {
+            PrinterFault::Offline => PrinterStatus::Offline,
+            PrinterFault::PaperOut => PrinterStatus::PaperOut,
+            PrinterFault::Jammed => PrinterStatus::Jammed,
+            PrinterFault::Error => PrinterStatus::Error,
+        }
+    }
+}
+
+impl fmt::Display for PrinterFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrinterFault::Offline => write!(f, "离线"),
+            PrinterFault::PaperOut => write!(f, "缺纸"),
+            PrinterFault::Jammed => write!(f, "卡纸"),
+            PrinterFault::Error => write!(f, "故障"),
+        }
+    }
+}
+
+//按计划注入的故障，时间从打印机池创建时算起
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledFault {
+    pub at_secs: f64,
+    pub duration_secs: f64,
+    pub fault: PrinterFault,
+}
+
+//随机故障：两次故障的间隔和每次的修复时间都服从指数分布
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RandomFaults {
+    pub mean_interval_secs: f64,
+    pub mean_repair_secs: f64,
+    #[serde(default)]
+    pub kinds: Vec<PrinterFault>, // 为空时从所有故障中选择
+}
+
+//打印机的故障注入配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FaultConfig {
+    #[serde(default)]
+    pub schedule: Vec<ScheduledFault>,
+    #[serde(default)]
+    pub random: Option<RandomFaults>,
+}
+
+impl FaultConfig {
+    pub fn is_empty(&self) -> bool {
+        self.schedule.is_empty() && self.random.is_none()
+    }
+
+    //按开始时间依次给出故障时段，随机故障由 `rng` 生成
+    pub fn plan(&self, rng: Rng) -> FaultPlan {
+        let mut scheduled: Vec<FaultWindow> = self
+            .schedule
+            .iter()
+            .map(|fault| FaultWindow {
+                start: Duration::from_secs_f64(fault.at_secs.max(0.0)),
+                duration: Duration::from_secs_f64(fault.duration_secs.max(0.0)),
+                fault: fault.fault,
+            })
+            .collect();
+        scheduled.sort_by_key(|window| window.start);
+        let mut plan = FaultPlan {
+            scheduled: scheduled.into_iter().peekable(),
+            random: self.random.clone(),
+            next_random: None,
+            rng,
+        };
+        plan.next_random = plan.random_after(Duration::ZERO);
+        plan
+    }
+}
+
+//一次故障的时段
+#[derive(Debug, Clone, Copy)]
+pub struct FaultWindow {
+    pub start: Duration,
+    pub duration: Duration,
+    pub fault: PrinterFault,
+}
+
+//故障时段序列，有随机故障时没有尽头
+pub struct FaultPlan {
+    scheduled: std::iter::Peekable<std::vec::IntoIter<FaultWindow>>,
+    random: Option<RandomFaults>,
+    next_random: Option<FaultWindow>,
+    rng: Rng,
+}
+
+impl FaultPlan {
+    //`after` 之后的下一次随机故障
+    fn random_after(&mut self, after: Duration) -> Option<FaultWindow> {
+        let random = self.random.as_ref()?;
+        if random.mean_interval_secs <= 0.0 {
+            return None;
+        }
+        let kinds: &[PrinterFault] = if random.kinds.is_empty() {
+            &PrinterFault::ALL
+        } else {
+            &random.kinds
+        };
+        let interval = self.rng.exponential(random.mean_interval_secs);
+        let repair = self.rng.exponential(random.mean_repair_secs.max(0.0));
+        let fault = kinds[self.rng.range(0, kinds.len() - 1)];
+        Some(FaultWindow {
+            start: after + Duration::from_secs_f64(interval),
+            duration: Duration::from_secs_f64(repair),
+            fault,
+        })
+    }
+}
+
+impl Iterator for FaultPlan {
+    type Item = FaultWindow;
+
+    fn next(&mut self) -> Option<FaultWindow> {
+        let take_random = match (self.scheduled.peek(), &self.next_random) {
+            (Some(scheduled), Some(random)) => random.start < scheduled.start,
+            (None, Some(_)) => true,
+            (_, None) => false,
+        };
+        if !take_random {
+            return self.scheduled.next();
+        }
+        let window = self.next_random.take()?;
+        self.next_random = self.random_after(window.start + window.duration);
+        Some(window)
+    }
+}
+
+//模拟打印速度：耗时 = 预热 + 页数 × 每页耗时，再乘以 [1 - jitter, 1 + jitter] 内的随机倍数
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrintSpeed {
+    pub warmup_ms: u64,     // 每份任务的预热时间
+    pub mono_page_ms: u64,  // 黑白每页耗时
+    pub color_page_ms: u64, // 彩色每页耗时
+    pub jitter: f64,        // 随机抖动比例，0 表示没有抖动
+}
+
+impl Default for PrintSpeed {
+    //一页黑白任务耗时 5 秒
+    fn default() -> Self {
+        PrintSpeed {
+            warmup_ms: 3000,
+            mono_page_ms: 2000,
+            color_page_ms: 3000,
+            jitter: 0.0,
+        }
+    }
+}
+
+impl PrintSpeed {
+    //打印耗时，`sample` 为 [0, 1) 上的均匀随机数
+    pub fn duration(&self, pages: usize, color: bool, sample: f64) -> Duration {
+        let page_ms = if color { self.color_page_ms } else { self.mono_page_ms };
+        let base = self.warmup_ms as f64 + page_ms as f64 * pages as f64;
+        let factor = 1.0 + self.jitter.clamp(0.0, 1.0) * (2.0 * sample - 1.0);
+        Duration::from_millis((base * factor).round() as u64)
+    }
+}
+
+//打印机的静态信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrinterConfig {
+    pub name: String,
+    pub color: bool,      // 是否支持彩色
+    pub location: String,
+    #[serde(default)]
+    pub speed: PrintSpeed,
+    #[serde(default)]
+    pub faults: FaultConfig,
+    #[serde(default)]
+    pub backend: BackendConfig,
+}
+
+//打印机状态快照，用于状态接口
+#[derive(Debug, Serialize)]
+pub struct PrinterInfo {
+    pub name: String,
+    pub color: bool,
+    pub location: String,
+    pub status: PrinterStatus,
+    pub printed_count: usize,
+    pub backend: String,
+}
+
+//打印机空闲通知：每台打印机打印结束时递增计数并唤醒等待者
+#[derive(Default)]
+pub struct IdleSignal {
+    generation: Mutex<u64>,
+    cond: Condvar,
+}
+
+impl IdleSignal {
+    //当前计数，等待前先读取，避免错过在检查之后发生的通知
+    pub fn generation(&self) -> u64 {
+        *self.generation.lock().unwrap()
+    }
+
+    pub fn notify(&self) {
+        *self.generation.lock().unwrap() += 1;
+        self.cond.notify_all();
+    }
+
+    //阻塞直到计数不再等于 `seen`
+    pub fn wait_changed(&self, seen: u64) {
+        let mut generation = self.generation.lock().unwrap();
+        while *generation == seen {
+            generation = self.cond.wait(generation).unwrap();
+        }
+    }
 }
 
 //记录打印机状态
 pub struct Printer {
-    status:AtomicUsize,
+    config: PrinterConfig,
+    status: Mutex<PrinterStatus>,
     printed_count:AtomicUsize,
+    idle: Arc<IdleSignal>,
+    current: Mutex<Option<(usize, Arc<AtomicBool>)>>, // 正在打印的任务id及其取消标记
+    clock: Arc<dyn Clock>,
+    rng: Mutex<Rng>, // 打印耗时的随机抖动
+    fault: Mutex<Option<PrinterFault>>,
+    backend: Box<dyn PrinterBackend>,
 }
 
 impl Printer{
     
-    //初始时打印机为空，可以打印东西
-    pub fn new()->Self{
+    //初始时打印机为空，可以打印东西；打印结束时通过 `idle` 通知，打印耗时和时间戳取自 `clock`
+    pub fn new(config: PrinterConfig, idle: Arc<IdleSignal>, clock: Arc<dyn Clock>)->Self{
+        let seed = clock.now().timestamp_nanos_opt().unwrap_or_default() as u64
+            ^ config.name.bytes().fold(0u64, |hash, byte| hash.wrapping_mul(31).wrapping_add(u64::from(byte)));
         Self{
-            status: AtomicUsize::new(PrinterStatus::Free as usize),
+            backend: config.backend.build().expect("打印机后端配置在加载时已检查"),
+            config,
+            status: Mutex::new(PrinterStatus::Free),
             printed_count:AtomicUsize::new(0),
+            idle,
+            current: Mutex::new(None),
+            clock,
+            rng: Mutex::new(Rng::new(seed)),
+            fault: Mutex::new(None),
         }
     }
 
-    //读取打印机工作状态
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    pub fn is_color(&self) -> bool {
+        self.config.color
+    }
+
+    //按本打印机的速度计算一份任务的打印耗时
+    pub fn print_time(&self, job: &Job) -> Duration {
+        let sample = self.rng.lock().unwrap().next_f64();
+        self.config.speed.duration(job.page_count(), job.color, sample)
+    }
+
+    pub fn printed_count(&self) -> usize {
+        self.printed_count.load(Ordering::SeqCst)
+    }
+
+    pub fn info(&self) -> PrinterInfo {
+        PrinterInfo {
+            name: self.config.name.clone(),
+            color: self.config.color,
+            location: self.config.location.clone(),
+            status: self.get_status(),
+            printed_count: self.printed_count(),
+            backend: self.backend.describe(),
+        }
+    }
+
+    //读取打印机工作状态，有故障时返回故障
     pub fn get_status(&self) -> PrinterStatus {
-        match self.status.load(Ordering::SeqCst) {
-            0 => PrinterStatus::Free,
-            1 => PrinterStatus::Printing,
-            _ => panic!("Unknown printer status"),
+        let status = self.status.lock().unwrap();
+        self.fault().map_or(*status, PrinterStatus::from)
+    }
+
+    pub fn fault(&self) -> Option<PrinterFault> {
+        *self.fault.lock().unwrap()
+    }
+
+    //注入故障：不再接收任务，正在打印的任务被中断后交回假脱机系统
+    pub fn inject_fault(&self, fault: PrinterFault) {
+        println!("[WARN] 打印机 {} {}", self.config.name, fault);
+        *self.fault.lock().unwrap() = Some(fault);
+    }
+
+    //排除故障，通知等待空闲打印机的分派者
+    pub fn clear_fault(&self) {
+        if self.fault.lock().unwrap().take().is_some() {
+            println!("[INFO] 打印机 {} 恢复正常", self.config.name);
+            self.idle.notify();
         }
     }
 
-    pub fn set_status(&mut self, new_status: PrinterStatus) {
-        self.status.store(new_status as usize, Ordering::SeqCst);
+    //后端能报告打印机状态时，在后台线程中定期查询并同步为故障状态。
+    //只排除自己同步的故障，不覆盖手动或按计划注入的故障
+    pub fn start_state_monitor(self: &Arc<Self>) {
+        if !self.backend.reports_state() {
+            return;
+        }
+        let printer = Arc::clone(self);
+        thread::spawn(move || {
+            let mut reported = None;
+            loop {
+                let fault = printer.backend.query_fault();
+                if fault != reported {
+                    let current = printer.fault();
+                    if current.is_none() || current == reported {
+                        match fault {
+                            Some(fault) => printer.inject_fault(fault),
+                            None => printer.clear_fault(),
+                        }
+                    }
+                    reported = fault;
+                }
+                printer.clock.sleep(STATE_POLL_INTERVAL);
+            }
+        });
     }
 
-    //模拟打印功能
-    fn print_file(&self,code: &str,file_name:&str)->Result<(),()> {
-        //加载字体
-        let font_family =
-            fonts::from_files("./fonts", "MapleMono", None).expect("Failed to load font family");
+    //按配置在后台线程中注入计划故障和随机故障
+    pub fn start_fault_injection(self: &Arc<Self>) {
+        if self.config.faults.is_empty() {
+            return;
+        }
+        let rng = Rng::new(self.rng.lock().unwrap().next_u64());
+        let plan = self.config.faults.plan(rng);
+        let printer = Arc::clone(self);
+        thread::spawn(move || {
+            let origin = printer.clock.now();
+            let sleep_until = |offset: Duration| {
+                let target = origin + chrono::Duration::from_std(offset).unwrap_or(chrono::Duration::MAX);
+                if let Ok(wait) = (target - printer.clock.now()).to_std() {
+                    printer.clock.sleep(wait);
+                }
+            };
+            for window in plan {
+                sleep_until(window.start);
+                printer.inject_fault(window.fault);
+                sleep_until(window.start + window.duration);
+                printer.clear_fault();
+            }
+        });
+    }
 
-        let mut doc = Document::new(font_family);
-        doc.set_title("Demo document");
-        
-        //设置页面样式
-        let mut decorator = genpdf::SimplePageDecorator::new();
-        decorator.set_margins(10);
-        doc.set_page_decorator(decorator);
+    pub fn set_status(&self, new_status: PrinterStatus) {
+        *self.status.lock().unwrap() = new_status;
+        if new_status == PrinterStatus::Free {
+            self.idle.notify();
+        }
+    }
 
+    //取消正在打印的任务，该任务不在本打印机上时返回 false
+    pub fn cancel(&self, job_id: usize) -> bool {
+        match &*self.current.lock().unwrap() {
+            Some((current_id, cancelled)) if *current_id == job_id => {
+                cancelled.store(true, Ordering::SeqCst);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    //交给后端打印；`cancelled` 被置位或打印机故障时尽快停止
+    fn print_file(&self, job: &Job, duration: Duration, cancelled: &AtomicBool) -> Result<PrintOutcome, String> {
+        let control = PrintControl {
+            cancelled,
+            fault: &self.fault,
+            clock: self.clock.as_ref(),
+            duration,
+        };
+        self.backend.print(job, &control)
+    }
 
-        for line in code.lines() {
-            let p = genpdf::elements::Paragraph::new(line);
-            doc.push(p);
+    //打印机空闲时接收任务并在后台线程打印，忙碌时退回任务；状态变化通过 `on_event` 通知
+    //失败时原样退回任务由调用方重试
+    pub fn submit_task(self: &Arc<Self>, job: Job, on_event: PrintCallback) -> Result<usize, Box<Job>> {
+        {
+            let mut status = self.status.lock().unwrap();
+            if *status != PrinterStatus::Free || self.fault().is_some() {
+                return Err(Box::new(job));
+            }
+            *status = PrinterStatus::Printing;
         }
 
-        doc.render_to_file(&format!("./output/{}.pdf", file_name))
-            .expect("Failed to write PDF file");
-        
-        //模拟打印，一份文件等待10s
-        thread::sleep(Duration::from_secs(5));
+        let job_id = job.job_id;
+        let mut job = job;
+        let printer_arc = Arc::clone(self);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        *self.current.lock().unwrap() = Some((job_id, cancelled.clone()));
+
+        thread::spawn(move || {
+            job.start_printing(printer_arc.clock.now());
+            on_event(&job);
+
+            let printer_arc2 = Arc::clone(&printer_arc); // ✅ clone 一份进去
+            let printing = job.clone();
+            let duration = printer_arc.print_time(&job);
+            let cancelled2 = cancelled.clone();
+            // 在单独线程中渲染，渲染 panic 时打印机也能恢复空闲
+            let print_result = thread::spawn(move || {
+                printer_arc2.print_file(&printing, duration, &cancelled2)
+            })
+            .join()
+            .unwrap_or_else(|_| Err("渲染线程异常退出".to_string()));
+
+            *printer_arc.current.lock().unwrap() = None;
+            match print_result {
+                _ if cancelled.load(Ordering::SeqCst) => {
+                    println!("打印任务 {} 已取消", job_id);
+                    job.cancel(printer_arc.clock.now());
+                }
+                Ok(PrintOutcome::Done) => {
+                    printer_arc.printed_count.fetch_add(1, Ordering::SeqCst);
+                    job.complete(printer_arc.clock.now());
+                }
+                Ok(PrintOutcome::Interrupted(fault)) => {
+                    // 任务回到等待状态，由 `on_event` 的接收方重新分派
+                    let reason = format!("打印机 {} {}", printer_arc.name(), fault);
+                    println!("[WARN] 打印任务 {} 中断：{}", job_id, reason);
+                    job.interrupt(reason, printer_arc.clock.now());
+                }
+                Err(reason) => {
+                    eprintln!("打印任务 {} 执行失败: {}", job_id, reason);
+                    job.fail(reason, printer_arc.clock.now());
+                }
+            }
+            printer_arc.set_status(PrinterStatus::Free);
+            on_event(&job);
+        });
+
+        Ok(job_id)
+    }
+}
+
+//没有空闲彩色打印机时对彩色任务的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorFallback {
+    #[default]
+    Wait,      // 等待彩色打印机空闲
+    Downgrade, // 降级为黑白，交给任意空闲打印机
+    Reject,    // 直接拒绝
+}
+
+//打印机池配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolConfig {
+    #[serde(default)]
+    pub color_fallback: ColorFallback,
+    pub printers: Vec<PrinterConfig>,
+}
 
+impl PoolConfig {
+    //检查每台打印机的后端配置
+    pub fn validate(&self) -> Result<(), String> {
+        for printer in &self.printers {
+            printer
+                .backend
+                .build()
+                .map_err(|e| format!("打印机 {}：{}", printer.name, e))?;
+        }
         Ok(())
     }
+}
 
-    pub fn submit_task(self: &Arc<Self>, job: Job) -> Result<usize, Job> {
-        let prev_status = self.status.compare_exchange(
-            PrinterStatus::Free as usize,
-            PrinterStatus::Printing as usize,
-            Ordering::SeqCst,
-            Ordering::SeqCst,
-        );
+//分派时看到的一台打印机
+#[derive(Debug, Clone, Copy)]
+pub struct Slot {
+    pub color: bool,
+    pub free: bool,
+    pub printed: usize,
+}
+
+//分派规则给出的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Route {
+    Printers { order: Vec<usize>, downgrade: bool }, // 依次尝试的打印机下标，downgrade 时降级为黑白
+    Wait,                                            // 没有合适的空闲打印机，稍后重试
+    Reject(String),                                  // 无法打印
+}
 
-        if prev_status.is_err() {
-            return Err(job);
+//分派规则，打印机池和离散事件模拟共用：彩色任务只交给彩色打印机，黑白任务优先交给黑白打印机，
+//同类中打印数量少的优先；没有空闲的彩色打印机时按 `fallback` 处理彩色任务
+pub fn route(slots: &[Slot], color: bool, fallback: ColorFallback) -> Route {
+    let free = |filter: &dyn Fn(&Slot) -> bool| {
+        let mut order: Vec<usize> = (0..slots.len())
+            .filter(|&i| slots[i].free && filter(&slots[i]))
+            .collect();
+        order.sort_by_key(|&i| slots[i].printed);
+        order
+    };
+    let candidates = |order: Vec<usize>, downgrade: bool| {
+        if order.is_empty() {
+            Route::Wait
+        } else {
+            Route::Printers { order, downgrade }
         }
+    };
 
-        let job_id = job.job_id;
-        let job_clone = job.clone();
+    if !color {
+        let mut order = free(&|slot| !slot.color);
+        order.extend(free(&|slot| slot.color));
+        return candidates(order, false);
+    }
 
-        let printer_arc = Arc::clone(self);
+    let color_printers = free(&|slot| slot.color);
+    if !color_printers.is_empty() {
+        return candidates(color_printers, false);
+    }
+    match fallback {
+        ColorFallback::Wait if slots.iter().any(|slot| slot.color) => Route::Wait,
+        ColorFallback::Wait | ColorFallback::Reject => {
+            Route::Reject("没有可用的彩色打印机".to_string())
+        }
+        ColorFallback::Downgrade => candidates(free(&|_| true), true),
+    }
+}
+
+//分派成功：实际打印的打印机；被降级为黑白的任务通过 `on_event` 写回状态表
+pub struct Dispatched {
+    pub printer: String,
+}
+
+//分派失败
+pub enum DispatchError {
+    Busy(Box<Job>),                             // 没有合适的空闲打印机，稍后重试
+    Rejected { job: Box<Job>, reason: String }, // 无法打印
+}
+
+//打印机池：登记多台打印机，按颜色能力把任务分派给空闲且负载最低的一台
+pub struct PrinterPool {
+    printers: Vec<Arc<Printer>>,
+    color_fallback: ColorFallback,
+    idle: Arc<IdleSignal>, // 池内所有打印机共享
+    clock: Arc<dyn Clock>,
+}
+
+impl PrinterPool {
+    pub fn new(config: PoolConfig, clock: Arc<dyn Clock>) -> Self {
+        let idle = Arc::new(IdleSignal::default());
+        Self {
+            printers: config
+                .printers
+                .into_iter()
+                .map(|config| Arc::new(Printer::new(config, idle.clone(), clock.clone())))
+                .collect(),
+            color_fallback: config.color_fallback,
+            idle,
+            clock,
+        }
+    }
+
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    //启动故障注入线程和打印机状态查询线程
+    pub fn start_background(&self) {
+        for printer in &self.printers {
+            printer.start_fault_injection();
+            printer.start_state_monitor();
+        }
+    }
+
+    //手动注入或排除故障（`fault` 为 None），找不到打印机时返回 false
+    pub fn set_fault(&self, name: &str, fault: Option<PrinterFault>) -> bool {
+        let Some(printer) = self.printers.iter().find(|printer| printer.name() == name) else {
+            return false;
+        };
+        match fault {
+            Some(fault) => printer.inject_fault(fault),
+            None => printer.clear_fault(),
+        }
+        true
+    }
+
+    //各打印机状态
+    pub fn info(&self) -> Vec<PrinterInfo> {
+        self.printers.iter().map(|printer| printer.info()).collect()
+    }
 
-        tokio::spawn(async move {
-            let mut res = String::new();
-            for (count, line) in job_clone.file_content.lines().enumerate() {
-                res += &format!("{:>3}: {}\n", count + 1, line);
+    //是否所有打印机都空闲
+    pub fn all_free(&self) -> bool {
+        self.printers
+            .iter()
+            .all(|printer| printer.get_status() == PrinterStatus::Free)
+    }
+
+    //依次尝试候选打印机
+    fn try_printers(&self, order: &[usize], mut job: Job, on_event: &PrintCallback) -> Result<Dispatched, Box<Job>> {
+        for &index in order {
+            let printer = &self.printers[index];
+            // 状态可能在筛选之后被其他线程改变，失败时换下一台
+            match printer.submit_task(job.clone(), on_event.clone()) {
+                Ok(_) => {
+                    return Ok(Dispatched {
+                        printer: printer.name().to_string(),
+                    });
+                }
+                Err(returned) => job = *returned,
             }
+        }
+        Err(Box::new(job))
+    }
 
-            let printer_arc2 = Arc::clone(&printer_arc); // ✅ clone 一份进去
-            let print_result = tokio::task::spawn_blocking(move || {
-                printer_arc2.print_file(&res, &job_clone.file_name)
+    //按 `route` 的规则分派任务，候选打印机都已被占用时返回 Busy
+    pub fn submit_task(&self, job: Job, on_event: PrintCallback) -> Result<Dispatched, DispatchError> {
+        let slots: Vec<Slot> = self
+            .printers
+            .iter()
+            .map(|printer| Slot {
+                color: printer.is_color(),
+                free: printer.get_status() == PrinterStatus::Free,
+                printed: printer.printed_count(),
             })
-            .await;
+            .collect();
+
+        match route(&slots, job.color, self.color_fallback) {
+            Route::Printers { order, downgrade } => {
+                let mut job = job;
+                if downgrade {
+                    job.color = false;
+                    println!("[INFO] 没有空闲的彩色打印机，Job {} 降级为黑白打印", job.job_id);
+                }
+                self.try_printers(&order, job, &on_event)
+                    .map_err(DispatchError::Busy)
+            }
+            Route::Wait => Err(DispatchError::Busy(Box::new(job))),
+            Route::Reject(reason) => Err(DispatchError::Rejected {
+                job: Box::new(job),
+                reason,
+            }),
+        }
+    }
 
-            printer_arc.status.store(PrinterStatus::Free as usize, Ordering::SeqCst);
-            printer_arc.printed_count.fetch_add(1, Ordering::SeqCst);
+    //取消正在某台打印机上打印的任务
+    pub fn cancel(&self, job_id: usize) -> bool {
+        self.printers.iter().any(|printer| printer.cancel(job_id))
+    }
 
-            if let Err(e) = print_result {
-                eprintln!("打印任务 {} 执行失败: {:?}", job_id, e);
+    //阻塞分派：没有合适的空闲打印机时等待某台打印机打印结束后重试，只在任务无法打印时失败
+    pub fn dispatch_blocking(
+        &self,
+        job: Job,
+        on_event: PrintCallback,
+    ) -> Result<Dispatched, DispatchError> {
+        let mut job = job;
+        loop {
+            let seen = self.idle.generation();
+            match self.submit_task(job, on_event.clone()) {
+                Err(DispatchError::Busy(returned)) => {
+                    job = *returned;
+                    self.idle.wait_changed(seen);
+                }
+                result => return result,
             }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock;
+    use crate::job::{JobStatus, MAX_INTERRUPTIONS};
+    use chrono::Utc;
+    use std::fs;
+    use std::path::Path;
+    use std::sync::mpsc;
+
+    fn slot(color: bool, free: bool, printed: usize) -> Slot {
+        Slot { color, free, printed }
+    }
+
+    //生成 PDF 的打印机池，每份任务耗时 `print_ms` 毫秒
+    fn pool(dir: &Path, printers: &[(&str, bool)], print_ms: u64) -> PrinterPool {
+        let printers = printers
+            .iter()
+            .map(|&(name, color)| PrinterConfig {
+                name: name.to_string(),
+                color,
+                location: String::new(),
+                speed: PrintSpeed {
+                    warmup_ms: print_ms,
+                    mono_page_ms: 0,
+                    color_page_ms: 0,
+                    jitter: 0.0,
+                },
+                faults: FaultConfig::default(),
+                backend: BackendConfig::Pdf { dir: dir.to_path_buf() },
+            })
+            .collect();
+        PrinterPool::new(
+            PoolConfig {
+                color_fallback: ColorFallback::Wait,
+                printers,
+            },
+            clock::system(),
+        )
+    }
+
+    fn job(job_id: usize, color: bool) -> Job {
+        Job::new(
+            job_id,
+            1,
+            "team1".to_string(),
+            Utc::now(),
+            "int main() {}".to_string(),
+            color,
+            "A".to_string(),
+        )
+    }
+
+    //把打印机回调的任务状态转发到通道
+    fn events() -> (PrintCallback, mpsc::Receiver<Job>) {
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let on_event: PrintCallback = Arc::new(move |job: &Job| {
+            let _ = sender.lock().unwrap().send(job.clone());
         });
+        (on_event, receiver)
+    }
 
-        Ok(job_id)
+    fn next_event(receiver: &mpsc::Receiver<Job>) -> Job {
+        receiver.recv_timeout(Duration::from_secs(10)).expect("打印机没有回调")
     }
-}
\ No newline at end of file
+
+    fn dispatched(result: Result<Dispatched, DispatchError>) -> String {
+        match result {
+            Ok(dispatched) => dispatched.printer,
+            Err(_) => panic!("任务没有被分派"),
+        }
+    }
+
+    #[test]
+    fn mono_jobs_prefer_mono_printers_with_fewest_prints() {
+        let slots = [
+            slot(true, true, 0),
+            slot(false, true, 5),
+            slot(false, false, 0),
+            slot(false, true, 2),
+        ];
+        let order = vec![3, 1, 0];
+        assert_eq!(
+            route(&slots, false, ColorFallback::Wait),
+            Route::Printers { order, downgrade: false }
+        );
+    }
+
+    #[test]
+    fn colour_jobs_only_go_to_colour_printers() {
+        let slots = [slot(false, true, 0), slot(true, true, 3), slot(true, true, 1)];
+        assert_eq!(
+            route(&slots, true, ColorFallback::Downgrade),
+            Route::Printers { order: vec![2, 1], downgrade: false }
+        );
+
+        //彩色打印机都忙时按 `fallback` 处理
+        let busy = [slot(false, true, 0), slot(true, false, 0)];
+        assert_eq!(route(&busy, true, ColorFallback::Wait), Route::Wait);
+        assert!(matches!(route(&busy, true, ColorFallback::Reject), Route::Reject(_)));
+        assert_eq!(
+            route(&busy, true, ColorFallback::Downgrade),
+            Route::Printers { order: vec![0], downgrade: true }
+        );
+
+        //没有彩色打印机时等待没有意义
+        let mono_only = [slot(false, false, 0)];
+        assert!(matches!(route(&mono_only, true, ColorFallback::Wait), Route::Reject(_)));
+    }
+
+    #[test]
+    fn pool_routes_jobs_to_capable_printers() {
+        let dir = std::env::temp_dir().join(format!("spooling-route-{}", std::process::id()));
+        let pool = pool(&dir, &[("mono", false), ("color", true)], 30_000);
+        let (on_event, _events) = events();
+
+        assert_eq!(dispatched(pool.submit_task(job(1, true), on_event.clone())), "color");
+        assert_eq!(dispatched(pool.submit_task(job(2, false), on_event.clone())), "mono");
+        //两台都在打印：彩色任务和黑白任务都要等待
+        assert!(matches!(pool.submit_task(job(3, true), on_event.clone()), Err(DispatchError::Busy(_))));
+        assert!(matches!(pool.submit_task(job(4, false), on_event), Err(DispatchError::Busy(_))));
+
+        assert!(pool.cancel(1));
+        assert!(pool.cancel(2));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn faults_interrupt_job_until_it_fails() {
+        let dir = std::env::temp_dir().join(format!("spooling-interrupt-{}", std::process::id()));
+        let pool = pool(&dir, &[("p1", false)], 30_000);
+        let (on_event, events) = events();
+
+        let mut job = job(1, false);
+        for interruptions in 1..=MAX_INTERRUPTIONS {
+            dispatched(pool.submit_task(job, on_event.clone()));
+            assert_eq!(next_event(&events).status, JobStatus::Printing);
+            assert!(pool.set_fault("p1", Some(PrinterFault::Jammed)));
+            //故障期间打印机不接收任务
+            job = next_event(&events);
+            assert_eq!(job.interruptions, interruptions);
+            assert!(matches!(
+                pool.submit_task(job.clone(), on_event.clone()),
+                Err(DispatchError::Busy(_))
+            ));
+            pool.set_fault("p1", None);
+
+            if interruptions < MAX_INTERRUPTIONS {
+                assert_eq!(job.status, JobStatus::Waiting);
+                assert_eq!(job.start_print_time, None);
+            }
+        }
+        assert!(matches!(job.status, JobStatus::Failed { .. }), "{:?}", job.status);
+        assert!(job.end_print_time.is_some());
+        assert_eq!(pool.info()[0].printed_count, 0);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,204 @@
+use crate::job::Document;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+//IPP、LPD 等打印协议入口共用的部分：确定提交的队伍，保存客户端提交的 PDF
+
+//客户端提交的 PDF 保存在这里，任务结束后删除
+pub const DOCUMENT_DIR: &str = "./documents";
+
+//单个文档的大小上限
+pub const MAX_DOCUMENT: usize = 64 * 1024 * 1024;
+
+//客户端到队伍的映射，写在 ipp.json、lpd.json 中
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TeamMapping {
+    #[serde(default)]
+    pub hosts: HashMap<String, String>, // 客户端 IP -> 队伍名，优先于用户名
+    #[serde(default)]
+    pub users: HashMap<String, String>, // 用户名 -> 队伍名
+    #[serde(default)]
+    pub require_mapping: bool, // 为 true 时拒绝未配置的客户端，否则直接用用户名（或 IP）作队伍名
+}
+
+//无法确定队伍的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TeamError {
+    Unmapped(String),    // require_mapping 时客户端没有配置
+    InvalidName(String), // 未配置的用户名含有字母、数字、下划线和连字符以外的字符
+    Unknown,             // 既没有用户名也没有客户端地址
+}
+
+impl std::fmt::Display for TeamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TeamError::Unmapped(client) => write!(f, "客户端 {} 没有对应的队伍", client),
+            TeamError::InvalidName(user) => {
+                write!(f, "用户名 {:?} 只能包含字母、数字、下划线和连字符", user)
+            }
+            TeamError::Unknown => write!(f, "无法确定提交的队伍"),
+        }
+    }
+}
+
+impl TeamMapping {
+    //先按客户端 IP，再按用户名查配置；未配置时直接使用用户名，没有用户名时使用 IP（. 和 : 换成 -）。
+    //直接使用的名称会出现在文件名中，只接受字母、数字、下划线和连字符
+    pub fn resolve(&self, peer: Option<IpAddr>, user: Option<&str>) -> Result<String, TeamError> {
+        let user = user.map(str::trim).filter(|user| !user.is_empty());
+        let host = peer.map(|peer| peer.to_canonical().to_string());
+
+        if let Some(team_name) = host.as_ref().and_then(|host| self.hosts.get(host)) {
+            return Ok(team_name.clone());
+        }
+        if let Some(team_name) = user.and_then(|user| self.users.get(user)) {
+            return Ok(team_name.clone());
+        }
+        if self.require_mapping {
+            return Err(TeamError::Unmapped(format!(
+                "{}（用户 {}）",
+                host.as_deref().unwrap_or("未知"),
+                user.unwrap_or("未知")
+            )));
+        }
+        match (user, host) {
+            (Some(user), _) if is_plain_name(user) => Ok(user.to_string()),
+            (Some(user), _) => Err(TeamError::InvalidName(user.to_string())),
+            (None, Some(host)) => Ok(host.replace(['.', ':'], "-")),
+            (None, None) => Err(TeamError::Unknown),
+        }
+    }
+}
+
+//未配置映射时作为队伍名的用户名的长度上限
+const MAX_PLAIN_NAME: usize = 64;
+
+fn is_plain_name(name: &str) -> bool {
+    name.len() <= MAX_PLAIN_NAME
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-')
+}
+
+//把提交的 PDF 写入文档目录
+pub struct DocumentStore {
+    dir: PathBuf,
+    prefix: &'static str,
+    next: AtomicU64,
+}
+
+impl DocumentStore {
+    //`prefix` 区分来源，例如 ipp、lpd
+    pub fn new(prefix: &'static str) -> Self {
+        DocumentStore {
+            dir: PathBuf::from(DOCUMENT_DIR),
+            prefix,
+            next: AtomicU64::new(0),
+        }
+    }
+
+    //测试中使用单独的目录
+    #[cfg(test)]
+    pub fn in_dir(dir: PathBuf, prefix: &'static str) -> Self {
+        DocumentStore {
+            dir,
+            prefix,
+            next: AtomicU64::new(0),
+        }
+    }
+
+    //文档目录中一个新文件的路径
+    fn next_path(&self) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+        let name = format!(
+            "{}_{}_{}.pdf",
+            self.prefix,
+            Utc::now().format("%Y%m%d_%H%M%S"),
+            self.next.fetch_add(1, Ordering::SeqCst)
+        );
+        Ok(self.dir.join(name))
+    }
+
+    pub fn store(&self, data: &[u8]) -> io::Result<Document> {
+        let path = self.next_path()?;
+        fs::write(&path, data)?;
+        Ok(Document {
+            path: path.to_string_lossy().into_owned(),
+            pages: pdf_page_count(data),
+        })
+    }
+
+    //把 `reader` 中的 `count` 字节直接写入文档目录，不在内存中缓存；数据不足时删除文件并返回错误
+    pub fn receive(&self, reader: &mut impl Read, count: u64) -> io::Result<PathBuf> {
+        let path = self.next_path()?;
+        let result = fs::File::create(&path)
+            .and_then(|mut file| io::copy(&mut reader.take(count), &mut file));
+        match result {
+            Ok(copied) if copied == count => Ok(path),
+            Ok(_) => {
+                let _ = fs::remove_file(&path);
+                Err(io::ErrorKind::UnexpectedEof.into())
+            }
+            Err(e) => {
+                let _ = fs::remove_file(&path);
+                Err(e)
+            }
+        }
+    }
+}
+
+pub fn is_pdf(data: &[u8]) -> bool {
+    data.starts_with(b"%PDF-")
+}
+
+//按 /Type /Page 对象估算页数；页面对象被压缩在对象流中时数不到，按一页计
+pub fn pdf_page_count(data: &[u8]) -> usize {
+    const TYPE: &[u8] = b"/Type";
+    let mut pages = 0;
+    let mut rest = data;
+    while let Some(index) = rest.windows(TYPE.len()).position(|window| window == TYPE) {
+        rest = &rest[index + TYPE.len()..];
+        let value = rest.trim_ascii_start();
+        if value.starts_with(b"/Page")
+            && !value.get(5).is_some_and(|byte| byte.is_ascii_alphanumeric())
+        {
+            pages += 1;
+        }
+    }
+    pages.max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unmapped_names_cannot_leave_the_output_directory() {
+        let teams = TeamMapping {
+            users: HashMap::from([("alice".to_string(), "team1".to_string())]),
+            ..TeamMapping::default()
+        };
+        let peer: Option<IpAddr> = Some("10.0.0.11".parse().unwrap());
+
+        assert_eq!(teams.resolve(peer, Some("alice")), Ok("team1".to_string()));
+        assert_eq!(teams.resolve(peer, Some(" team_2-b ")), Ok("team_2-b".to_string()));
+        for user in ["../../etc/passwd", "a/b", "a\\b", "队伍", "team.1", "x\0"] {
+            assert_eq!(
+                teams.resolve(peer, Some(user)),
+                Err(TeamError::InvalidName(user.to_string()))
+            );
+        }
+        assert_eq!(teams.resolve(peer, None), Ok("10-0-0-11".to_string()));
+        assert_eq!(
+            teams.resolve(Some("::1".parse().unwrap()), Some("")),
+            Ok("--1".to_string())
+        );
+        assert_eq!(teams.resolve(None, None), Err(TeamError::Unknown));
+    }
+}